@@ -1,4 +1,5 @@
 // One module per subcommand
+mod check;
 mod collections;
 mod generate;
 mod import;
@@ -7,8 +8,9 @@ mod show;
 
 use crate::{
     cli::{
-        collections::CollectionsCommand, generate::GenerateCommand,
-        import::ImportCommand, request::RequestCommand, show::ShowCommand,
+        check::CheckCommand, collections::CollectionsCommand,
+        generate::GenerateCommand, import::ImportCommand,
+        request::RequestCommand, show::ShowCommand,
     },
     GlobalArgs,
 };
@@ -23,6 +25,7 @@ pub enum CliCommand {
     Import(ImportCommand),
     Collections(CollectionsCommand),
     Show(ShowCommand),
+    Check(CheckCommand),
 }
 
 /// An executable subcommand. This trait isn't strictly necessary because we do
@@ -43,6 +46,7 @@ impl CliCommand {
             Self::Import(command) => command.execute(global).await,
             Self::Collections(command) => command.execute(global).await,
             Self::Show(command) => command.execute(global).await,
+            Self::Check(command) => command.execute(global).await,
         }
     }
 }
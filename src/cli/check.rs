@@ -0,0 +1,217 @@
+use crate::{
+    cli::Subcommand,
+    collection::{self, CollectionFile, Issue, IssueLocation},
+    config::Config,
+    GlobalArgs,
+};
+use anyhow::Context;
+use async_trait::async_trait;
+use clap::Parser;
+use std::{collections::HashMap, fs, path::Path, process::ExitCode};
+
+/// Check a collection for problems without sending any requests
+///
+/// Exits with a non-zero code if any problems are found, so this can be used
+/// in CI.
+#[derive(Clone, Debug, Parser)]
+pub struct CheckCommand {}
+
+#[async_trait]
+impl Subcommand for CheckCommand {
+    async fn execute(self, global: GlobalArgs) -> anyhow::Result<ExitCode> {
+        let collection_path = CollectionFile::try_path(global.file)?;
+        let config = Config::load()?;
+        let collection_file =
+            CollectionFile::load(collection_path, &config).await?;
+        let issues = collection::check(&collection_file.collection);
+
+        if issues.is_empty() {
+            println!("No issues found");
+            return Ok(ExitCode::SUCCESS);
+        }
+
+        // Cache file contents, since most issues will be in the same file
+        let mut contents: HashMap<&Path, String> = HashMap::new();
+        for issue in &issues {
+            let path = match &issue.location {
+                IssueLocation::Profile(id) => {
+                    collection_file.profile_source(id)
+                }
+                IssueLocation::Chain(id) => collection_file.chain_source(id),
+                IssueLocation::Recipe(id) => collection_file.recipe_source(id),
+            }
+            .unwrap_or(collection_file.path());
+            if !contents.contains_key(path) {
+                let content = fs::read_to_string(path)
+                    .with_context(|| format!("Error reading {path:?}"))?;
+                contents.insert(path, content);
+            }
+            let line = find_line(&contents[path], issue);
+            println!("{}:{line}: {}", path.display(), issue.message);
+        }
+        eprintln!("Found {} issue(s)", issues.len());
+        Ok(ExitCode::FAILURE)
+    }
+}
+
+/// Find the (1-indexed) line number that an issue points to in a file. The
+/// collection is parsed without position info, so this searches the source
+/// text: first for the key that defines the profile/chain/recipe, then for the
+/// issue's snippet within that definition. Falls back to the definition line,
+/// or the start of the file if the key can't be found (e.g. it was generated
+/// by a YAML merge).
+fn find_line(content: &str, issue: &Issue) -> usize {
+    let (section, id) = match &issue.location {
+        IssueLocation::Profile(id) => ("profiles", id as &str),
+        IssueLocation::Chain(id) => ("chains", id as &str),
+        IssueLocation::Recipe(id) => ("requests", id as &str),
+    };
+    let lines: Vec<&str> = content.lines().collect();
+    let Some(definition) = find_definition(&lines, section, id) else {
+        return 1;
+    };
+    let end = block_end(&lines, definition);
+
+    let snippet = issue
+        .snippet
+        .as_deref()
+        .and_then(|snippet| snippet.lines().next())
+        .filter(|snippet| !snippet.trim().is_empty());
+    let offset = snippet.and_then(|snippet| {
+        lines[definition..end]
+            .iter()
+            .position(|line| line.contains(snippet.trim()))
+    });
+    definition + offset.unwrap_or(0) + 1
+}
+
+/// Find the index of the line that defines a profile/chain/recipe. It has to
+/// be a key directly beneath the given top-level section (or for recipes,
+/// beneath a folder's `requests` within the section), so an ID that matches
+/// some other key, such as a data field or header, isn't mistaken for it.
+fn find_definition(lines: &[&str], section: &str, id: &str) -> Option<usize> {
+    let start = lines
+        .iter()
+        .position(|line| indentation(line) == 0 && is_key(line, section))?;
+    let end = block_end(lines, start);
+    (start + 1..end).find(|&index| {
+        is_key(lines[index], id)
+            && parent(lines, index)
+                .is_some_and(|parent| is_key(lines[parent], section))
+    })
+}
+
+/// Get the index *after* the last line of the block started by the given
+/// line, i.e. the next line that isn't nested beneath it
+fn block_end(lines: &[&str], start: usize) -> usize {
+    let indent = indentation(lines[start]);
+    lines[start + 1..]
+        .iter()
+        .position(|line| is_content(line) && indentation(line) <= indent)
+        .map_or(lines.len(), |offset| start + 1 + offset)
+}
+
+/// Get the index of the line that the given line is nested beneath
+fn parent(lines: &[&str], index: usize) -> Option<usize> {
+    let indent = indentation(lines[index]);
+    lines[..index]
+        .iter()
+        .rposition(|line| is_content(line) && indentation(line) < indent)
+}
+
+/// Is this line anything other than whitespace or a comment?
+fn is_content(line: &str) -> bool {
+    let line = line.trim_start();
+    !line.is_empty() && !line.starts_with('#')
+}
+
+fn indentation(line: &str) -> usize {
+    line.len() - line.trim_start().len()
+}
+
+/// Does this line define the given mapping key?
+fn is_key(line: &str, key: &str) -> bool {
+    let line = line.trim_start();
+    [key.to_owned(), format!("\"{key}\""), format!("'{key}'")]
+        .iter()
+        .any(|quoted| {
+            line.strip_prefix(quoted.as_str())
+                .is_some_and(|rest| rest.trim_start().starts_with(':'))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    const CONTENT: &str = "profiles:
+  local:
+    data:
+      host: http://localhost
+      login: admin
+
+chains:
+  host:
+    source: !request
+      recipe: login
+
+requests:
+  login: !request
+    method: POST
+    url: \"{{host}}/login\"
+    headers:
+      X-Token: \"{{chains.token}}\"
+  'logout': !request
+    method: POST
+    url: \"{{host}}/logout\"
+  # A comment
+  users: !folder
+    requests:
+      host: !request
+        method: GET
+        url: \"{{chains.token}}\"
+";
+
+    #[rstest]
+    #[case::snippet(
+        IssueLocation::Recipe("login".into()),
+        Some("{{chains.token}}"),
+        17
+    )]
+    #[case::no_snippet(IssueLocation::Recipe("login".into()), None, 13)]
+    #[case::snippet_not_found(
+        IssueLocation::Recipe("login".into()),
+        Some("{{missing}}"),
+        13
+    )]
+    // The snippet appears later in the file, but not in this recipe
+    #[case::snippet_other_recipe(
+        IssueLocation::Recipe("logout".into()),
+        Some("{{chains.token}}"),
+        18
+    )]
+    #[case::quoted(IssueLocation::Recipe("logout".into()), None, 18)]
+    #[case::folder(IssueLocation::Recipe("users".into()), None, 22)]
+    // These IDs collide with data keys, which come earlier in the file
+    #[case::nested_recipe(IssueLocation::Recipe("host".into()), None, 24)]
+    #[case::chain(IssueLocation::Chain("host".into()), None, 8)]
+    #[case::profile(IssueLocation::Profile("local".into()), None, 2)]
+    #[case::unknown_key(
+        IssueLocation::Recipe("unknown".into()),
+        Some("{{host}}"),
+        1
+    )]
+    fn test_find_line(
+        #[case] location: IssueLocation,
+        #[case] snippet: Option<&str>,
+        #[case] expected: usize,
+    ) {
+        let issue = Issue {
+            location,
+            snippet: snippet.map(String::from),
+            message: "bad".into(),
+        };
+        assert_eq!(find_line(CONTENT, &issue), expected);
+    }
+}
@@ -27,6 +27,9 @@ use tracing::warn;
 /// Exit code to return when `exit_status` flag is set and the HTTP response has
 /// an error status code
 const HTTP_ERROR_EXIT_CODE: u8 = 2;
+/// Exit code to return when any of the recipe's assertions fail. This takes
+/// precedence over [HTTP_ERROR_EXIT_CODE]
+const ASSERTION_FAILURE_EXIT_CODE: u8 = 3;
 
 /// Execute a single request, and print its response
 #[derive(Clone, Debug, Parser)]
@@ -48,7 +51,8 @@ pub struct RequestCommand {
     no_body: bool,
 
     /// Set process exit code based on HTTP response status. If the status is
-    /// <400, exit code is 0. If it's >=400, exit code is 2.
+    /// <400, exit code is 0. If it's >=400, exit code is 2. Failed assertions
+    /// always set the exit code to 3, regardless of this flag.
     #[clap(long)]
     exit_status: bool,
 
@@ -63,7 +67,13 @@ pub struct RequestCommand {
 #[derive(Clone, Debug, Parser)]
 pub struct BuildRequestCommand {
     /// ID of the recipe to render into a request
-    recipe_id: RecipeId,
+    #[clap(required_unless_present = "tag")]
+    recipe_id: Option<RecipeId>,
+
+    /// Select the recipe by tag instead of ID. Exactly one recipe must have
+    /// the tag
+    #[clap(long, conflicts_with = "recipe_id")]
+    tag: Option<String>,
 
     /// ID of the profile to pull template values from
     #[clap(long = "profile", short)]
@@ -76,6 +86,11 @@ pub struct BuildRequestCommand {
         value_parser = parse_key_val::<String, String>,
     )]
     overrides: Vec<(String, String)>,
+
+    /// List of key=value path parameter values, e.g. `--path user_id=5`.
+    /// These replace the recipe's values and are not rendered as templates
+    #[clap(long = "path", value_parser = parse_key_val::<String, String>)]
+    path: Vec<(String, String)>,
 }
 
 #[async_trait]
@@ -130,7 +145,21 @@ impl Subcommand for RequestCommand {
                 }
             }
 
-            if self.exit_status && status.as_u16() >= 400 {
+            // Report failed assertions last, so they aren't buried by the body
+            let mut assertions_failed = false;
+            for result in record.check_assertions() {
+                if let Some(failure) = &result.failure {
+                    eprintln!(
+                        "Assertion failed: {}: {failure}",
+                        result.assertion
+                    );
+                    assertions_failed = true;
+                }
+            }
+
+            if assertions_failed {
+                Ok(ExitCode::from(ASSERTION_FAILURE_EXIT_CODE))
+            } else if self.exit_status && status.as_u16() >= 400 {
                 Ok(ExitCode::from(HTTP_ERROR_EXIT_CODE))
             } else {
                 Ok(ExitCode::SUCCESS)
@@ -156,12 +185,13 @@ impl BuildRequestCommand {
     ) -> anyhow::Result<(Option<HttpEngine>, Request)> {
         let collection_path = CollectionFile::try_path(global.file)?;
         let database = Database::load()?.into_collection(&collection_path)?;
-        let collection_file = CollectionFile::load(collection_path).await?;
+        let config = Config::load()?;
+        let collection_file =
+            CollectionFile::load(collection_path, &config).await?;
         let collection = collection_file.collection;
         // Passing the HTTP engine is how we tell the template renderer that
         // it's ok to execute subrequests during render
         let http_engine = if trigger_dependencies {
-            let config = Config::load()?;
             Some(HttpEngine::new(&config, database.clone()))
         } else {
             None
@@ -177,18 +207,37 @@ impl BuildRequestCommand {
             })?;
         }
 
-        // Find recipe by ID
-        let recipe = collection
-            .recipes
-            .get_recipe(&self.recipe_id)
-            .ok_or_else(|| {
-                anyhow!(
-                    "No recipe with ID `{}`; options are: {}",
-                    self.recipe_id,
-                    collection.recipes.recipe_ids().format(", ")
-                )
-            })?
-            .clone();
+        // Find recipe by ID or tag. Clap guarantees exactly one is given
+        let recipes = &collection.recipes;
+        let recipe = match (&self.recipe_id, &self.tag) {
+            (Some(recipe_id), _) => {
+                recipes.get_recipe(recipe_id).ok_or_else(|| {
+                    anyhow!(
+                        "No recipe with ID `{recipe_id}`; options are: {}",
+                        recipes.recipe_ids().format(", ")
+                    )
+                })?
+            }
+            (None, Some(tag)) => {
+                let matches = recipes.recipes_with_tag(tag).collect_vec();
+                match matches.as_slice() {
+                    [recipe] => *recipe,
+                    [] => return Err(anyhow!("No recipes with tag `{tag}`")),
+                    _ => {
+                        return Err(anyhow!(
+                            "Multiple recipes with tag `{tag}`; pass one of \
+                            these IDs instead: {}",
+                            matches
+                                .iter()
+                                .map(|recipe| &recipe.id)
+                                .format(", ")
+                        ))
+                    }
+                }
+            }
+            (None, None) => unreachable!("Clap requires recipe ID or tag"),
+        }
+        .clone();
 
         // Build the request
         let overrides: IndexMap<_, _> = self.overrides.into_iter().collect();
@@ -201,7 +250,11 @@ impl BuildRequestCommand {
             prompter: Box::new(CliPrompter),
             recursion_count: Default::default(),
         };
-        let request = RequestBuilder::new(recipe, RecipeOptions::default())
+        let options = RecipeOptions {
+            path_overrides: self.path.into_iter().collect(),
+            ..Default::default()
+        };
+        let request = RequestBuilder::new(recipe, options)
             .build(&template_context)
             .await?;
         Ok((http_engine, request))
@@ -1,6 +1,12 @@
 use crate::{
-    cli::Subcommand, collection::CollectionFile, config::Config, db::Database,
-    util::paths::DataDirectory, GlobalArgs,
+    cli::Subcommand,
+    collection::{Collection, CollectionFile, RecipeId, RecipeTree},
+    config::Config,
+    db::Database,
+    http::Redactions,
+    template::Template,
+    util::paths::DataDirectory,
+    GlobalArgs,
 };
 use async_trait::async_trait;
 use clap::Parser;
@@ -14,7 +20,7 @@ pub struct ShowCommand {
     target: ShowTarget,
 }
 
-#[derive(Copy, Clone, Debug, clap::Subcommand)]
+#[derive(Clone, Debug, clap::Subcommand)]
 enum ShowTarget {
     /// Print the path of all directories/files that Slumber uses
     Paths,
@@ -22,6 +28,16 @@ enum ShowTarget {
     Config,
     /// Print current request collection
     Collection,
+    /// Print the ID of every recipe in the collection, one per line
+    Recipes {
+        /// Only include recipes with this tag, either on the recipe itself or
+        /// on a folder that contains it
+        #[clap(long)]
+        tag: Option<String>,
+    },
+    /// Print the JSON Schema for collection files, for use with editor
+    /// tooling such as YAML language servers
+    Schema,
 }
 
 #[async_trait]
@@ -48,16 +64,128 @@ impl Subcommand for ShowCommand {
             }
             ShowTarget::Collection => {
                 let collection_path = CollectionFile::try_path(global.file)?;
+                let config = Config::load()?;
+                let collection_file =
+                    CollectionFile::load(collection_path, &config).await?;
+                let mut collection = collection_file.collection;
+                redact_secrets(&mut collection);
+                println!("{}", to_yaml(&collection));
+            }
+            ShowTarget::Recipes { tag } => {
+                let collection_path = CollectionFile::try_path(global.file)?;
+                let config = Config::load()?;
                 let collection_file =
-                    CollectionFile::load(collection_path).await?;
-                println!("{}", to_yaml(&collection_file.collection));
+                    CollectionFile::load(collection_path, &config).await?;
+                for recipe_id in recipe_ids(
+                    &collection_file.collection.recipes,
+                    tag.as_deref(),
+                ) {
+                    println!("{recipe_id}");
+                }
+            }
+            ShowTarget::Schema => {
+                // Panic is intentional, indicates a wonky bug
+                let schema =
+                    serde_json::to_string_pretty(&Collection::schema())
+                        .expect("Error serializing");
+                println!("{schema}");
             }
         }
         Ok(ExitCode::SUCCESS)
     }
 }
 
+/// Get the ID of every recipe to list, optionally only those with a tag
+fn recipe_ids<'a>(
+    recipes: &'a RecipeTree,
+    tag: Option<&'a str>,
+) -> Vec<&'a RecipeId> {
+    match tag {
+        Some(tag) => recipes
+            .recipes_with_tag(tag)
+            .map(|recipe| &recipe.id)
+            .collect(),
+        None => recipes.recipe_ids().collect(),
+    }
+}
+
+/// Mask profile fields that were loaded from the secrets file, so they don't
+/// get printed
+fn redact_secrets(collection: &mut Collection) {
+    for profile in collection.profiles.values_mut() {
+        for field in &profile.sensitive_fields {
+            if let Some(value) = profile.data.get_mut(field) {
+                *value = Template::raw(Redactions::PLACEHOLDER.to_owned());
+            }
+        }
+    }
+}
+
 fn to_yaml<T: Serialize>(value: &T) -> String {
     // Panic is intentional, indicates a wonky bug
     serde_yaml::to_string(value).expect("Error serializing")
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::*;
+    use factori::create;
+    use indexmap::indexmap;
+    use rstest::rstest;
+
+    /// Recipes are listed if they have the tag, or are in a folder that
+    /// has it. Folders themselves are never listed
+    #[rstest]
+    #[case::all(None, &["r1", "r2", "r3"])]
+    #[case::recipe_tag(Some("public"), &["r1", "r3"])]
+    #[case::folder_tag(Some("admin"), &["r2", "r3"])]
+    #[case::unknown(Some("unknown"), &[])]
+    fn test_recipe_ids(#[case] tag: Option<&str>, #[case] expected: &[&str]) {
+        let recipes = RecipeTree::new(indexmap! {
+            "r1".into() => create!(
+                Recipe, id: "r1".into(), tags: vec!["public".into()]
+            ).into(),
+            "f1".into() => create!(
+                Folder,
+                id: "f1".into(),
+                tags: vec!["admin".into()],
+                children: indexmap! {
+                    "r2".into() => create!(Recipe, id: "r2".into()).into(),
+                    "r3".into() => create!(
+                        Recipe, id: "r3".into(), tags: vec!["public".into()]
+                    ).into(),
+                },
+            ).into(),
+        })
+        .unwrap();
+
+        let ids: Vec<&str> = recipe_ids(&recipes, tag)
+            .into_iter()
+            .map(|id| id.as_str())
+            .collect();
+        assert_eq!(ids, expected);
+    }
+
+    #[test]
+    fn test_redact_secrets() {
+        let profile = create!(
+            Profile,
+            data: indexmap! {
+                "host".into() => "http://localhost".into(),
+                "password".into() => "hunter2".into(),
+            },
+            sensitive_fields: ["password".into()].into(),
+        );
+        let mut collection = create!(
+            Collection,
+            profiles: indexmap! {profile.id.clone() => profile},
+        );
+
+        redact_secrets(&mut collection);
+        let yaml = to_yaml(&collection);
+        assert!(yaml.contains("host: http://localhost"), "{yaml}");
+        assert!(yaml.contains("password: <sensitive>"), "{yaml}");
+        assert!(!yaml.contains("hunter2"), "{yaml}");
+    }
+}
@@ -2,23 +2,36 @@
 //! possible
 
 mod cereal;
+mod check;
 mod insomnia;
 mod models;
 mod recipe_tree;
+mod schema;
 
+pub use check::*;
 pub use models::*;
 pub use recipe_tree::*;
 
-use crate::util::{parse_yaml, ResultExt};
+use crate::{
+    config::Config,
+    template::Template,
+    util::{parse_yaml, ResultExt},
+};
 use anyhow::{anyhow, Context};
+use indexmap::IndexMap;
+use itertools::Itertools;
+use serde::Deserialize;
 use std::{
-    fmt::Debug,
+    collections::{HashMap, HashSet},
+    fmt::{Debug, Display},
     fs,
     future::Future,
+    hash::Hash,
+    io, iter, mem,
     path::{Path, PathBuf},
 };
 use tokio::task;
-use tracing::{info, warn};
+use tracing::{debug, info, warn};
 
 /// The support file names to be automatically loaded as a config. We only
 /// support loading from one file at a time, so if more than one of these is
@@ -30,39 +43,100 @@ pub const CONFIG_FILES: &[&str] = &[
     ".slumber.yaml",
 ];
 
+/// Default name of the secrets file, which is loaded from the same directory
+/// as the collection file
+pub const SECRETS_FILE: &str = "slumber.secrets.yml";
+
 /// A wrapper around a request collection, to handle functionality around the
 /// file system.
 #[derive(Debug)]
 pub struct CollectionFile {
     /// Path to the file that this collection was loaded from
     path: PathBuf,
+    /// Every file that this collection was loaded from: the root file, plus
+    /// all of its imports
+    paths: Vec<PathBuf>,
+    /// Which file (index in [Self::paths]) defined each ID
+    sources: Sources,
+    /// Dotenv files loaded by profiles
+    env_files: Vec<PathBuf>,
+    /// Where to look for the secrets file. It may not exist
+    secrets_path: PathBuf,
+    /// Was the secrets file found and loaded?
+    secrets_loaded: bool,
     pub collection: Collection,
 }
 
-impl CollectionFile {
-    /// Create a new collection file with the given path and a default
-    /// collection. Useful when the collection failed to load and you want a
-    /// placeholder.
-    pub fn with_path(path: PathBuf) -> Self {
-        Self {
-            path,
-            collection: Default::default(),
-        }
-    }
+/// Index of the file that defined each profile, chain and recipe/folder ID
+#[derive(Debug, Default)]
+struct Sources {
+    profiles: HashMap<ProfileId, usize>,
+    chains: HashMap<ChainId, usize>,
+    recipes: HashMap<RecipeId, usize>,
+}
 
+impl CollectionFile {
     /// Load config from the given file. The caller is responsible for using
     /// [Self::try_path] to find the file themself. This pattern enables the
     /// TUI to start up and watch the collection file, even if it's invalid.
-    pub async fn load(path: PathBuf) -> anyhow::Result<Self> {
-        let collection = load_collection(path.clone()).await?;
-        Ok(Self { path, collection })
+    ///
+    /// If a secrets file exists, its profile data is merged into the
+    /// collection. See [SECRETS_FILE] and [Config::secrets_file].
+    pub async fn load(path: PathBuf, config: &Config) -> anyhow::Result<Self> {
+        match Self::load_partial(path, config).await {
+            (collection_file, None) => Ok(collection_file),
+            (_, Some(error)) => Err(error),
+        }
+    }
+
+    /// Load config from the given file, like [Self::load]. If loading fails,
+    /// the error is returned alongside an empty collection. The empty
+    /// collection still tracks every file that was found before the failure
+    /// (imports, env files, etc.), so they can be watched for a fix.
+    pub async fn load_partial(
+        path: PathBuf,
+        config: &Config,
+    ) -> (Self, Option<anyhow::Error>) {
+        let secrets_path = secrets_path(&path, config);
+        Self::load_with_secrets(path, secrets_path).await
+    }
+
+    async fn load_with_secrets(
+        path: PathBuf,
+        secrets_path: PathBuf,
+    ) -> (Self, Option<anyhow::Error>) {
+        let (loader, result) =
+            load_collection(path.clone(), secrets_path.clone()).await;
+        let mut collection_file = Self {
+            path,
+            paths: loader.paths,
+            sources: loader.sources,
+            env_files: loader.env_files,
+            secrets_path,
+            secrets_loaded: loader.secrets_loaded,
+            collection: loader.collection,
+        };
+        match result {
+            Ok(()) => (collection_file, None),
+            Err(error) => {
+                // Don't expose a half-loaded collection
+                collection_file.sources = Default::default();
+                collection_file.secrets_loaded = false;
+                collection_file.collection = Default::default();
+                (collection_file, Some(error))
+            }
+        }
     }
 
-    /// Reload a new collection from the same file used for this one.
+    /// Reload a new collection from the same file used for this one. Imports
+    /// are reloaded as well, so the set of files can change. See
+    /// [Self::load_partial] for how errors are returned.
     ///
     /// Returns `impl Future` to unlink the future from `&self`'s lifetime.
-    pub fn reload(&self) -> impl Future<Output = anyhow::Result<Collection>> {
-        load_collection(self.path.clone())
+    pub fn reload(
+        &self,
+    ) -> impl Future<Output = (Self, Option<anyhow::Error>)> {
+        Self::load_with_secrets(self.path.clone(), self.secrets_path.clone())
     }
 
     /// Get the path of the file that this collection was loaded from
@@ -70,6 +144,59 @@ impl CollectionFile {
         &self.path
     }
 
+    /// Get the paths of all files that make up this collection: the root
+    /// file, plus everything it imports
+    pub fn paths(&self) -> &[PathBuf] {
+        &self.paths
+    }
+
+    /// Get the paths of all dotenv files loaded by profiles
+    pub fn env_files(&self) -> &[PathBuf] {
+        &self.env_files
+    }
+
+    /// Get every file that should trigger a reload when it changes: the
+    /// collection files, env files, and the secrets file, *even if it doesn't
+    /// exist yet*. Paths are made absolute so they can be matched against
+    /// file watcher events, which always have absolute paths.
+    pub fn watch_paths(&self) -> Vec<PathBuf> {
+        self.paths()
+            .iter()
+            .chain(self.env_files())
+            .chain([&self.secrets_path])
+            .filter_map(|path| {
+                // The file may not exist, so resolve its directory instead
+                let directory = path.parent()?;
+                let directory = if directory.as_os_str().is_empty() {
+                    Path::new(".")
+                } else {
+                    directory
+                };
+                Some(directory.canonicalize().ok()?.join(path.file_name()?))
+            })
+            .unique()
+            .collect()
+    }
+
+    /// Get the path of the file that defined a profile
+    pub fn profile_source(&self, profile_id: &ProfileId) -> Option<&Path> {
+        self.source(self.sources.profiles.get(profile_id))
+    }
+
+    /// Get the path of the file that defined a chain
+    pub fn chain_source(&self, chain_id: &ChainId) -> Option<&Path> {
+        self.source(self.sources.chains.get(chain_id))
+    }
+
+    /// Get the path of the file that defined a recipe/folder
+    pub fn recipe_source(&self, recipe_id: &RecipeId) -> Option<&Path> {
+        self.source(self.sources.recipes.get(recipe_id))
+    }
+
+    fn source(&self, index: Option<&usize>) -> Option<&Path> {
+        index.map(|index| self.paths[*index].as_path())
+    }
+
     /// Get the path to the collection file, returning an error if none is
     /// available. This will use the override if given, otherwise it will fall
     /// back to searching the current directory for a collection.
@@ -105,29 +232,870 @@ fn detect_path() -> Option<PathBuf> {
     }
 }
 
-/// Load a collection from the given file. Takes an owned path because it
-/// needs to be passed to a future
-async fn load_collection(path: PathBuf) -> anyhow::Result<Collection> {
+/// Get the path to look for a secrets file at, for a collection file. The
+/// path is relative to the collection file
+fn secrets_path(collection_path: &Path, config: &Config) -> PathBuf {
+    let file_name = config
+        .secrets_file
+        .as_deref()
+        .unwrap_or(Path::new(SECRETS_FILE));
+    collection_path
+        .parent()
+        .unwrap_or(Path::new(""))
+        .join(file_name)
+}
+
+/// Load a collection from the given file, including all of its imports and
+/// secrets. Returns the loader, which holds the collection as well as the file
+/// each part of it was loaded from. The loader is returned even if loading
+/// fails, because it knows which files were found before the failure. Takes
+/// owned paths because they need to be passed to a future
+async fn load_collection(
+    path: PathBuf,
+    secrets_path: PathBuf,
+) -> (CollectionLoader, anyhow::Result<()>) {
     info!(?path, "Loading collection file");
     // A bit pessimistic, huh... This gets around some lifetime struggles
     let error_context = format!("Error loading data from {path:?}");
 
-    // This async block is really just a try block
-    let result =
-        task::spawn_blocking::<_, anyhow::Result<Collection>>(move || {
-            let bytes = fs::read(path)?;
-            let collection = parse_yaml(&bytes)?;
-            Ok(collection)
-        })
-        .await;
+    let result = task::spawn_blocking(move || {
+        let mut loader = CollectionLoader::default();
+        // Shitty try block
+        let result = (|| {
+            loader.load_file(&path)?;
+            loader.load_secrets(&secrets_path)?;
+            loader.check_profile_inheritance()?;
+            loader.check_recipe_inheritance()
+        })();
+        (loader, result)
+    })
+    .await;
 
-    // Flatten the join error result into the inner task result. Result::flatten
-    // is experimental :(
-    // https://doc.rust-lang.org/std/result/enum.Result.html#method.flatten
-    let result = match result {
-        Ok(result) => result,
-        Err(error) => Err(error.into()),
+    let (loader, result) = match result {
+        Ok(output) => output,
+        Err(error) => (CollectionLoader::default(), Err(error.into())),
     };
+    (loader, result.context(error_context).traced())
+}
+
+/// Loads a collection file and everything it imports, merging them all into
+/// a single collection. IDs must be unique across all files.
+#[derive(Debug, Default)]
+struct CollectionLoader {
+    collection: Collection,
+    /// Every file found so far, in load order, including one that failed to
+    /// load
+    paths: Vec<PathBuf>,
+    /// Canonicalized versions of [Self::paths], so the same file isn't loaded
+    /// twice. This also prevents import cycles.
+    canonical_paths: HashSet<PathBuf>,
+    /// Index of the file (in [Self::paths]) that defined each ID
+    sources: Sources,
+    /// Dotenv files loaded by profiles, in load order
+    env_files: Vec<PathBuf>,
+    secrets_loaded: bool,
+}
+
+/// A secrets file, which sets profile data that shouldn't be committed
+/// alongside the collection. This can *only* set profile data.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Secrets {
+    #[serde(default)]
+    profiles: IndexMap<ProfileId, SecretsProfile>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SecretsProfile {
+    #[serde(default)]
+    data: IndexMap<String, Template>,
+}
+
+impl CollectionLoader {
+    /// Load a single file and merge it into the collection, then load its
+    /// imports. Files that have already been loaded are skipped. The path is
+    /// tracked before it's loaded, so it can be watched even if it's invalid.
+    fn load_file(&mut self, path: &Path) -> anyhow::Result<()> {
+        let canonical_path = match path.canonicalize() {
+            Ok(canonical_path) => canonical_path,
+            Err(error) => {
+                // The file may be created later
+                self.paths.push(path.to_owned());
+                return Err(anyhow::Error::from(error)
+                    .context(format!("Error resolving path {path:?}")));
+            }
+        };
+        if !self.canonical_paths.insert(canonical_path) {
+            debug!(?path, "Collection file already loaded, skipping");
+            return Ok(());
+        }
+        let source = self.paths.len();
+        self.paths.push(path.to_owned());
+
+        let bytes = fs::read(path)?;
+        let mut collection: Collection = parse_yaml(&bytes)?;
+        let imports = mem::take(&mut collection.imports);
+
+        // Import and env file paths are relative to the importing file
+        let directory = path.parent().unwrap_or(Path::new(""));
+        for profile in collection.profiles.values_mut() {
+            if let Some(env_file) = &profile.env_file {
+                let env_file = directory.join(env_file);
+                self.env_files.push(env_file.clone());
+                load_env_file(&env_file, &mut profile.data).with_context(
+                    || {
+                        format!(
+                            "Error loading env file {env_file:?} for profile \
+                            `{}`",
+                            profile.id
+                        )
+                    },
+                )?;
+            }
+        }
+
+        self.merge(collection, source)?;
 
-    result.context(error_context).traced()
+        for import in imports {
+            let import = directory.join(import);
+            info!(path = ?import, "Loading imported collection file");
+            self.load_file(&import)
+                .with_context(|| format!("Error loading import {import:?}"))?;
+        }
+        Ok(())
+    }
+
+    /// Load the secrets file, if it exists, and merge its data into the
+    /// profiles. Secrets take precedence over all other profile data, and
+    /// are marked sensitive. This has to be done after all imports are
+    /// loaded, so secrets can be set for imported profiles.
+    fn load_secrets(&mut self, path: &Path) -> anyhow::Result<()> {
+        let bytes = match fs::read(path) {
+            Ok(bytes) => bytes,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => {
+                debug!(?path, "No secrets file found, skipping");
+                return Ok(());
+            }
+            Err(error) => {
+                return Err(anyhow::Error::from(error)
+                    .context(format!("Error reading secrets file {path:?}")))
+            }
+        };
+        info!(?path, "Loading secrets file");
+        let secrets: Secrets = parse_yaml(&bytes)
+            .with_context(|| format!("Error loading secrets file {path:?}"))?;
+
+        for (profile_id, secrets_profile) in secrets.profiles {
+            let Some(profile) = self.collection.profiles.get_mut(&profile_id)
+            else {
+                return Err(anyhow!(
+                    "Secrets file {path:?} sets data for unknown profile \
+                    `{profile_id}`"
+                ));
+            };
+            for (field, template) in secrets_profile.data {
+                profile.sensitive_fields.insert(field.clone());
+                profile.data.insert(field, template);
+            }
+        }
+        self.secrets_loaded = true;
+        Ok(())
+    }
+
+    /// Merge a newly loaded collection into the combined collection. Returns
+    /// an error if any ID is already defined
+    fn merge(
+        &mut self,
+        collection: Collection,
+        source: usize,
+    ) -> anyhow::Result<()> {
+        merge_map(
+            &self.paths,
+            &mut self.collection.profiles,
+            &mut self.sources.profiles,
+            collection.profiles,
+            source,
+            "profile",
+        )?;
+        merge_map(
+            &self.paths,
+            &mut self.collection.chains,
+            &mut self.sources.chains,
+            collection.chains,
+            source,
+            "chain",
+        )?;
+
+        // Recipe/folder IDs are unique across the whole tree, so check every
+        // node, not just the top level
+        for (_, node) in collection.recipes.iter() {
+            check_duplicate(
+                &self.paths,
+                &mut self.sources.recipes,
+                node.id(),
+                source,
+                "recipe/folder",
+            )?;
+        }
+        let mut tree = mem::take(&mut self.collection.recipes).into_inner();
+        tree.extend(collection.recipes.into_inner());
+        // We already checked for duplicates, so this shouldn't fail
+        self.collection.recipes = RecipeTree::new(tree).map_err(|id| {
+            anyhow!(
+                "Duplicate recipe/folder ID `{id}` in {:?}",
+                self.paths[source]
+            )
+        })?;
+        Ok(())
+    }
+
+    /// Make sure every profile only extends profiles that exist, and there
+    /// are no cycles in the inheritance tree. This has to be done after all
+    /// imports are loaded, because parents can be defined in other files.
+    fn check_profile_inheritance(&self) -> anyhow::Result<()> {
+        fn visit<'a>(
+            loader: &'a CollectionLoader,
+            profile: &'a Profile,
+            stack: &mut Vec<&'a ProfileId>,
+        ) -> anyhow::Result<()> {
+            if stack.contains(&&profile.id) {
+                let cycle = stack
+                    .iter()
+                    .skip_while(|id| **id != &profile.id)
+                    .chain([&&profile.id])
+                    .join(" -> ");
+                return Err(anyhow!(
+                    "Profile inheritance cycle in {:?}: {cycle}",
+                    loader.profile_source(&profile.id)
+                ));
+            }
+            stack.push(&profile.id);
+            for parent_id in &profile.extends {
+                let parent =
+                    loader.collection.profiles.get(parent_id).ok_or_else(
+                        || {
+                            anyhow!(
+                                "Profile `{}` in {:?} extends unknown profile \
+                            `{parent_id}`",
+                                profile.id,
+                                loader.profile_source(&profile.id)
+                            )
+                        },
+                    )?;
+                visit(loader, parent, stack)?;
+            }
+            stack.pop();
+            Ok(())
+        }
+
+        for profile in self.collection.profiles.values() {
+            visit(self, profile, &mut Vec::new())?;
+        }
+        Ok(())
+    }
+
+    /// Get the path of the file that defined a profile
+    fn profile_source(&self, profile_id: &ProfileId) -> &Path {
+        &self.paths[self.sources.profiles[profile_id]]
+    }
+
+    /// Make sure every recipe only extends recipes that exist, there are no
+    /// cycles, and every recipe ends up with a method and URL once
+    /// inheritance is applied. Like profiles, this has to wait until all
+    /// imports are loaded.
+    fn check_recipe_inheritance(&self) -> anyhow::Result<()> {
+        let recipes = &self.collection.recipes;
+        for (_, node) in recipes.iter() {
+            let RecipeNode::Recipe(recipe) = node else {
+                continue;
+            };
+            let source = self.recipe_source(&recipe.id);
+
+            // Walk up the chain of parents
+            let mut chain = vec![&recipe.id];
+            let mut next = recipe;
+            while let Some(parent_id) = &next.extends {
+                if chain.contains(&parent_id) {
+                    let cycle = chain.iter().chain([&parent_id]).join(" -> ");
+                    return Err(anyhow!(
+                        "Recipe inheritance cycle in {source:?}: {cycle}"
+                    ));
+                }
+                next = match recipes.get(parent_id) {
+                    Some(RecipeNode::Recipe(parent)) => parent,
+                    Some(RecipeNode::Folder(_)) => {
+                        return Err(anyhow!(
+                            "Recipe `{}` in {:?} extends folder \
+                            `{parent_id}`; only recipes can be extended",
+                            next.id,
+                            self.recipe_source(&next.id)
+                        ))
+                    }
+                    None => {
+                        return Err(anyhow!(
+                            "Recipe `{}` in {:?} extends unknown recipe \
+                            `{parent_id}`",
+                            next.id,
+                            self.recipe_source(&next.id)
+                        ))
+                    }
+                };
+                chain.push(parent_id);
+            }
+
+            let parents = recipes.parents(recipe);
+            let lineage = || iter::once(recipe).chain(parents.iter().copied());
+            for (field, is_defined) in [
+                ("method", lineage().any(|recipe| recipe.method.is_some())),
+                ("url", lineage().any(|recipe| recipe.url.is_some())),
+            ] {
+                if !is_defined {
+                    return Err(anyhow!(
+                        "Recipe `{}` in {source:?} has no `{field}`; define \
+                        it or extend a recipe that does",
+                        recipe.id
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Get the path of the file that defined a recipe/folder
+    fn recipe_source(&self, recipe_id: &RecipeId) -> &Path {
+        &self.paths[self.sources.recipes[recipe_id]]
+    }
+}
+
+/// Merge one map of IDs into another, returning an error if any ID is
+/// already defined
+fn merge_map<K, V>(
+    paths: &[PathBuf],
+    target: &mut IndexMap<K, V>,
+    sources: &mut HashMap<K, usize>,
+    values: IndexMap<K, V>,
+    source: usize,
+    kind: &str,
+) -> anyhow::Result<()>
+where
+    K: Clone + Display + Eq + Hash,
+{
+    for (id, value) in values {
+        check_duplicate(paths, sources, &id, source, kind)?;
+        target.insert(id, value);
+    }
+    Ok(())
+}
+
+/// Record the source file of an ID, returning an error if the ID was already
+/// defined in another file
+fn check_duplicate<K>(
+    paths: &[PathBuf],
+    sources: &mut HashMap<K, usize>,
+    id: &K,
+    source: usize,
+    kind: &str,
+) -> anyhow::Result<()>
+where
+    K: Clone + Display + Eq + Hash,
+{
+    if let Some(existing) = sources.insert(id.clone(), source) {
+        return Err(anyhow!(
+            "Duplicate {kind} ID `{id}` in {:?}; already defined in {:?}",
+            paths[source],
+            paths[existing],
+        ));
+    }
+    Ok(())
+}
+
+/// Load fields from a dotenv file into a profile's data. Fields that are
+/// already defined take precedence. Values are used literally, so they're
+/// never parsed as templates.
+fn load_env_file(
+    path: &Path,
+    data: &mut IndexMap<String, Template>,
+) -> anyhow::Result<()> {
+    for item in dotenvy::from_path_iter(path)? {
+        let (key, value) = item?;
+        data.entry(key).or_insert_with(|| Template::raw(value));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::*;
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+    use std::env;
+    use uuid::Uuid;
+
+    /// Create a unique directory with the given files in it, and return its
+    /// path
+    fn create_files(files: &[(&str, &str)]) -> PathBuf {
+        let directory =
+            env::temp_dir().join(format!("slumber-{}", Uuid::new_v4()));
+        for (path, content) in files {
+            let path = directory.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+        directory
+    }
+
+    /// Imported files should be merged into the root collection, with paths
+    /// relative to the importing file
+    #[tokio::test]
+    async fn test_imports() {
+        let directory = create_files(&[
+            (
+                "slumber.yml",
+                "
+imports: [profiles.yml, requests/users.yml]
+requests:
+  root: !request
+    method: GET
+    url: http://localhost/root
+",
+            ),
+            (
+                "profiles.yml",
+                "
+profiles:
+  local:
+    data:
+      host: http://localhost
+",
+            ),
+            (
+                "requests/users.yml",
+                "
+imports: [../profiles.yml, chains.yml]
+requests:
+  users: !folder
+    requests:
+      get_user: !request
+        method: GET
+        url: '{{host}}/users/1'
+",
+            ),
+            (
+                "requests/chains.yml",
+                "
+chains:
+  token:
+    source: !file
+      path: token.txt
+",
+            ),
+        ]);
+
+        let collection_file = CollectionFile::load(
+            directory.join("slumber.yml"),
+            &Config::default(),
+        )
+        .await
+        .unwrap();
+        let collection = &collection_file.collection;
+
+        assert_eq!(
+            collection_file.paths(),
+            &[
+                directory.join("slumber.yml"),
+                directory.join("profiles.yml"),
+                directory.join("requests/users.yml"),
+                directory.join("requests/chains.yml"),
+            ]
+        );
+        assert!(collection.imports.is_empty());
+        assert_eq!(
+            collection.profiles.keys().collect::<Vec<_>>(),
+            [&ProfileId::from("local")]
+        );
+        assert_eq!(
+            collection.chains.keys().collect::<Vec<_>>(),
+            [&ChainId::from("token")]
+        );
+        assert_eq!(
+            collection.recipes.recipe_ids().collect::<Vec<_>>(),
+            [&RecipeId::from("root"), &RecipeId::from("get_user")]
+        );
+    }
+
+    /// When loading fails, the files found before the failure should still be
+    /// known, so they can be watched for a fix
+    #[rstest]
+    #[case::import(
+        "imports: [imported.yml]",
+        &[("imported.yml", "requests: not a map")],
+        "imported.yml"
+    )]
+    #[case::env_file("profiles:\n  local:\n    env_file: .env", &[], ".env")]
+    #[tokio::test]
+    async fn test_load_partial(
+        #[case] root: &str,
+        #[case] files: &[(&str, &str)],
+        #[case] broken_file: &str,
+    ) {
+        let directory =
+            create_files(&[&[("slumber.yml", root)], files].concat());
+        // Canonicalize to match file watcher events
+        let directory = directory.canonicalize().unwrap();
+
+        let (collection_file, error) = CollectionFile::load_partial(
+            directory.join("slumber.yml"),
+            &Config::default(),
+        )
+        .await;
+        assert!(error.is_some());
+        assert_eq!(collection_file.collection, Collection::default());
+        assert_eq!(
+            collection_file.watch_paths(),
+            &[
+                directory.join("slumber.yml"),
+                directory.join(broken_file),
+                // Doesn't exist yet, but should be picked up if created
+                directory.join("slumber.secrets.yml"),
+            ]
+        );
+    }
+
+    /// Duplicate IDs across files are an error, and should say which files
+    /// they came from
+    #[rstest]
+    #[case::profile(
+        "profiles:\n  local:\n    data: {}",
+        "Duplicate profile ID `local` in \"{dir}/imported.yml\"; \
+        already defined in \"{dir}/slumber.yml\""
+    )]
+    #[case::recipe(
+        "requests:\n  inner: !request\n    method: GET\n    url: ''",
+        "Duplicate recipe/folder ID `inner` in \"{dir}/imported.yml\"; \
+        already defined in \"{dir}/slumber.yml\""
+    )]
+    #[tokio::test]
+    async fn test_import_duplicate_id(
+        #[case] imported: &str,
+        #[case] expected_error: &str,
+    ) {
+        let directory = create_files(&[
+            (
+                "slumber.yml",
+                "
+imports: [imported.yml]
+profiles:
+  local:
+    data: {}
+requests:
+  folder: !folder
+    requests:
+      inner: !request
+        method: GET
+        url: ''
+",
+            ),
+            ("imported.yml", imported),
+        ]);
+
+        let expected_error =
+            expected_error.replace("{dir}", &directory.to_string_lossy());
+        assert_err!(
+            CollectionFile::load(
+                directory.join("slumber.yml"),
+                &Config::default()
+            )
+            .await,
+            &expected_error
+        );
+    }
+
+    /// Profiles can extend profiles from other files, but unknown parents and
+    /// cycles are rejected
+    #[rstest]
+    #[case::imported_parent("extends: base", None)]
+    #[case::unknown_parent(
+        "extends: [base, unknown]",
+        Some(
+            "Profile `child` in \"{dir}/slumber.yml\" extends unknown \
+            profile `unknown`"
+        )
+    )]
+    #[case::cycle(
+        "extends: cycle2",
+        Some(
+            "Profile inheritance cycle in \"{dir}/slumber.yml\": \
+            child -> cycle2 -> child"
+        )
+    )]
+    #[tokio::test]
+    async fn test_profile_inheritance(
+        #[case] extends: &str,
+        #[case] expected_error: Option<&str>,
+    ) {
+        let directory = create_files(&[
+            (
+                "slumber.yml",
+                &format!(
+                    "
+imports: [base.yml]
+profiles:
+  child:
+    {extends}
+  cycle2:
+    extends: child
+"
+                ),
+            ),
+            ("base.yml", "profiles:\n  base:\n    data: {}"),
+        ]);
+
+        let result = CollectionFile::load(
+            directory.join("slumber.yml"),
+            &Config::default(),
+        )
+        .await;
+        if let Some(expected_error) = expected_error {
+            let expected_error =
+                expected_error.replace("{dir}", &directory.to_string_lossy());
+            assert_err!(result, &expected_error);
+        } else {
+            result.unwrap();
+        }
+    }
+
+    /// Recipes can extend recipes from other files, but unknown parents,
+    /// folders, cycles, and missing required fields are rejected
+    #[rstest]
+    #[case::imported_parent("extends: base", None)]
+    #[case::unknown_parent(
+        "extends: unknown",
+        Some(
+            "Recipe `child` in \"{dir}/slumber.yml\" extends unknown recipe \
+            `unknown`"
+        )
+    )]
+    #[case::folder(
+        "extends: folder",
+        Some(
+            "Recipe `child` in \"{dir}/slumber.yml\" extends folder \
+            `folder`; only recipes can be extended"
+        )
+    )]
+    #[case::cycle(
+        "extends: cycle2",
+        Some(
+            "Recipe inheritance cycle in \"{dir}/slumber.yml\": \
+            child -> cycle2 -> child"
+        )
+    )]
+    #[case::no_method(
+        "url: /child",
+        Some(
+            "Recipe `child` in \"{dir}/slumber.yml\" has no `method`; \
+            define it or extend a recipe that does"
+        )
+    )]
+    #[tokio::test]
+    async fn test_recipe_inheritance(
+        #[case] child: &str,
+        #[case] expected_error: Option<&str>,
+    ) {
+        let directory = create_files(&[
+            (
+                "slumber.yml",
+                &format!(
+                    "
+imports: [base.yml]
+requests:
+  child: !request
+    {child}
+  cycle2: !request
+    extends: child
+  folder: !folder
+    requests: {{}}
+"
+                ),
+            ),
+            (
+                "base.yml",
+                "requests:\n  base: !request\n    method: GET\n    url: /",
+            ),
+        ]);
+
+        let result = CollectionFile::load(
+            directory.join("slumber.yml"),
+            &Config::default(),
+        )
+        .await;
+        if let Some(expected_error) = expected_error {
+            let expected_error =
+                expected_error.replace("{dir}", &directory.to_string_lossy());
+            assert_err!(result, &expected_error);
+        } else {
+            result.unwrap();
+        }
+    }
+
+    /// Env files are relative to the file that defines the profile, and
+    /// explicit data takes precedence over them
+    #[tokio::test]
+    async fn test_env_file() {
+        let directory = create_files(&[
+            ("slumber.yml", "imports: [profiles/local.yml]"),
+            (
+                "profiles/local.yml",
+                "
+profiles:
+  local:
+    env_file: .env
+    data:
+      host: http://localhost
+",
+            ),
+            (
+                "profiles/.env",
+                "host=http://ignored\nTOKEN=\"abc{{123}}\"\n",
+            ),
+        ]);
+
+        let collection_file = CollectionFile::load(
+            directory.join("slumber.yml"),
+            &Config::default(),
+        )
+        .await
+        .unwrap();
+        let profile = &collection_file.collection.profiles[0];
+        assert_eq!(
+            profile.data,
+            IndexMap::<String, Template>::from_iter([
+                ("host".into(), "http://localhost".into()),
+                ("TOKEN".into(), Template::raw("abc{{123}}".into())),
+            ])
+        );
+        assert_eq!(
+            collection_file.env_files(),
+            &[directory.join("profiles/.env")]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_env_file_missing() {
+        let directory = create_files(&[(
+            "slumber.yml",
+            "profiles:\n  local:\n    env_file: .env",
+        )]);
+
+        let result = CollectionFile::load(
+            directory.join("slumber.yml"),
+            &Config::default(),
+        )
+        .await;
+        assert_err!(
+            result,
+            &format!(
+                "Error loading env file {:?} for profile `local`",
+                directory.join(".env")
+            )
+        );
+    }
+
+    /// Secrets override profile data and are marked sensitive
+    #[tokio::test]
+    async fn test_secrets_file() {
+        let directory = create_files(&[
+            (
+                "slumber.yml",
+                "
+profiles:
+  local:
+    data:
+      host: http://localhost
+      password: placeholder
+",
+            ),
+            (
+                "slumber.secrets.yml",
+                "profiles:\n  local:\n    data:\n      password: hunter2",
+            ),
+        ]);
+
+        let collection_file = CollectionFile::load(
+            directory.join("slumber.yml"),
+            &Config::default(),
+        )
+        .await
+        .unwrap();
+        let profile = &collection_file.collection.profiles[0];
+        assert_eq!(
+            profile.data,
+            IndexMap::<String, Template>::from_iter([
+                ("host".into(), "http://localhost".into()),
+                ("password".into(), "hunter2".into()),
+            ])
+        );
+        assert_eq!(
+            profile.sensitive_fields,
+            HashSet::from(["password".into()])
+        );
+        assert!(collection_file.secrets_loaded);
+        assert_eq!(
+            collection_file.secrets_path,
+            directory.join("slumber.secrets.yml")
+        );
+    }
+
+    /// The secrets file location can be changed in the config
+    #[tokio::test]
+    async fn test_secrets_file_config() {
+        let directory = create_files(&[
+            ("slumber.yml", "profiles:\n  local:\n    data: {}"),
+            (
+                "secrets/local.yml",
+                "profiles:\n  local:\n    data:\n      token: abc",
+            ),
+        ]);
+        let config = Config {
+            secrets_file: Some("secrets/local.yml".into()),
+            ..Config::default()
+        };
+
+        let collection_file =
+            CollectionFile::load(directory.join("slumber.yml"), &config)
+                .await
+                .unwrap();
+        let profile = &collection_file.collection.profiles[0];
+        assert_eq!(profile.data["token"], "abc".into());
+        assert!(profile.sensitive_fields.contains("token"));
+    }
+
+    #[rstest]
+    #[case::unknown_profile(
+        "profiles:\n  remote:\n    data:\n      token: abc",
+        "sets data for unknown profile `remote`"
+    )]
+    #[case::not_data(
+        "profiles:\n  local:\n    name: Local",
+        "unknown field `name`"
+    )]
+    #[case::not_profiles("requests: {}", "unknown field `requests`")]
+    #[tokio::test]
+    async fn test_secrets_file_error(
+        #[case] secrets: &str,
+        #[case] expected_error: &str,
+    ) {
+        let directory = create_files(&[
+            ("slumber.yml", "profiles:\n  local:\n    data: {}"),
+            ("slumber.secrets.yml", secrets),
+        ]);
+
+        let result = CollectionFile::load(
+            directory.join("slumber.yml"),
+            &Config::default(),
+        )
+        .await;
+        assert_err!(result, expected_error);
+    }
 }
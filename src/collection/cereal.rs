@@ -2,16 +2,27 @@
 
 use crate::{
     collection::{
-        recipe_tree::RecipeNode, Chain, ChainId, Profile, ProfileId, RecipeId,
+        recipe_tree::RecipeNode, Chain, ChainId, JsonTemplate, MultipartField,
+        Profile, ProfileId, RecipeBody, RecipeId,
     },
     template::Template,
 };
+use indexmap::IndexMap;
 use serde::{
-    de::{Error, Visitor},
-    Deserialize, Deserializer,
+    de::{EnumAccess, Error, MapAccess, SeqAccess, VariantAccess, Visitor},
+    Deserialize, Deserializer, Serialize, Serializer,
 };
 use std::hash::Hash;
 
+/// YAML tag for [RecipeBody::FormUrlencoded]
+const TAG_FORM_URLENCODED: &str = "form_urlencoded";
+/// YAML tag for [RecipeBody::FormMultipart]
+const TAG_FORM_MULTIPART: &str = "form_multipart";
+/// YAML tag for [RecipeBody::Json]
+const TAG_JSON: &str = "json";
+/// YAML tag for [MultipartField::File]
+const TAG_FILE: &str = "file";
+
 /// A type that has an `id` field. This is ripe for a derive macro, maybe a fun
 /// project some day?
 pub trait HasId {
@@ -68,6 +79,43 @@ where
     Ok(map)
 }
 
+/// Deserialize either a single value or a list of values into a list
+pub fn deserialize_one_or_many<'de, T, D>(
+    deserializer: D,
+) -> Result<Vec<T>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany<T> {
+        One(T),
+        Many(Vec<T>),
+    }
+
+    match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(value) => Ok(vec![value]),
+        OneOrMany::Many(values) => Ok(values),
+    }
+}
+
+/// Generate a visitor function that parses a primitive value as a template,
+/// then converts it to the visitor's output type. Templates can be any
+/// primitive, not just strings.
+macro_rules! visit_primitive {
+    ($func:ident, $type:ty) => {
+        fn $func<E>(self, v: $type) -> Result<Self::Value, E>
+        where
+            E: Error,
+        {
+            Template::try_from(v.to_string())
+                .map(<Self::Value>::from)
+                .map_err(E::custom)
+        }
+    };
+}
+
 // Custom deserializer for `Template`. This is useful for deserializing values
 // that are not strings, but should be treated as strings such as numbers,
 // booleans, and nulls.
@@ -78,17 +126,6 @@ impl<'de> Deserialize<'de> for Template {
     {
         struct TemplateVisitor;
 
-        macro_rules! visit_primitive {
-            ($func:ident, $type:ty) => {
-                fn $func<E>(self, v: $type) -> Result<Self::Value, E>
-                where
-                    E: Error,
-                {
-                    Template::try_from(v.to_string()).map_err(E::custom)
-                }
-            };
-        }
-
         impl<'de> Visitor<'de> for TemplateVisitor {
             type Value = Template;
 
@@ -110,6 +147,421 @@ impl<'de> Deserialize<'de> for Template {
     }
 }
 
+/// Raw bodies are serialized as a plain template, without a tag. Structured
+/// bodies get a tag, e.g. `!form_urlencoded`
+impl Serialize for RecipeBody {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            RecipeBody::Raw(template) => template.serialize(serializer),
+            RecipeBody::FormUrlencoded(fields) => serializer
+                .serialize_newtype_variant(
+                    "RecipeBody",
+                    1,
+                    TAG_FORM_URLENCODED,
+                    fields,
+                ),
+            RecipeBody::FormMultipart(fields) => serializer
+                .serialize_newtype_variant(
+                    "RecipeBody",
+                    2,
+                    TAG_FORM_MULTIPART,
+                    fields,
+                ),
+            RecipeBody::Json(value) => serializer.serialize_newtype_variant(
+                "RecipeBody",
+                3,
+                TAG_JSON,
+                value,
+            ),
+        }
+    }
+}
+
+/// Accept an untagged template as a raw body, or a tagged mapping as a
+/// structured body
+impl<'de> Deserialize<'de> for RecipeBody {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct RecipeBodyVisitor;
+
+        impl<'de> Visitor<'de> for RecipeBodyVisitor {
+            type Value = RecipeBody;
+
+            fn expecting(
+                &self,
+                formatter: &mut std::fmt::Formatter,
+            ) -> std::fmt::Result {
+                formatter.write_str(
+                    "string, !form_urlencoded mapping, \
+                    !form_multipart mapping, or !json value",
+                )
+            }
+
+            visit_primitive!(visit_bool, bool);
+            visit_primitive!(visit_u64, u64);
+            visit_primitive!(visit_i64, i64);
+            visit_primitive!(visit_f64, f64);
+            visit_primitive!(visit_str, &str);
+
+            fn visit_enum<A>(self, data: A) -> Result<Self::Value, A::Error>
+            where
+                A: EnumAccess<'de>,
+            {
+                let (tag, value) = data.variant::<String>()?;
+                match tag.as_str() {
+                    TAG_FORM_URLENCODED => {
+                        Ok(RecipeBody::FormUrlencoded(value.newtype_variant()?))
+                    }
+                    TAG_FORM_MULTIPART => {
+                        Ok(RecipeBody::FormMultipart(value.newtype_variant()?))
+                    }
+                    TAG_JSON => Ok(RecipeBody::Json(value.newtype_variant()?)),
+                    _ => Err(A::Error::unknown_variant(
+                        &tag,
+                        &[TAG_FORM_URLENCODED, TAG_FORM_MULTIPART, TAG_JSON],
+                    )),
+                }
+            }
+        }
+
+        deserializer.deserialize_any(RecipeBodyVisitor)
+    }
+}
+
+/// Strings are serialized as templates, everything else as its JSON type
+impl Serialize for JsonTemplate {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            JsonTemplate::Null => serializer.serialize_unit(),
+            JsonTemplate::Bool(value) => serializer.serialize_bool(*value),
+            JsonTemplate::Number(value) => value.serialize(serializer),
+            JsonTemplate::String(template) => template.serialize(serializer),
+            JsonTemplate::Array(values) => values.serialize(serializer),
+            JsonTemplate::Object(fields) => fields.serialize(serializer),
+        }
+    }
+}
+
+/// Accept any YAML value that can be represented as JSON. Unlike [Template],
+/// numbers and booleans keep their type instead of becoming strings
+impl<'de> Deserialize<'de> for JsonTemplate {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct JsonTemplateVisitor;
+
+        impl<'de> Visitor<'de> for JsonTemplateVisitor {
+            type Value = JsonTemplate;
+
+            fn expecting(
+                &self,
+                formatter: &mut std::fmt::Formatter,
+            ) -> std::fmt::Result {
+                formatter.write_str("any JSON value")
+            }
+
+            fn visit_unit<E>(self) -> Result<Self::Value, E>
+            where
+                E: Error,
+            {
+                Ok(JsonTemplate::Null)
+            }
+
+            fn visit_bool<E>(self, v: bool) -> Result<Self::Value, E>
+            where
+                E: Error,
+            {
+                Ok(JsonTemplate::Bool(v))
+            }
+
+            fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E>
+            where
+                E: Error,
+            {
+                Ok(JsonTemplate::Number(v.into()))
+            }
+
+            fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E>
+            where
+                E: Error,
+            {
+                Ok(JsonTemplate::Number(v.into()))
+            }
+
+            fn visit_f64<E>(self, v: f64) -> Result<Self::Value, E>
+            where
+                E: Error,
+            {
+                serde_json::Number::from_f64(v)
+                    .map(JsonTemplate::Number)
+                    .ok_or_else(|| {
+                        E::custom(format!("Invalid JSON number {v}"))
+                    })
+            }
+
+            visit_primitive!(visit_str, &str);
+
+            fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+            where
+                A: SeqAccess<'de>,
+            {
+                let mut values =
+                    Vec::with_capacity(seq.size_hint().unwrap_or(0));
+                while let Some(value) = seq.next_element()? {
+                    values.push(value);
+                }
+                Ok(JsonTemplate::Array(values))
+            }
+
+            fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+            where
+                A: MapAccess<'de>,
+            {
+                let mut fields = IndexMap::new();
+                while let Some((key, value)) = map.next_entry()? {
+                    fields.insert(key, value);
+                }
+                Ok(JsonTemplate::Object(fields))
+            }
+        }
+
+        deserializer.deserialize_any(JsonTemplateVisitor)
+    }
+}
+
+/// Text fields are serialized as a plain template, files get the `!file` tag
+impl Serialize for MultipartField {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            MultipartField::Text(template) => template.serialize(serializer),
+            MultipartField::File(path) => serializer.serialize_newtype_variant(
+                "MultipartField",
+                1,
+                TAG_FILE,
+                path,
+            ),
+        }
+    }
+}
+
+/// Accept an untagged template as a text field, or `!file <path>` as a file
+impl<'de> Deserialize<'de> for MultipartField {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct MultipartFieldVisitor;
+
+        impl<'de> Visitor<'de> for MultipartFieldVisitor {
+            type Value = MultipartField;
+
+            fn expecting(
+                &self,
+                formatter: &mut std::fmt::Formatter,
+            ) -> std::fmt::Result {
+                formatter.write_str("string or !file path")
+            }
+
+            visit_primitive!(visit_bool, bool);
+            visit_primitive!(visit_u64, u64);
+            visit_primitive!(visit_i64, i64);
+            visit_primitive!(visit_f64, f64);
+            visit_primitive!(visit_str, &str);
+
+            fn visit_enum<A>(self, data: A) -> Result<Self::Value, A::Error>
+            where
+                A: EnumAccess<'de>,
+            {
+                let (tag, value) = data.variant::<String>()?;
+                match tag.as_str() {
+                    TAG_FILE => {
+                        Ok(MultipartField::File(value.newtype_variant()?))
+                    }
+                    _ => Err(A::Error::unknown_variant(&tag, &[TAG_FILE])),
+                }
+            }
+        }
+
+        deserializer.deserialize_any(MultipartFieldVisitor)
+    }
+}
+
+/// Serialize/deserialize a map where each key can have multiple values, such
+/// as query parameters or headers. Each value can be a single template or a
+/// list of templates. Alternatively, the whole map can be a list of
+/// single-entry mappings, to write repeated keys in order. Either way, values
+/// for the same key are grouped together. Keys can be any type that
+/// deserializes from a string, e.g. a template for header names.
+pub mod serde_multimap {
+    use crate::template::Template;
+    use indexmap::IndexMap;
+    use serde::{
+        de::{Error, MapAccess, SeqAccess, Visitor},
+        Deserialize, Deserializer, Serialize, Serializer,
+    };
+    use std::{
+        fmt::{self, Formatter},
+        hash::Hash,
+        marker::PhantomData,
+    };
+
+    /// All the values for a single key
+    struct Values(Vec<Template>);
+
+    impl From<Template> for Values {
+        fn from(template: Template) -> Self {
+            Self(vec![template])
+        }
+    }
+
+    impl<'de> Deserialize<'de> for Values {
+        fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            struct ValuesVisitor;
+
+            impl<'de> Visitor<'de> for ValuesVisitor {
+                type Value = Values;
+
+                fn expecting(&self, formatter: &mut Formatter) -> fmt::Result {
+                    formatter.write_str("template or list of templates")
+                }
+
+                visit_primitive!(visit_bool, bool);
+                visit_primitive!(visit_u64, u64);
+                visit_primitive!(visit_i64, i64);
+                visit_primitive!(visit_f64, f64);
+                visit_primitive!(visit_str, &str);
+
+                fn visit_seq<A>(self, mut seq: A) -> Result<Values, A::Error>
+                where
+                    A: SeqAccess<'de>,
+                {
+                    let mut values = Vec::new();
+                    while let Some(value) = seq.next_element()? {
+                        values.push(value);
+                    }
+                    Ok(Values(values))
+                }
+            }
+
+            deserializer.deserialize_any(ValuesVisitor)
+        }
+    }
+
+    /// Serialize single values as a plain template, for brevity
+    impl Serialize for Values {
+        fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            match self.0.as_slice() {
+                [value] => value.serialize(serializer),
+                values => values.serialize(serializer),
+            }
+        }
+    }
+
+    pub fn serialize<K, S>(
+        map: &IndexMap<K, Vec<Template>>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error>
+    where
+        K: Serialize,
+        S: Serializer,
+    {
+        // Cloning is lazy, but serialization is rare and these maps are small
+        serializer.collect_map(
+            map.iter()
+                .map(|(key, values)| (key, Values(values.clone()))),
+        )
+    }
+
+    pub fn deserialize<'de, K, D>(
+        deserializer: D,
+    ) -> Result<IndexMap<K, Vec<Template>>, D::Error>
+    where
+        K: Deserialize<'de> + Hash + Eq,
+        D: Deserializer<'de>,
+    {
+        struct MultiMapVisitor<K>(PhantomData<K>);
+
+        impl<'de, K> Visitor<'de> for MultiMapVisitor<K>
+        where
+            K: Deserialize<'de> + Hash + Eq,
+        {
+            type Value = IndexMap<K, Vec<Template>>;
+
+            fn expecting(&self, formatter: &mut Formatter) -> fmt::Result {
+                formatter.write_str("mapping or list of mappings")
+            }
+
+            fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+            where
+                A: MapAccess<'de>,
+            {
+                let mut output: Self::Value = IndexMap::new();
+                while let Some((key, Values(values))) = map.next_entry()? {
+                    output.entry(key).or_default().extend(values);
+                }
+                Ok(output)
+            }
+
+            fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+            where
+                A: SeqAccess<'de>,
+            {
+                let mut output: Self::Value = IndexMap::new();
+                while let Some(entries) =
+                    seq.next_element::<IndexMap<K, Values>>()?
+                {
+                    if entries.len() != 1 {
+                        return Err(A::Error::custom(
+                            "Each list item must be a mapping with exactly \
+                            one entry, e.g. `- id: 3`",
+                        ));
+                    }
+                    for (key, Values(values)) in entries {
+                        output.entry(key).or_default().extend(values);
+                    }
+                }
+                Ok(output)
+            }
+
+            // An empty key (e.g. `headers:`) is null in YAML, which is
+            // accepted as an empty map
+            fn visit_unit<E>(self) -> Result<Self::Value, E>
+            where
+                E: Error,
+            {
+                Ok(IndexMap::new())
+            }
+
+            fn visit_none<E>(self) -> Result<Self::Value, E>
+            where
+                E: Error,
+            {
+                Ok(IndexMap::new())
+            }
+        }
+
+        deserializer.deserialize_any(MultiMapVisitor(PhantomData))
+    }
+}
+
 /// Serialize/deserialize a duration with unit shorthand. This does *not* handle
 /// subsecond precision. Supported units are:
 /// - s
@@ -195,6 +647,36 @@ pub mod serde_duration {
         Ok(Duration::from_secs(seconds))
     }
 
+    /// Same as the parent module, but for an optional duration
+    pub mod option {
+        use serde::{Deserialize, Deserializer, Serialize, Serializer};
+        use std::time::Duration;
+
+        #[derive(Serialize, Deserialize)]
+        #[serde(transparent)]
+        struct Wrap(#[serde(with = "super")] Duration);
+
+        pub fn serialize<S>(
+            duration: &Option<Duration>,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            duration.map(Wrap).serialize(serializer)
+        }
+
+        pub fn deserialize<'de, D>(
+            deserializer: D,
+        ) -> Result<Option<Duration>, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            let duration: Option<Wrap> = Option::deserialize(deserializer)?;
+            Ok(duration.map(|Wrap(duration)| duration))
+        }
+    }
+
     #[cfg(test)]
     mod tests {
         use super::*;
@@ -269,9 +751,18 @@ pub mod serde_duration {
 
 #[cfg(test)]
 mod tests {
-    use crate::template::Template;
+    use super::*;
+    use crate::{
+        collection::{
+            Assertion, ChainRequestSection, ChainSource, Folder, HttpSettings,
+            HttpVersion, JsonTemplate, Recipe, RedirectPolicy, StatusRange,
+        },
+        test_util::assert_err,
+    };
+    use indexmap::{indexmap, IndexMap};
     use rstest::rstest;
     use serde_test::{assert_de_tokens, Token};
+    use std::time::Duration;
 
     #[rstest]
     // boolean
@@ -290,4 +781,269 @@ mod tests {
     fn test_deserialize_template(#[case] token: Token, #[case] expected: &str) {
         assert_de_tokens(&Template::from(expected), &[token]);
     }
+
+    #[rstest]
+    #[case::raw("\"{{user_id}}\"", RecipeBody::Raw("{{user_id}}".into()))]
+    #[case::raw_number("3", RecipeBody::Raw("3".into()))]
+    #[case::json(
+        "!json\nid: 3\nname: '{{name}}'\ntags: [true, null, 1.5]",
+        RecipeBody::Json(JsonTemplate::Object(indexmap! {
+            "id".into() => JsonTemplate::Number(3.into()),
+            "name".into() => JsonTemplate::String("{{name}}".into()),
+            "tags".into() => JsonTemplate::Array(vec![
+                JsonTemplate::Bool(true),
+                JsonTemplate::Null,
+                JsonTemplate::Number(
+                    serde_json::Number::from_f64(1.5).unwrap(),
+                ),
+            ]),
+        })),
+    )]
+    #[case::json_string(
+        "!json '{{user}}'",
+        RecipeBody::Json(JsonTemplate::String("{{user}}".into())),
+    )]
+    #[case::form_urlencoded(
+        "!form_urlencoded\nuser_id: \"{{user_id}}\"\ntoken: 3",
+        RecipeBody::FormUrlencoded(indexmap! {
+            "user_id".into() => "{{user_id}}".into(),
+            "token".into() => "3".into(),
+        })
+    )]
+    #[case::form_multipart(
+        "!form_multipart\nuser_id: \"{{user_id}}\"\nimage: !file ./image.png",
+        RecipeBody::FormMultipart(indexmap! {
+            "user_id".into() => MultipartField::Text("{{user_id}}".into()),
+            "image".into() => MultipartField::File("./image.png".into()),
+        })
+    )]
+    fn test_recipe_body(#[case] yaml: &str, #[case] expected: RecipeBody) {
+        let body: RecipeBody = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(body, expected);
+        // Make sure it round trips
+        let serialized = serde_yaml::to_string(&body).unwrap();
+        assert_eq!(
+            serde_yaml::from_str::<RecipeBody>(&serialized).unwrap(),
+            expected
+        );
+    }
+
+    #[rstest]
+    #[case::unknown_tag(
+        "!form_json\nuser_id: 3",
+        "unknown variant `form_json`"
+    )]
+    #[case::unknown_field_tag(
+        "!form_multipart\nimage: !path ./image.png",
+        "unknown variant `path`"
+    )]
+    fn test_recipe_body_error(
+        #[case] yaml: &str,
+        #[case] expected_error: &str,
+    ) {
+        assert_err!(serde_yaml::from_str::<RecipeBody>(yaml), expected_error);
+    }
+
+    #[rstest]
+    #[case::none("data: {}", &[])]
+    #[case::one("extends: base\ndata: {}", &["base"])]
+    #[case::many("extends: [base, other]", &["base", "other"])]
+    fn test_profile_extends(#[case] yaml: &str, #[case] expected: &[&str]) {
+        let profile: Profile = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(
+            profile.extends,
+            expected
+                .iter()
+                .map(|id| ProfileId::from(id.to_string()))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    /// Inheritance isn't applied during deserialization, so a recipe should
+    /// serialize with the same structure it was defined with
+    #[test]
+    fn test_recipe_extends_round_trip() {
+        let yaml = "extends: base\nheaders:\n  Accept: application/json\n";
+        let recipe: Recipe = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(recipe.extends, Some("base".into()));
+        assert_eq!(recipe.method, None);
+        assert_eq!(recipe.url, None);
+
+        let serialized = serde_yaml::to_value(&recipe).unwrap();
+        assert_eq!(serialized["extends"], "base");
+        assert_eq!(serialized.get("method"), None);
+        assert_eq!(serialized.get("url"), None);
+        assert_eq!(
+            serde_yaml::from_value::<Recipe>(serialized).unwrap(),
+            recipe
+        );
+    }
+
+    #[rstest]
+    #[case::single(
+        "id: 1\nsort: name",
+        indexmap! {"id" => vec!["1"], "sort" => vec!["name"]}
+    )]
+    #[case::list(
+        "id: [1, 2]\nsort: name",
+        indexmap! {"id" => vec!["1", "2"], "sort" => vec!["name"]}
+    )]
+    #[case::pairs(
+        "- id: 1\n- sort: name\n- id: 2",
+        indexmap! {"id" => vec!["1", "2"], "sort" => vec!["name"]}
+    )]
+    #[case::pairs_list("- id: [1, 2]", indexmap! {"id" => vec!["1", "2"]})]
+    #[case::empty_list("id: []", indexmap! {"id" => vec![]})]
+    fn test_multimap(
+        #[case] yaml: &str,
+        #[case] expected: IndexMap<&str, Vec<&str>>,
+    ) {
+        let recipe: Recipe =
+            serde_yaml::from_str(&format!("query:\n{}", indent(yaml))).unwrap();
+        let expected: IndexMap<String, Vec<Template>> = expected
+            .into_iter()
+            .map(|(key, values)| {
+                (
+                    key.to_owned(),
+                    values.into_iter().map(Template::from).collect(),
+                )
+            })
+            .collect();
+        assert_eq!(recipe.query, expected);
+    }
+
+    #[rstest]
+    #[case::multiple_entries(
+        "- id: 1\n  sort: name",
+        "Each list item must be a mapping with exactly one entry"
+    )]
+    #[case::invalid_template("id: [\"{{\"]", "query.id[0]: ")]
+    fn test_multimap_error(#[case] yaml: &str, #[case] expected_err: &str) {
+        assert_err!(
+            serde_yaml::from_str::<Recipe>(&format!(
+                "query:\n{}",
+                indent(yaml)
+            )),
+            expected_err
+        );
+    }
+
+    /// An empty `headers:` or `query:` is null in YAML, which loads as an
+    /// empty map
+    #[test]
+    fn test_multimap_null() {
+        let recipe: Recipe = serde_yaml::from_str(
+            "method: GET\nurl: http://localhost\nheaders:\nquery:\n",
+        )
+        .unwrap();
+        assert!(recipe.headers.is_empty());
+        assert!(recipe.query.is_empty());
+
+        let folder: Folder =
+            serde_yaml::from_str("headers:\nquery:\nrequests: {}\n").unwrap();
+        assert!(folder.headers.is_empty());
+        assert!(folder.query.is_empty());
+    }
+
+    /// Single values are serialized without the list, for brevity
+    #[test]
+    fn test_multimap_serialize() {
+        let recipe = Recipe {
+            query: indexmap! {
+                "id".into() => vec!["1".into(), "2".into()],
+                "sort".into() => vec!["name".into()],
+            },
+            ..serde_yaml::from_str("{}").unwrap()
+        };
+        let serialized = serde_yaml::to_value(&recipe).unwrap();
+        assert_eq!(
+            serialized["query"],
+            serde_yaml::from_str::<serde_yaml::Value>(
+                "id: ['1', '2']\nsort: name"
+            )
+            .unwrap()
+        );
+    }
+
+    /// Indent every line of a YAML snippet, to nest it under a key
+    fn indent(yaml: &str) -> String {
+        yaml.lines().flat_map(|line| ["  ", line, "\n"]).collect()
+    }
+
+    #[rstest]
+    #[case::empty("{}", HttpSettings::default())]
+    #[case::full(
+        "timeout: 30s\nredirect: !max 5\nhttp_version: http2\ngzip: true",
+        HttpSettings {
+            timeout: Some(Duration::from_secs(30)),
+            redirect: Some(RedirectPolicy::Max(5)),
+            http_version: Some(HttpVersion::Http2),
+            gzip: Some(true),
+        },
+    )]
+    #[case::redirect_none("redirect: none", HttpSettings {
+        redirect: Some(RedirectPolicy::None),
+        ..Default::default()
+    })]
+    fn test_http_settings(#[case] yaml: &str, #[case] expected: HttpSettings) {
+        let settings: HttpSettings = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(settings, expected);
+    }
+
+    #[rstest]
+    #[case::default("", ChainRequestSection::Body)]
+    #[case::status("section: status", ChainRequestSection::Status)]
+    #[case::headers("section: !headers", ChainRequestSection::Headers)]
+    #[case::header(
+        "section: !header Location",
+        ChainRequestSection::Header("Location".into()),
+    )]
+    fn test_chain_request_section(
+        #[case] yaml: &str,
+        #[case] expected: ChainRequestSection,
+    ) {
+        let source: ChainSource =
+            serde_yaml::from_str(&format!("!request\nrecipe: login\n{yaml}"))
+                .unwrap();
+        assert_eq!(
+            source,
+            ChainSource::Request {
+                recipe: "login".into(),
+                trigger: Default::default(),
+                section: expected,
+            }
+        );
+    }
+
+    #[rstest]
+    #[case::code("200", 200, 200)]
+    #[case::code_string("'204'", 204, 204)]
+    #[case::class("4xx", 400, 499)]
+    #[case::range("200-204", 200, 204)]
+    fn test_status_range(
+        #[case] yaml: &str,
+        #[case] min: u16,
+        #[case] max: u16,
+    ) {
+        let range: StatusRange = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(range, StatusRange { min, max });
+    }
+
+    #[rstest]
+    #[case::invalid_class("2x", "Invalid status `2x`")]
+    #[case::backwards_range("299-200", "Invalid status `299-200`")]
+    #[case::not_a_number("ok", "Invalid status `ok`")]
+    fn test_status_range_error(#[case] yaml: &str, #[case] expected_err: &str) {
+        assert_err!(serde_yaml::from_str::<StatusRange>(yaml), expected_err);
+    }
+
+    #[test]
+    fn test_assertion_invalid_regex() {
+        assert_err!(
+            serde_yaml::from_str::<Assertion>(
+                "!header {name: Accept, expect: !matches '('}"
+            ),
+            "regex parse error"
+        );
+    }
 }
@@ -0,0 +1,725 @@
+//! Static analysis of a collection, to find problems without sending any
+//! requests
+
+use crate::{
+    collection::{
+        ApiKeyLocation, Authentication, Chain, ChainId, ChainSource,
+        Collection, Folder, MultipartField, Profile, ProfileId, Recipe,
+        RecipeBody, RecipeId, RecipeNode,
+    },
+    template::{Template, TemplateKey},
+};
+use indexmap::IndexMap;
+use itertools::Itertools;
+use reqwest::header::HeaderName;
+use std::collections::HashSet;
+use url::Url;
+
+/// A problem found in a collection
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Issue {
+    /// The profile/chain/recipe that has the problem
+    pub location: IssueLocation,
+    /// A piece of text within the location that caused the problem, e.g. a
+    /// template. Used to narrow down the location
+    pub snippet: Option<String>,
+    pub message: String,
+}
+
+/// The part of a collection that an [Issue] was found in
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum IssueLocation {
+    Profile(ProfileId),
+    Chain(ChainId),
+    /// A recipe or folder
+    Recipe(RecipeId),
+}
+
+/// Check a collection for problems that would prevent requests from being
+/// built, without actually building anything. Issues are ordered by location:
+/// profiles, then chains, then recipes and folders.
+pub fn check(collection: &Collection) -> Vec<Issue> {
+    let mut checker = Checker {
+        collection,
+        issues: Vec::new(),
+    };
+    checker.check_profiles();
+    checker.check_chains();
+    checker.check_chain_cycles();
+    checker.check_recipes();
+    // The same problem can show up multiple times in one location, e.g. if a
+    // field is used twice in a recipe
+    checker.issues.into_iter().unique().collect()
+}
+
+struct Checker<'a> {
+    collection: &'a Collection,
+    issues: Vec<Issue>,
+}
+
+impl<'a> Checker<'a> {
+    fn check_profiles(&mut self) {
+        let profiles = &self.collection.profiles;
+        for profile in profiles.values() {
+            // Profile templates are rendered with the selected profile, so
+            // check against every profile that inherits this one
+            let renderers = profiles
+                .values()
+                .filter(|other| {
+                    other
+                        .lineage(profiles)
+                        .iter()
+                        .any(|ancestor| ancestor.id == profile.id)
+                })
+                .collect_vec();
+            for template in profile.data.values() {
+                self.check_template(
+                    IssueLocation::Profile(profile.id.clone()),
+                    template,
+                    &renderers,
+                );
+            }
+        }
+    }
+
+    fn check_chains(&mut self) {
+        let all_profiles = self.collection.profiles.values().collect_vec();
+        for chain in self.collection.chains.values() {
+            let location = IssueLocation::Chain(chain.id.clone());
+            if let ChainSource::Request { recipe, .. } = &chain.source {
+                let message = match self.collection.recipes.get(recipe) {
+                    Some(RecipeNode::Recipe(_)) => None,
+                    Some(RecipeNode::Folder(_)) => Some(format!(
+                        "Chain requests folder `{recipe}`; only recipes can \
+                        be requested"
+                    )),
+                    None => Some(format!(
+                        "Chain requests unknown recipe `{recipe}`"
+                    )),
+                };
+                if let Some(message) = message {
+                    self.issues.push(Issue {
+                        location: location.clone(),
+                        snippet: Some(recipe.to_string()),
+                        message,
+                    });
+                }
+            }
+            for template in chain_templates(chain) {
+                self.check_template(location.clone(), template, &all_profiles);
+            }
+        }
+    }
+
+    /// Find chains that depend on themselves, either directly or through
+    /// other chains. A chain depends on every chain used in its templates, or
+    /// in the recipe that it requests. This includes chains used by any
+    /// profile field that those templates use.
+    fn check_chain_cycles(&mut self) {
+        let collection = self.collection;
+        let get_chains = |templates: &[&Template]| -> Vec<&'a Chain> {
+            chain_ids(&collection.profiles, templates)
+                .into_iter()
+                .filter_map(|id| {
+                    collection.chains.get(&ChainId::<&str>::from(id))
+                })
+                .collect()
+        };
+        let dependencies = |chain: &Chain| -> Vec<&'a Chain> {
+            match &chain.source {
+                ChainSource::Request { recipe, .. } => {
+                    let Some(recipe) = collection.recipes.get_recipe(recipe)
+                    else {
+                        return Vec::new();
+                    };
+                    let mut recipe = recipe.clone();
+                    collection.recipes.apply_inheritance(&mut recipe);
+                    get_chains(&recipe_templates(&recipe))
+                }
+                _ => get_chains(&chain_templates(chain)),
+            }
+        };
+
+        fn visit<'a>(
+            chain: &'a Chain,
+            dependencies: &impl Fn(&Chain) -> Vec<&'a Chain>,
+            stack: &mut Vec<&'a ChainId>,
+            visited: &mut HashSet<&'a ChainId>,
+            cycles: &mut Vec<Vec<&'a ChainId>>,
+        ) {
+            if let Some(position) = stack.iter().position(|id| **id == chain.id)
+            {
+                cycles.push(stack[position..].to_vec());
+                return;
+            }
+            if !visited.insert(&chain.id) {
+                return;
+            }
+            stack.push(&chain.id);
+            for dependency in dependencies(chain) {
+                visit(dependency, dependencies, stack, visited, cycles);
+            }
+            stack.pop();
+        }
+
+        let mut visited = HashSet::new();
+        let mut cycles = Vec::new();
+        for chain in collection.chains.values() {
+            visit(
+                chain,
+                &dependencies,
+                &mut Vec::new(),
+                &mut visited,
+                &mut cycles,
+            );
+        }
+        for cycle in cycles {
+            let first = cycle[0];
+            self.issues.push(Issue {
+                location: IssueLocation::Chain(first.clone()),
+                snippet: None,
+                message: format!(
+                    "Chain dependency cycle: {}",
+                    cycle.iter().chain([&first]).join(" -> ")
+                ),
+            });
+        }
+    }
+
+    fn check_recipes(&mut self) {
+        let all_profiles = self.collection.profiles.values().collect_vec();
+        for (_, node) in self.collection.recipes.iter() {
+            let location = IssueLocation::Recipe(node.id().clone());
+            let (templates, headers, authentication) = match node {
+                RecipeNode::Folder(folder) => (
+                    folder_templates(folder),
+                    folder.headers.keys().collect_vec(),
+                    folder.authentication.as_ref(),
+                ),
+                RecipeNode::Recipe(recipe) => {
+                    self.check_url(recipe);
+                    (
+                        recipe_templates(recipe),
+                        recipe.headers.keys().collect_vec(),
+                        recipe.authentication.as_ref(),
+                    )
+                }
+            };
+
+            for template in templates {
+                self.check_template(location.clone(), template, &all_profiles);
+            }
+
+            let api_key_header = match authentication {
+                Some(Authentication::ApiKey {
+                    location: ApiKeyLocation::Header,
+                    name,
+                    ..
+                }) => Some(name.as_str()),
+                _ => None,
+            };
+            // Header names can be templates. Keys could render to anything,
+            // so only check the static parts
+            let headers = headers
+                .into_iter()
+                .map(|header| (header.as_str(), header.fill_keys("a")))
+                .chain(api_key_header.map(|name| (name, name.to_owned())));
+            for (header, filled) in headers {
+                if HeaderName::try_from(filled).is_err() {
+                    self.issues.push(Issue {
+                        location: location.clone(),
+                        snippet: Some(header.to_owned()),
+                        message: format!("Invalid header name `{header}`"),
+                    });
+                }
+            }
+        }
+    }
+
+    /// Make sure a recipe's URL could be valid. Any template keys could
+    /// render to anything, so we can only check what's around them. If the
+    /// URL starts with a key, anything goes.
+    fn check_url(&mut self, recipe: &Recipe) {
+        let mut resolved = recipe.clone();
+        self.collection.recipes.apply_inheritance(&mut resolved);
+        let Some(url) = &resolved.url else {
+            return;
+        };
+        if url.as_str().starts_with("{{") {
+            return;
+        }
+        // Any value would do here, but a number is valid in the most places
+        // (host, port, path, etc.)
+        if let Err(error) = Url::parse(&url.fill_keys("1")) {
+            self.issues.push(Issue {
+                location: IssueLocation::Recipe(recipe.id.clone()),
+                snippet: recipe.url.as_ref().map(|url| url.to_string()),
+                message: format!("URL `{url}` can never be valid: {error}"),
+            });
+        }
+    }
+
+    /// Make sure every field used in a template is defined in each given
+    /// profile, and every chain it uses exists
+    fn check_template(
+        &mut self,
+        location: IssueLocation,
+        template: &Template,
+        profiles: &[&Profile],
+    ) {
+        let all_profiles = &self.collection.profiles;
+        // Check every key used, including within expressions. Keys with a
+        // fallback are allowed to fail, so don't check those
+        for key in template.keys().flat_map(|key| key.required_references()) {
+            let message = match key {
+                TemplateKey::Field(field) => {
+                    let missing = profiles
+                        .iter()
+                        .filter(|profile| {
+                            profile.resolve_field(all_profiles, field).is_none()
+                        })
+                        .map(|profile| format!("`{}`", profile.id))
+                        .collect_vec();
+                    if profiles.is_empty() {
+                        format!("Field `{field}` is not defined in any profile")
+                    } else if !missing.is_empty() {
+                        format!(
+                            "Field `{field}` is not defined in profile(s) {}",
+                            missing.join(", ")
+                        )
+                    } else {
+                        continue;
+                    }
+                }
+                TemplateKey::Chain(chain_id) => {
+                    if self
+                        .collection
+                        .chains
+                        .contains_key(&ChainId::<&str>::from(chain_id))
+                    {
+                        continue;
+                    }
+                    format!("Unknown chain `{chain_id}`")
+                }
+                // Environment variables can't be checked ahead of time
+                TemplateKey::Environment(_) => continue,
+                // References are never expressions
+                TemplateKey::Expression(_) => continue,
+                // Loop variables are bound by the template itself
+                TemplateKey::Variable(_) => continue,
+            };
+            self.issues.push(Issue {
+                location: location.clone(),
+                snippet: Some(template.to_string()),
+                message,
+            });
+        }
+    }
+}
+
+/// Get the IDs of every chain used in some templates. Fields are followed into
+/// their values in every profile, because any chain used in a field's value is
+/// rendered wherever the field is used.
+fn chain_ids<'a>(
+    profiles: &'a IndexMap<ProfileId, Profile>,
+    templates: &[&'a Template],
+) -> Vec<&'a str> {
+    let mut chain_ids = Vec::new();
+    let mut visited_fields = HashSet::new();
+    let mut templates = templates.to_vec();
+    // Templates are added as fields are found, so iterate by index
+    let mut i = 0;
+    while let Some(template) = templates.get(i) {
+        for key in template.keys().flat_map(|key| key.references()) {
+            match key {
+                TemplateKey::Chain(chain_id) => chain_ids.push(chain_id),
+                TemplateKey::Field(field) if visited_fields.insert(field) => {
+                    templates.extend(
+                        profiles
+                            .values()
+                            .filter_map(|profile| profile.data.get(field)),
+                    );
+                }
+                _ => {}
+            }
+        }
+        i += 1;
+    }
+    chain_ids
+}
+
+/// Get every template in a chain
+fn chain_templates(chain: &Chain) -> Vec<&Template> {
+    match &chain.source {
+        ChainSource::Request { .. } => Vec::new(),
+        ChainSource::Command { command } => command.iter().collect(),
+        ChainSource::File { path } => vec![path],
+        ChainSource::Prompt { message, default } => {
+            message.iter().chain(default).collect()
+        }
+    }
+}
+
+/// Get every template defined directly on a folder
+fn folder_templates(folder: &Folder) -> Vec<&Template> {
+    folder
+        .url_prefix
+        .iter()
+        .chain(folder.query.values().flatten())
+        .chain(folder.headers.keys())
+        .chain(folder.headers.values().flatten())
+        .chain(
+            folder
+                .authentication
+                .iter()
+                .flat_map(authentication_templates),
+        )
+        .collect()
+}
+
+/// Get every template defined directly on a recipe
+fn recipe_templates(recipe: &Recipe) -> Vec<&Template> {
+    let body: Vec<&Template> = match &recipe.body {
+        None => Vec::new(),
+        Some(RecipeBody::Raw(template)) => vec![template],
+        Some(RecipeBody::FormUrlencoded(fields)) => fields.values().collect(),
+        Some(RecipeBody::FormMultipart(fields)) => fields
+            .values()
+            .map(|field| match field {
+                MultipartField::Text(template)
+                | MultipartField::File(template) => template,
+            })
+            .collect(),
+        Some(RecipeBody::Json(value)) => value.templates(),
+    };
+    recipe
+        .url
+        .iter()
+        .chain(recipe.path.values())
+        .chain(recipe.query.values().flatten())
+        .chain(recipe.headers.keys())
+        .chain(recipe.headers.values().flatten())
+        .chain(body)
+        .chain(
+            recipe
+                .authentication
+                .iter()
+                .flat_map(authentication_templates),
+        )
+        .collect()
+}
+
+/// Get every template in an authentication scheme
+fn authentication_templates(authentication: &Authentication) -> Vec<&Template> {
+    match authentication {
+        Authentication::Basic { username, password } => {
+            [Some(username), password.as_ref()]
+                .into_iter()
+                .flatten()
+                .collect()
+        }
+        Authentication::Bearer(token) => vec![token],
+        Authentication::ApiKey { value, .. } => vec![value],
+        Authentication::Digest { username, password } => {
+            vec![username, password]
+        }
+        Authentication::Oauth2ClientCredentials {
+            token_url,
+            client_id,
+            client_secret,
+            scope,
+        } => [
+            Some(token_url),
+            Some(client_id),
+            Some(client_secret),
+            scope.as_ref(),
+        ]
+        .into_iter()
+        .flatten()
+        .collect(),
+        Authentication::Oauth2Password {
+            token_url,
+            client_id,
+            client_secret,
+            username,
+            password,
+            scope,
+        } => [
+            Some(token_url),
+            Some(client_id),
+            client_secret.as_ref(),
+            Some(username),
+            Some(password),
+            scope.as_ref(),
+        ]
+        .into_iter()
+        .flatten()
+        .collect(),
+        Authentication::Oauth2RefreshToken {
+            token_url,
+            client_id,
+            client_secret,
+            refresh_token,
+            scope,
+        } => [
+            Some(token_url),
+            Some(client_id),
+            client_secret.as_ref(),
+            Some(refresh_token),
+            scope.as_ref(),
+        ]
+        .into_iter()
+        .flatten()
+        .collect(),
+        Authentication::AwsSigV4 {
+            access_key,
+            secret_key,
+            session_token,
+            region,
+            service,
+        } => [
+            Some(access_key),
+            Some(secret_key),
+            session_token.as_ref(),
+            Some(region),
+            Some(service),
+        ]
+        .into_iter()
+        .flatten()
+        .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+
+    /// Check a collection defined in YAML, and return just the messages
+    fn check_yaml(yaml: &str) -> Vec<String> {
+        let collection: Collection = serde_yaml::from_str(yaml).unwrap();
+        check(&collection)
+            .into_iter()
+            .map(|issue| issue.message)
+            .collect()
+    }
+
+    #[rstest]
+    #[case::valid(
+        r#"
+profiles:
+  local:
+    data:
+      host: http://localhost
+chains:
+  token:
+    source: !request
+      recipe: login
+requests:
+  login: !request
+    method: POST
+    url: "{{host}}/login"
+  me: !request
+    method: GET
+    url: "{{host}}/me"
+    headers:
+      Authorization: "Bearer {{chains.token}}"
+"#,
+        &[],
+    )]
+    #[case::field_missing_from_profile(
+        r#"
+profiles:
+  local:
+    data:
+      host: http://localhost
+  prd:
+    data: {}
+requests:
+  me: !request
+    method: GET
+    url: "{{host}}/me"
+"#,
+        &["Field `host` is not defined in profile(s) `prd`"],
+    )]
+    #[case::field_no_profiles(
+        r#"
+requests:
+  me: !request
+    method: GET
+    url: "{{host}}/me"
+"#,
+        &["Field `host` is not defined in any profile"],
+    )]
+    #[case::field_inherited(
+        r#"
+profiles:
+  base:
+    data:
+      host: http://localhost
+  local:
+    extends: base
+    data:
+      url: "{{host}}/api"
+requests:
+  me: !request
+    method: GET
+    url: "{{url}}/me"
+"#,
+        &["Field `url` is not defined in profile(s) `base`"],
+    )]
+    #[case::unknown_chain(
+        r#"
+requests:
+  me: !request
+    method: GET
+    url: "http://localhost/{{chains.missing}}"
+"#,
+        &["Unknown chain `missing`"],
+    )]
+    #[case::expression(
+        r#"
+requests:
+  me: !request
+    method: GET
+    url: "http://localhost/{{ base64(chains.missing) | lower }}/{{ uuid() }}"
+"#,
+        &["Unknown chain `missing`"],
+    )]
+    // Only the last fallback has to be defined
+    #[case::fallback(
+        r#"
+requests:
+  me: !request
+    method: GET
+    url: "{{ host ?? \"http://localhost\" }}/{{ a ?? b }}"
+"#,
+        &["Field `b` is not defined in any profile"],
+    )]
+    // Loop variables are bound by the template, but everything else in a
+    // block is checked
+    #[case::blocks(
+        r#"
+requests:
+  me: !request
+    method: POST
+    url: "http://localhost"
+    body: "{% for id in ids %}{{ id | upper }}{{ loop.index }}{% endfor %}\
+      {% if debug %}{{ id }}{% endif %}"
+"#,
+        &[
+            "Field `ids` is not defined in any profile",
+            "Field `debug` is not defined in any profile",
+            "Field `id` is not defined in any profile",
+        ],
+    )]
+    #[case::chain_unknown_recipe(
+        r#"
+chains:
+  token:
+    source: !request
+      recipe: login
+"#,
+        &["Chain requests unknown recipe `login`"],
+    )]
+    #[case::chain_folder(
+        r#"
+chains:
+  token:
+    source: !request
+      recipe: auth
+requests:
+  auth: !folder
+    requests: {}
+"#,
+        &["Chain requests folder `auth`; only recipes can be requested"],
+    )]
+    #[case::chain_cycle(
+        r#"
+chains:
+  a:
+    source: !file
+      path: "{{chains.b}}"
+  b:
+    source: !request
+      recipe: get_b
+requests:
+  get_b: !request
+    method: GET
+    url: "http://localhost/{{chains.a}}"
+"#,
+        &["Chain dependency cycle: a -> b -> a"],
+    )]
+    #[case::chain_cycle_through_profile(
+        r#"
+profiles:
+  local:
+    data:
+      token: "{{chains.a}}"
+chains:
+  a:
+    source: !file
+      path: "{{chains.b}}"
+  b:
+    source: !command
+      command: ["echo", "{{token}}"]
+"#,
+        &["Chain dependency cycle: a -> b -> a"],
+    )]
+    #[case::invalid_header(
+        r#"
+requests:
+  me: !request
+    method: GET
+    url: "http://localhost/me"
+    headers:
+      "Bad Header": value
+    authentication: !api_key
+      location: header
+      name: "x:key"
+      value: abc
+"#,
+        &["Invalid header name `Bad Header`", "Invalid header name `x:key`"],
+    )]
+    #[case::templated_header(
+        r#"
+profiles:
+  local:
+    data:
+      tenant: acme
+requests:
+  me: !request
+    method: GET
+    url: "http://localhost/me"
+    headers:
+      "X-{{tenant}}-Token": abc
+      "X {{tenant}}": abc
+      "X-{{region}}": abc
+"#,
+        &[
+            "Field `region` is not defined in profile(s) `local`",
+            "Invalid header name `X {{tenant}}`",
+        ],
+    )]
+    #[case::invalid_url(
+        r#"
+requests:
+  me: !request
+    method: GET
+    url: "localhost/{{id}}"
+  ok: !request
+    method: GET
+    url: "{{host}}/me"
+"#,
+        &[
+            "URL `localhost/{{id}}` can never be valid: \
+            relative URL without a base",
+            "Field `id` is not defined in any profile",
+            "Field `host` is not defined in any profile",
+        ],
+    )]
+    fn test_check(#[case] yaml: &str, #[case] expected: &[&str]) {
+        assert_eq!(check_yaml(yaml), expected);
+    }
+}
@@ -3,8 +3,8 @@
 
 use crate::{
     collection::{
-        self, Collection, Folder, Method, Profile, ProfileId, Recipe, RecipeId,
-        RecipeNode, RecipeTree,
+        self, Collection, Folder, HttpSettings, Method, Profile, ProfileId,
+        Recipe, RecipeId, RecipeNode, RecipeTree,
     },
     template::Template,
 };
@@ -13,7 +13,7 @@ use indexmap::IndexMap;
 use itertools::Itertools;
 use reqwest::header;
 use serde::{Deserialize, Deserializer};
-use std::{collections::HashMap, fs::File, path::Path};
+use std::{collections::HashMap, fs::File, hash::Hash, path::Path};
 use tracing::{info, warn};
 
 impl Collection {
@@ -62,6 +62,7 @@ impl Collection {
             build_recipe_tree(&workspace_id, request_groups, requests)?;
 
         Ok(Collection {
+            imports: Vec::new(),
             profiles,
             recipes,
             // Parse templates into chains:
@@ -158,6 +159,10 @@ enum Authentication {
     Bearer {
         token: String,
     },
+    Digest {
+        username: String,
+        password: String,
+    },
     /// Catch-all for unknown variants
     #[serde(untagged)]
     Other {
@@ -244,11 +249,15 @@ impl From<Environment> for Profile {
         Profile {
             id: environment.id.into(),
             name: Some(environment.name),
+            extends: Vec::new(),
+            env_file: None,
             data: environment
                 .data
                 .into_iter()
                 .map(|(k, v)| (k, Template::dangerous(v)))
                 .collect(),
+            http: HttpSettings::default(),
+            sensitive_fields: Default::default(),
         }
     }
 }
@@ -258,6 +267,12 @@ impl From<RequestGroup> for RecipeNode {
         RecipeNode::Folder(Folder {
             id: folder.id.into(),
             name: Some(folder.name),
+            description: None,
+            tags: Vec::new(),
+            url_prefix: None,
+            authentication: None,
+            query: IndexMap::new(),
+            headers: IndexMap::new(),
             // This will be populated later
             children: IndexMap::new(),
         })
@@ -266,19 +281,23 @@ impl From<RequestGroup> for RecipeNode {
 
 impl From<Request> for RecipeNode {
     fn from(request: Request) -> Self {
-        let mut headers: IndexMap<String, Template> = IndexMap::new();
+        let mut headers: IndexMap<Template, Vec<Template>> = IndexMap::new();
 
         // Preload headers from implicit sources
         if let Some(Body { mime_type, .. }) = &request.body {
             headers.insert(
-                header::CONTENT_TYPE.as_str().into(),
-                Template::dangerous(mime_type.clone()),
+                Template::dangerous(header::CONTENT_TYPE.as_str().into()),
+                vec![Template::dangerous(mime_type.clone())],
             );
         }
-        // Load explicit headers *after* so we can override the implicit stuff
-        for header in request.headers {
-            headers.insert(header.name.to_lowercase(), header.value);
-        }
+        // Load explicit headers *after* so we can override the implicit stuff.
+        // Headers can be repeated, so group the values by name
+        headers.extend(group(request.headers.into_iter().map(|header| {
+            (
+                Template::dangerous(header.name.to_lowercase()),
+                header.value,
+            )
+        })));
         headers.shift_remove(header::USER_AGENT.as_str());
 
         // Load authentication scheme
@@ -298,20 +317,38 @@ impl From<Request> for RecipeNode {
         RecipeNode::Recipe(Recipe {
             id: request.id.into(),
             name: Some(request.name),
-            method: request.method,
-            url: request.url,
-            body: request.body.map(|body| body.text),
-            query: request
-                .parameters
-                .into_iter()
-                .map(|parameter| (parameter.name, parameter.value))
-                .collect(),
+            description: None,
+            tags: Vec::new(),
+            extends: None,
+            method: Some(request.method),
+            url: Some(request.url),
+            path: IndexMap::new(),
+            body: request.body.map(|body| body.text.into()),
+            query: group(
+                request
+                    .parameters
+                    .into_iter()
+                    .map(|parameter| (parameter.name, parameter.value)),
+            ),
             headers,
             authentication,
+            http: HttpSettings::default(),
+            assertions: Vec::new(),
         })
     }
 }
 
+/// Group key-value pairs by key, to handle repeated query params/headers
+fn group<K: Hash + Eq>(
+    pairs: impl IntoIterator<Item = (K, Template)>,
+) -> IndexMap<K, Vec<Template>> {
+    let mut map: IndexMap<K, Vec<Template>> = IndexMap::new();
+    for (key, value) in pairs {
+        map.entry(key).or_default().push(value);
+    }
+    map
+}
+
 /// Convert authentication type. If the type is unknown, return is as `Err`
 impl TryFrom<Authentication> for collection::Authentication {
     type Error = String;
@@ -327,6 +364,12 @@ impl TryFrom<Authentication> for collection::Authentication {
             Authentication::Bearer { token } => Ok(
                 collection::Authentication::Bearer(Template::dangerous(token)),
             ),
+            Authentication::Digest { username, password } => {
+                Ok(collection::Authentication::Digest {
+                    username: Template::dangerous(username),
+                    password: Template::dangerous(password),
+                })
+            }
             // Caller should print a warning for this
             Authentication::Other { kind } => Err(kind),
         }
@@ -373,7 +416,11 @@ fn build_profiles(
                 Profile {
                     id,
                     name: Some(environment.name),
+                    extends: Vec::new(),
+                    env_file: None,
                     data,
+                    http: HttpSettings::default(),
+                    sensitive_fields: Default::default(),
                 },
             )
         })
@@ -461,7 +508,7 @@ where
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::collection::CollectionFile;
+    use crate::{collection::CollectionFile, config::Config};
     use indexmap::indexmap;
     use pretty_assertions::assert_eq;
     use serde::de::DeserializeOwned;
@@ -479,10 +526,13 @@ mod tests {
     #[tokio::test]
     async fn test_insomnia_import() {
         let imported = Collection::from_insomnia(INSOMNIA_FILE).unwrap();
-        let expected = CollectionFile::load(INSOMNIA_IMPORTED_FILE.into())
-            .await
-            .unwrap()
-            .collection;
+        let expected = CollectionFile::load(
+            INSOMNIA_IMPORTED_FILE.into(),
+            &Config::default(),
+        )
+        .await
+        .unwrap()
+        .collection;
         assert_eq!(imported, expected);
     }
 
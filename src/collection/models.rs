@@ -4,6 +4,7 @@ use crate::{
     collection::{
         cereal,
         recipe_tree::{RecipeNode, RecipeTree},
+        schema::{DurationString, MultiMap, OneOrMany},
     },
     http::{ContentType, Query},
     template::Template,
@@ -13,16 +14,32 @@ use derive_more::{Deref, Display, From, FromStr};
 use equivalent::Equivalent;
 use indexmap::IndexMap;
 use itertools::Itertools;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::time::Duration;
+use std::{
+    borrow::Borrow,
+    collections::{HashMap, HashSet},
+    fmt,
+    hash::Hash,
+    mem,
+    path::PathBuf,
+    time::Duration,
+};
 use strum::{EnumIter, IntoEnumIterator};
 
 /// A collection of profiles, requests, etc. This is the primary Slumber unit
 /// of configuration.
-#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize, JsonSchema)]
 #[cfg_attr(test, derive(PartialEq))]
 #[serde(deny_unknown_fields)]
 pub struct Collection {
+    /// Other collection files to pull profiles, chains, and requests from.
+    /// Paths are relative to the file that imports them. Imports are merged
+    /// into this collection during loading, so this is empty once loaded.
+    // schemars marks skip_serializing fields as required, unless optional
+    #[serde(default, skip_serializing)]
+    #[schemars(with = "Option<Vec<PathBuf>>")]
+    pub imports: Vec<PathBuf>,
     #[serde(default, deserialize_with = "cereal::deserialize_id_map")]
     pub profiles: IndexMap<ProfileId, Profile>,
     #[serde(default, deserialize_with = "cereal::deserialize_id_map")]
@@ -36,18 +53,48 @@ pub struct Collection {
     /// ignore anything that starts with `.` (recursively) but that
     /// requires a custom serde impl for each type, or changes to the macro
     #[serde(default, skip_serializing, rename = ".ignore")]
+    #[schemars(
+        with = "Option<serde_json::Value>",
+        description = "Arbitrary data, e.g. YAML anchors. Ignored by Slumber"
+    )]
     pub _ignore: serde::de::IgnoredAny,
 }
 
 /// Mutually exclusive hot-swappable config group
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
 #[cfg_attr(test, derive(PartialEq))]
 #[serde(deny_unknown_fields)]
 pub struct Profile {
     #[serde(skip)] // This will be auto-populated from the map key
     pub id: ProfileId,
     pub name: Option<String>,
+    /// Parent profile(s) whose data is inherited by this one. Fields defined
+    /// on this profile take precedence over inherited ones, and earlier
+    /// parents take precedence over later ones.
+    #[serde(
+        default,
+        skip_serializing_if = "Vec::is_empty",
+        deserialize_with = "cereal::deserialize_one_or_many"
+    )]
+    #[schemars(with = "OneOrMany<ProfileId>")]
+    pub extends: Vec<ProfileId>,
+    /// Load additional fields from a dotenv file, relative to the collection
+    /// file that defines this profile. Fields in `data` take precedence. The
+    /// fields are merged into `data` when the collection is loaded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub env_file: Option<PathBuf>,
+    /// An empty `data:` is null in YAML, which is accepted as an empty map
+    #[serde(default)]
+    #[schemars(with = "Option<IndexMap<String, Template>>")]
     pub data: IndexMap<String, Template>,
+    /// HTTP client settings for all requests sent under this profile.
+    /// Recipe settings take precedence
+    #[serde(default, skip_serializing_if = "HttpSettings::is_empty")]
+    pub http: HttpSettings,
+    /// Fields whose values were loaded from the secrets file. These are
+    /// masked in the UI and redacted from generated requests.
+    #[serde(skip)]
+    pub sensitive_fields: HashSet<String>,
 }
 
 #[derive(
@@ -62,17 +109,39 @@ pub struct Profile {
     PartialEq,
     Serialize,
     Deserialize,
+    JsonSchema,
 )]
 pub struct ProfileId(String);
 
 /// A gathering of like-minded recipes and/or folders
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
 #[cfg_attr(test, derive(PartialEq))]
 #[serde(deny_unknown_fields)]
 pub struct Folder {
     #[serde(skip)] // This will be auto-populated from the map key
     pub id: RecipeId,
     pub name: Option<String>,
+    /// Markdown documentation for this folder
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Labels for filtering. Every recipe in the folder has these tags too
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Prepended to the URL of every recipe in this folder. Prefixes of
+    /// nested folders are joined, outermost first
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url_prefix: Option<Template>,
+    /// Default authentication for every recipe in this folder
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub authentication: Option<Authentication>,
+    /// Default query parameters for every recipe in this folder
+    #[serde(default, with = "cereal::serde_multimap")]
+    #[schemars(with = "MultiMap")]
+    pub query: IndexMap<String, Vec<Template>>,
+    /// Default headers for every recipe in this folder
+    #[serde(default, with = "cereal::serde_multimap")]
+    #[schemars(with = "MultiMap")]
+    pub headers: IndexMap<Template, Vec<Template>>,
     /// RECURSION. Use `requests` in serde to match the root field.
     #[serde(
         default,
@@ -86,24 +155,57 @@ pub struct Folder {
 /// order to distinguish it from a single instance of an HTTP request. And it's
 /// not called `RequestTemplate` because the word "template" has a specific
 /// meaning related to string interpolation.
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
 #[cfg_attr(test, derive(PartialEq))]
 #[serde(deny_unknown_fields)]
 pub struct Recipe {
     #[serde(skip)] // This will be auto-populated from the map key
     pub id: RecipeId,
     pub name: Option<String>,
+    /// Markdown documentation for this recipe. Not inherited via `extends`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Labels for filtering, e.g. `admin`. Not inherited via `extends`, but
+    /// tags from parent folders apply
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Another recipe to inherit values from. Anything not defined on this
+    /// recipe is taken from the parent, and query parameters and headers are
+    /// merged with the parent's.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extends: Option<RecipeId>,
     /// *Not* a template string because the usefulness doesn't justify the
     /// complexity. This gives the user an immediate error if the method is
-    /// wrong which is helpful.
-    pub method: Method,
-    pub url: Template,
-    pub body: Option<Template>,
+    /// wrong which is helpful. This is only optional so it can be inherited
+    /// via `extends`. Every recipe has a method once inheritance is applied,
+    /// which is checked when the collection is loaded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub method: Option<Method>,
+    /// Optional for the same reason as `method`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<Template>,
+    /// Values for `:param` placeholders in the URL path. Values are
+    /// percent-encoded when inserted. Merged with the parent's
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub path: IndexMap<String, Template>,
+    pub body: Option<RecipeBody>,
     pub authentication: Option<Authentication>,
-    #[serde(default)]
-    pub query: IndexMap<String, Template>,
-    #[serde(default)]
-    pub headers: IndexMap<String, Template>,
+    /// Query parameters. Each parameter can have multiple values
+    #[serde(default, with = "cereal::serde_multimap")]
+    #[schemars(with = "MultiMap")]
+    pub query: IndexMap<String, Vec<Template>>,
+    /// Headers. Each header can have multiple values. Header names are
+    /// templates too, e.g. `X-{{tenant}}-Token`
+    #[serde(default, with = "cereal::serde_multimap")]
+    #[schemars(with = "MultiMap")]
+    pub headers: IndexMap<Template, Vec<Template>>,
+    /// HTTP client settings for this recipe. Unset fields are inherited
+    /// from the parent recipe, then the selected profile
+    #[serde(default, skip_serializing_if = "HttpSettings::is_empty")]
+    pub http: HttpSettings,
+    /// Checks to run against every response received for this recipe
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub assertions: Vec<Assertion>,
 }
 
 #[derive(
@@ -118,6 +220,7 @@ pub struct Recipe {
     PartialEq,
     Serialize,
     Deserialize,
+    JsonSchema,
 )]
 pub struct RecipeId(String);
 
@@ -152,10 +255,56 @@ pub enum Method {
     Trace,
 }
 
+/// Template for a request body. A plain string is a raw body, which is sent
+/// as-is after rendering. Structured bodies are specified with a YAML tag, and
+/// will be encoded according to their type. Serialization/deserialization is
+/// implemented manually in [cereal], so that raw bodies don't need a tag.
+#[derive(Clone, Debug)]
+#[cfg_attr(test, derive(PartialEq))]
+pub enum RecipeBody {
+    /// Raw text/bytes body
+    Raw(Template),
+    /// `application/x-www-form-urlencoded` body. Each field is a template
+    FormUrlencoded(IndexMap<String, Template>),
+    /// `multipart/form-data` body. Each field is a part, which can be text or
+    /// loaded from a file
+    FormMultipart(IndexMap<String, MultipartField>),
+    /// `application/json` body. Strings are templates, and everything else is
+    /// sent as-is
+    Json(JsonTemplate),
+}
+
+/// A JSON value where every string is a template. A string that is exactly
+/// one template key (e.g. `"{{user_id}}"`) keeps the JSON type of its
+/// rendered value. Any other string is rendered and sent as a JSON string.
+/// Serialization/deserialization is implemented manually in [cereal].
+#[derive(Clone, Debug)]
+#[cfg_attr(test, derive(PartialEq))]
+pub enum JsonTemplate {
+    Null,
+    Bool(bool),
+    Number(serde_json::Number),
+    String(Template),
+    Array(Vec<JsonTemplate>),
+    Object(IndexMap<String, JsonTemplate>),
+}
+
+/// A single part in a multipart form body. Like [RecipeBody], a plain string
+/// is a text field, and the `!file` tag indicates a file upload.
+#[derive(Clone, Debug)]
+#[cfg_attr(test, derive(PartialEq))]
+pub enum MultipartField {
+    /// Plain text field
+    Text(Template),
+    /// Load the part's content from a file. The file name is included in the
+    /// part's `Content-Disposition` header
+    File(Template),
+}
+
 /// Shortcut for defining authentication method. If this is defined in addition
 /// to the `Authorization` header, that header will end up being included in the
 /// request twice.
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
 #[cfg_attr(test, derive(PartialEq))]
 #[serde(rename_all = "snake_case", deny_unknown_fields)]
 pub enum Authentication {
@@ -166,12 +315,218 @@ pub enum Authentication {
     },
     /// `Authorization: Bearer {token}`
     Bearer(Template),
+    /// API key, sent in a custom header or query parameter
+    ApiKey {
+        location: ApiKeyLocation,
+        /// Name of the header or query parameter
+        name: String,
+        value: Template,
+    },
+    /// [Digest authentication](https://datatracker.ietf.org/doc/html/rfc7616).
+    /// The request is sent without credentials first, then retried with a
+    /// response to the server's challenge.
+    Digest {
+        username: Template,
+        password: Template,
+    },
+    /// OAuth 2.0 [client credentials grant](https://datatracker.ietf.org/doc/html/rfc6749#section-4.4).
+    /// The fetched access token is sent as a bearer token.
+    Oauth2ClientCredentials {
+        token_url: Template,
+        client_id: Template,
+        client_secret: Template,
+        scope: Option<Template>,
+    },
+    /// OAuth 2.0 [resource owner password credentials grant](https://datatracker.ietf.org/doc/html/rfc6749#section-4.3).
+    /// The fetched access token is sent as a bearer token.
+    Oauth2Password {
+        token_url: Template,
+        client_id: Template,
+        client_secret: Option<Template>,
+        username: Template,
+        password: Template,
+        scope: Option<Template>,
+    },
+    /// Exchange a long-lived OAuth 2.0 [refresh token](https://datatracker.ietf.org/doc/html/rfc6749#section-6)
+    /// for an access token, which is sent as a bearer token
+    Oauth2RefreshToken {
+        token_url: Template,
+        client_id: Template,
+        client_secret: Option<Template>,
+        refresh_token: Template,
+        scope: Option<Template>,
+    },
+    /// Sign the request with [AWS Signature Version 4](https://docs.aws.amazon.com/IAM/latest/UserGuide/reference_aws-signing.html).
+    /// The signature is generated immediately before the request is sent.
+    #[serde(rename = "aws_sigv4")]
+    AwsSigV4 {
+        access_key: Template,
+        secret_key: Template,
+        session_token: Option<Template>,
+        region: Template,
+        service: Template,
+    },
+}
+
+/// Where an API key is placed in the request
+#[derive(Copy, Clone, Debug, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(test, derive(PartialEq))]
+#[serde(rename_all = "snake_case")]
+pub enum ApiKeyLocation {
+    Header,
+    Query,
+}
+
+/// Settings for the HTTP client that sends a request. These can be defined on
+/// a recipe or a profile. Any field left unset on the recipe falls back to the
+/// selected profile, then to the default client behavior.
+#[derive(
+    Clone,
+    Debug,
+    Default,
+    Eq,
+    Hash,
+    PartialEq,
+    Serialize,
+    Deserialize,
+    JsonSchema,
+)]
+#[serde(deny_unknown_fields)]
+pub struct HttpSettings {
+    /// Total time allowed for the request, from sending until the response
+    /// body is finished. No timeout if unset
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "cereal::serde_duration::option"
+    )]
+    #[schemars(with = "Option<DurationString>")]
+    pub timeout: Option<Duration>,
+    /// How to handle redirect responses. Defaults to following up to 10
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub redirect: Option<RedirectPolicy>,
+    /// Preferred HTTP version. Defaults to negotiating with the server
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub http_version: Option<HttpVersion>,
+    /// Request a gzip-encoded response and decompress it transparently.
+    /// Defaults to `false`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gzip: Option<bool>,
+}
+
+/// What the HTTP client should do when it receives a redirect response
+#[derive(
+    Copy, Clone, Debug, Eq, Hash, PartialEq, Serialize, Deserialize, JsonSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum RedirectPolicy {
+    /// Follow redirects, up to the client's default limit of 10
+    Follow,
+    /// Never follow redirects; the redirect response is returned as-is
+    None,
+    /// Follow up to this many redirects, then fail the request
+    Max(usize),
+}
+
+/// HTTP version for the client to use
+#[derive(
+    Copy, Clone, Debug, Eq, Hash, PartialEq, Serialize, Deserialize, JsonSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum HttpVersion {
+    /// Only use HTTP/1.x
+    Http1,
+    /// Use HTTP/2 with prior knowledge, i.e. without negotiating an upgrade
+    Http2,
+}
+
+/// A check to run against a response. Assertions are copied onto each
+/// [Request](crate::http::Request) when it's built, so historical responses
+/// are checked against the assertions they were sent with.
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(test, derive(PartialEq))]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum Assertion {
+    /// Status code is within a range
+    Status(StatusRange),
+    /// Check the value of a response header. Header names are
+    /// case-insensitive
+    Header { name: String, expect: Expectation },
+    /// Query the response body, and check the result
+    Body { query: Query, expect: Expectation },
+    /// Maximum time for the response, in milliseconds
+    Duration(u64),
+}
+
+/// An inclusive range of status codes. Deserialized from a single code (e.g.
+/// `200`), a class (e.g. `2xx`), or a range (e.g. `200-299`).
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(into = "String", try_from = "StringOrInt")]
+pub struct StatusRange {
+    pub min: u16,
+    pub max: u16,
+}
+
+/// What to expect of a value in an [Assertion]
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(test, derive(PartialEq))]
+#[serde(rename_all = "snake_case")]
+pub enum Expectation {
+    /// The value is present. For body queries, at least one value matched
+    Exists,
+    /// The value is equal to this. Header values are compared as strings
+    Equals(serde_json::Value),
+    /// The value, as a string, matches this regex
+    Matches(Pattern),
+    /// The value is of this JSON type
+    Type(JsonType),
+}
+
+/// A regex, validated when the collection is loaded
+#[derive(Clone, Debug, Deref, Display, Serialize, Deserialize)]
+#[serde(into = "String", try_from = "String")]
+pub struct Pattern(regex::Regex);
+
+/// Types of JSON values, for checking in an [Expectation]
+#[derive(
+    Copy,
+    Clone,
+    Debug,
+    Display,
+    Eq,
+    PartialEq,
+    Serialize,
+    Deserialize,
+    JsonSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum JsonType {
+    #[display("null")]
+    Null,
+    #[display("boolean")]
+    Boolean,
+    #[display("number")]
+    Number,
+    #[display("string")]
+    String,
+    #[display("array")]
+    Array,
+    #[display("object")]
+    Object,
+}
+
+/// Helper for deserializing a value that may be a string or integer in YAML
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StringOrInt {
+    Int(u16),
+    String(String),
 }
 
 /// A chain is a means to data from one response in another request. The chain
 /// is the middleman: it defines where and how to pull the value, then recipes
 /// can use it in a template via `{{chains.<chain_id>}}`.
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
 #[cfg_attr(test, derive(PartialEq))]
 #[serde(deny_unknown_fields)]
 pub struct Chain {
@@ -191,6 +546,9 @@ pub struct Chain {
     /// response (e.g. a file) **or** if the response's `Content-Type` header
     /// is incorrect.
     pub content_type: Option<ContentType>,
+    /// Transformations to apply to the value, in order, after the selector
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub transforms: Vec<ChainTransform>,
 }
 
 /// Unique ID for a chain. Takes a generic param so we can create these during
@@ -207,6 +565,7 @@ pub struct Chain {
     PartialEq,
     Serialize,
     Deserialize,
+    JsonSchema,
 )]
 pub struct ChainId<S = String>(S);
 
@@ -230,7 +589,7 @@ impl Equivalent<ChainId> for ChainId<&str> {
 }
 
 /// The source of data for a chain
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
 #[cfg_attr(test, derive(PartialEq))]
 #[serde(rename_all = "snake_case", deny_unknown_fields)]
 pub enum ChainSource {
@@ -240,6 +599,9 @@ pub enum ChainSource {
         /// When should this request be automatically re-executed?
         #[serde(default)]
         trigger: ChainRequestTrigger,
+        /// Which part of the response should the value come from?
+        #[serde(default)]
+        section: ChainRequestSection,
     },
     /// Run an external command to get a result
     Command { command: Vec<Template> },
@@ -256,7 +618,7 @@ pub enum ChainSource {
 
 /// Define when a recipe with a chained request should auto-execute the
 /// dependency request.
-#[derive(Copy, Clone, Debug, Default, Serialize, Deserialize)]
+#[derive(Copy, Clone, Debug, Default, Serialize, Deserialize, JsonSchema)]
 #[cfg_attr(test, derive(PartialEq))]
 #[serde(rename_all = "snake_case", deny_unknown_fields)]
 pub enum ChainRequestTrigger {
@@ -269,16 +631,142 @@ pub enum ChainRequestTrigger {
     NoHistory,
     /// Trigger the request if the last response is older than some
     /// duration (or there is none in history)
-    Expire(#[serde(with = "cereal::serde_duration")] Duration),
+    Expire(
+        #[serde(with = "cereal::serde_duration")]
+        #[schemars(with = "DurationString")]
+        Duration,
+    ),
     /// Trigger the request every time the dependent request is rendered
     Always,
 }
 
+/// The part of a response that a chained request takes its value from
+#[derive(Clone, Debug, Default, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(test, derive(PartialEq))]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum ChainRequestSection {
+    /// The response body
+    #[default]
+    Body,
+    /// The status code, e.g. `200`
+    Status,
+    /// All headers, as a JSON object keyed by lowercase header name. This
+    /// allows a selector to be applied
+    Headers,
+    /// The value of a single header. The name is case-insensitive
+    Header(String),
+}
+
+/// A transformation applied to a chained value, after the selector. Each
+/// transform takes a string and produces a new string.
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(test, derive(PartialEq))]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum ChainTransform {
+    /// Remove leading and trailing whitespace
+    Trim,
+    /// Encode as standard base64
+    Base64Encode,
+    /// Decode from standard base64. The decoded value must be valid UTF-8
+    Base64Decode,
+    /// Percent-encode everything except unreserved URL characters
+    UrlEncode,
+    /// Hash the value, and encode the hash as lowercase hex
+    Hash(HashAlgorithm),
+    /// Replace the value with the first capture group of a regex. If the
+    /// regex has no capture groups, the entire match is used instead
+    Regex(Pattern),
+    /// Escape the value for inclusion in a JSON string. Surrounding quotes are
+    /// *not* added
+    JsonEscape,
+}
+
+/// Hash function for [ChainTransform::Hash]
+#[derive(Copy, Clone, Debug, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(test, derive(PartialEq))]
+#[serde(rename_all = "snake_case")]
+pub enum HashAlgorithm {
+    Md5,
+    Sha256,
+}
+
 impl Profile {
     /// Get a presentable name for this profile
     pub fn name(&self) -> &str {
         self.name.as_deref().unwrap_or(&self.id)
     }
+
+    /// Get this profile and all of its ancestors, in order of precedence. The
+    /// inheritance tree is walked depth-first, so a parent's ancestors take
+    /// precedence over the next parent. Each profile is included only once,
+    /// and unknown parents are skipped. Cycles and unknown parents are
+    /// rejected when the collection is loaded.
+    pub fn lineage<'a>(
+        &'a self,
+        profiles: &'a IndexMap<ProfileId, Profile>,
+    ) -> Vec<&'a Profile> {
+        fn visit<'a>(
+            profile: &'a Profile,
+            profiles: &'a IndexMap<ProfileId, Profile>,
+            lineage: &mut Vec<&'a Profile>,
+        ) {
+            if lineage.iter().any(|other| other.id == profile.id) {
+                return;
+            }
+            lineage.push(profile);
+            for parent_id in &profile.extends {
+                if let Some(parent) = profiles.get(parent_id) {
+                    visit(parent, profiles, lineage);
+                }
+            }
+        }
+
+        let mut lineage = Vec::new();
+        visit(self, profiles, &mut lineage);
+        lineage
+    }
+
+    /// Look up a field in this profile or its ancestors. Returns the template
+    /// along with the profile that defined it
+    pub fn resolve_field<'a>(
+        &'a self,
+        profiles: &'a IndexMap<ProfileId, Profile>,
+        field: &str,
+    ) -> Option<(&'a Profile, &'a Template)> {
+        self.lineage(profiles).into_iter().find_map(|profile| {
+            profile.data.get(field).map(|template| (profile, template))
+        })
+    }
+
+    /// Get all fields available to this profile, including inherited ones.
+    /// Each template is paired with the profile that defined it. Inherited
+    /// fields are ordered first, so fields keep their position when
+    /// overridden.
+    pub fn resolved_data<'a>(
+        &'a self,
+        profiles: &'a IndexMap<ProfileId, Profile>,
+    ) -> IndexMap<&'a str, (&'a Profile, &'a Template)> {
+        let mut data = IndexMap::new();
+        for profile in self.lineage(profiles).into_iter().rev() {
+            for (field, template) in &profile.data {
+                data.insert(field.as_str(), (profile, template));
+            }
+        }
+        data
+    }
+
+    /// Get the HTTP settings for this profile, with unset fields filled in
+    /// from its ancestors
+    pub fn resolved_http(
+        &self,
+        profiles: &IndexMap<ProfileId, Profile>,
+    ) -> HttpSettings {
+        self.lineage(profiles)
+            .into_iter()
+            .fold(HttpSettings::default(), |settings, profile| {
+                settings.or(&profile.http)
+            })
+    }
 }
 
 impl Folder {
@@ -293,6 +781,240 @@ impl Recipe {
     pub fn name(&self) -> &str {
         self.name.as_deref().unwrap_or(&self.id)
     }
+
+    /// Merge values inherited from parent recipes (via `extends`) and parent
+    /// folders into this recipe. Parents should be ordered from nearest to
+    /// farthest, and folders from outermost to innermost. Values defined on
+    /// the recipe take precedence, followed by parent recipes, then folders
+    /// (innermost first). Returns the recipe/folder that each inherited value
+    /// came from.
+    pub fn apply_inheritance(
+        &mut self,
+        parents: &[&Recipe],
+        folders: &[&Folder],
+    ) -> InheritedValues {
+        let mut inherited = InheritedValues::default();
+
+        // Scalar values are taken from the nearest parent that defines them
+        fn inherit<T: Clone>(
+            value: &mut Option<T>,
+            parents: &[&Recipe],
+            get: impl Fn(&Recipe) -> &Option<T>,
+        ) -> Option<RecipeId> {
+            if value.is_some() {
+                return None;
+            }
+            let parent = parents.iter().find(|parent| get(parent).is_some())?;
+            *value = get(parent).clone();
+            Some(parent.id.clone())
+        }
+        inherit(&mut self.method, parents, |recipe| &recipe.method);
+        inherit(&mut self.url, parents, |recipe| &recipe.url);
+        inherit(&mut self.body, parents, |recipe| &recipe.body);
+        inherited.authentication =
+            inherit(&mut self.authentication, parents, |recipe| {
+                &recipe.authentication
+            });
+        for parent in parents {
+            self.http = mem::take(&mut self.http).or(&parent.http);
+        }
+        if self.assertions.is_empty() {
+            if let Some(parent) =
+                parents.iter().find(|parent| !parent.assertions.is_empty())
+            {
+                self.assertions = parent.assertions.clone();
+            }
+        }
+
+        let prefixes = folders
+            .iter()
+            .filter_map(|folder| folder.url_prefix.as_ref())
+            .collect_vec();
+        if let (false, Some(url)) = (prefixes.is_empty(), &self.url) {
+            self.url =
+                Some(Template::concat(prefixes.into_iter().chain([url])));
+        }
+
+        // Maps are layered, lowest precedence first. Query params are
+        // case-sensitive, headers aren't
+        merge_inherited(
+            &mut self.query,
+            &mut inherited.query,
+            folders
+                .iter()
+                .map(|folder| (&folder.id, &folder.query))
+                .chain(
+                    parents
+                        .iter()
+                        .rev()
+                        .map(|parent| (&parent.id, &parent.query)),
+                ),
+            |a, b| a == b,
+        );
+        merge_inherited(
+            &mut self.headers,
+            &mut inherited.headers,
+            folders
+                .iter()
+                .map(|folder| (&folder.id, &folder.headers))
+                .chain(
+                    parents
+                        .iter()
+                        .rev()
+                        .map(|parent| (&parent.id, &parent.headers)),
+                ),
+            str::eq_ignore_ascii_case,
+        );
+        // Path params are specific to a URL, so folders don't define them
+        merge_inherited(
+            &mut self.path,
+            &mut inherited.path,
+            parents
+                .iter()
+                .rev()
+                .map(|parent| (&parent.id, &parent.path)),
+            |a, b| a == b,
+        );
+
+        if self.authentication.is_none() {
+            if let Some(folder) = folders
+                .iter()
+                .rev()
+                .find(|folder| folder.authentication.is_some())
+            {
+                self.authentication = folder.authentication.clone();
+                inherited.authentication = Some(folder.id.clone());
+            }
+        }
+
+        inherited
+    }
+}
+
+/// Which of a recipe's values were inherited from parent recipes or folders,
+/// rather than defined on the recipe itself. Each inherited value is mapped to
+/// the ID of the recipe/folder that defined it.
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(test, derive(PartialEq))]
+pub struct InheritedValues {
+    pub query: HashMap<String, RecipeId>,
+    pub headers: HashMap<String, RecipeId>,
+    pub path: HashMap<String, RecipeId>,
+    pub authentication: Option<RecipeId>,
+}
+
+/// Merge inherited key-value maps underneath a recipe's own map. Layers should
+/// be ordered lowest precedence first. Inherited entries are ordered first.
+/// When a key is overridden, it keeps its original position (and casing). An
+/// overriding key replaces *all* values of the inherited key.
+fn merge_inherited<'a, K, V>(
+    map: &mut IndexMap<K, V>,
+    sources: &mut HashMap<String, RecipeId>,
+    layers: impl Iterator<Item = (&'a RecipeId, &'a IndexMap<K, V>)>,
+    key_eq: impl Fn(&str, &str) -> bool,
+) where
+    K: 'a + Clone + Hash + Eq + Borrow<str>,
+    V: 'a + Clone,
+{
+    let own = mem::take(map);
+    let layers = layers
+        .map(|(source, layer)| (Some(source), layer.clone()))
+        .chain([(None, own)]);
+    for (source, layer) in layers {
+        for (key, value) in layer {
+            let key = match map
+                .iter_mut()
+                .find(|(other, _)| key_eq((*other).borrow(), key.borrow()))
+            {
+                Some((existing_key, existing_value)) => {
+                    *existing_value = value;
+                    existing_key.clone()
+                }
+                None => {
+                    map.insert(key.clone(), value);
+                    key
+                }
+            };
+            let key: &str = key.borrow();
+            match source {
+                Some(source) => {
+                    sources.insert(key.to_owned(), source.clone());
+                }
+                None => {
+                    sources.remove(key);
+                }
+            }
+        }
+    }
+}
+
+impl HttpSettings {
+    /// Are all settings unset?
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+
+    /// Fill in any unset fields from another group of settings
+    pub fn or(self, other: &Self) -> Self {
+        Self {
+            timeout: self.timeout.or(other.timeout),
+            redirect: self.redirect.or(other.redirect),
+            http_version: self.http_version.or(other.http_version),
+            gzip: self.gzip.or(other.gzip),
+        }
+    }
+}
+
+impl RecipeBody {
+    /// Value of the `Content-Type` header implied by the body type. Raw bodies
+    /// don't have an implicit content type. Multipart bodies need to know the
+    /// boundary that will separate their parts.
+    pub fn content_type(&self, boundary: &str) -> Option<String> {
+        match self {
+            Self::Raw(_) => None,
+            Self::FormUrlencoded(_) => {
+                Some("application/x-www-form-urlencoded".into())
+            }
+            Self::FormMultipart(_) => {
+                Some(format!("multipart/form-data; boundary={boundary}"))
+            }
+            Self::Json(_) => Some("application/json".into()),
+        }
+    }
+}
+
+impl JsonTemplate {
+    /// Get every template in this value, depth-first
+    pub fn templates(&self) -> Vec<&Template> {
+        match self {
+            Self::Null | Self::Bool(_) | Self::Number(_) => Vec::new(),
+            Self::String(template) => vec![template],
+            Self::Array(values) => {
+                values.iter().flat_map(Self::templates).collect()
+            }
+            Self::Object(fields) => {
+                fields.values().flat_map(Self::templates).collect()
+            }
+        }
+    }
+}
+
+impl From<Template> for RecipeBody {
+    fn from(template: Template) -> Self {
+        Self::Raw(template)
+    }
+}
+
+impl From<Template> for JsonTemplate {
+    fn from(template: Template) -> Self {
+        Self::String(template)
+    }
+}
+
+impl From<Template> for MultipartField {
+    fn from(template: Template) -> Self {
+        Self::Text(template)
+    }
 }
 
 /// For deserialization
@@ -316,3 +1038,94 @@ impl From<Method> for String {
         method.to_string()
     }
 }
+
+impl StatusRange {
+    /// Is the status code within this range?
+    pub fn contains(&self, status: u16) -> bool {
+        (self.min..=self.max).contains(&status)
+    }
+}
+
+impl Display for StatusRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.min == self.max {
+            write!(f, "{}", self.min)
+        } else if self.min % 100 == 0 && self.max == self.min + 99 {
+            write!(f, "{}xx", self.min / 100)
+        } else {
+            write!(f, "{}-{}", self.min, self.max)
+        }
+    }
+}
+
+impl FromStr for StatusRange {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || {
+            anyhow!(
+                "Invalid status `{s}`. Must be a code (`200`), class (`2xx`) \
+                or range (`200-299`)"
+            )
+        };
+        let parse = |s: &str| s.parse::<u16>().map_err(|_| error());
+        let (min, max) = if let Some(class) = s.strip_suffix("xx") {
+            let min = parse(class)? * 100;
+            (min, min + 99)
+        } else if let Some((min, max)) = s.split_once('-') {
+            (parse(min)?, parse(max)?)
+        } else {
+            let code = parse(s)?;
+            (code, code)
+        };
+        if min > max {
+            return Err(error());
+        }
+        Ok(Self { min, max })
+    }
+}
+
+/// For deserialization
+impl TryFrom<StringOrInt> for StatusRange {
+    type Error = anyhow::Error;
+
+    fn try_from(value: StringOrInt) -> Result<Self, Self::Error> {
+        match value {
+            StringOrInt::Int(code) => Ok(Self {
+                min: code,
+                max: code,
+            }),
+            StringOrInt::String(s) => s.parse(),
+        }
+    }
+}
+
+/// For serialization
+impl From<StatusRange> for String {
+    fn from(range: StatusRange) -> Self {
+        range.to_string()
+    }
+}
+
+/// For deserialization
+impl TryFrom<String> for Pattern {
+    type Error = regex::Error;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        regex::Regex::new(&value).map(Self)
+    }
+}
+
+/// For serialization
+impl From<Pattern> for String {
+    fn from(pattern: Pattern) -> Self {
+        pattern.0.as_str().to_owned()
+    }
+}
+
+#[cfg(test)]
+impl PartialEq for Pattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
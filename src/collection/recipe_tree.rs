@@ -1,8 +1,11 @@
 //! Recipe/folder tree structure
 
-use crate::collection::{cereal::deserialize_id_map, Folder, Recipe, RecipeId};
+use crate::collection::{
+    cereal::deserialize_id_map, Folder, InheritedValues, Recipe, RecipeId,
+};
 use derive_more::{Debug, From};
 use indexmap::{map::Values, IndexMap};
+use schemars::JsonSchema;
 use serde::{de::Error, Deserialize, Deserializer, Serialize};
 
 /// A folder/recipe tree. This is exactly what the user inputs in their
@@ -31,7 +34,7 @@ pub struct RecipeTree {
 pub struct RecipeLookupKey(Vec<RecipeId>);
 
 /// A node in the recipe tree, either a folder or recipe
-#[derive(Clone, Debug, From, Serialize, Deserialize)]
+#[derive(Clone, Debug, From, Serialize, Deserialize, JsonSchema)]
 #[cfg_attr(test, derive(PartialEq))]
 #[serde(rename_all = "snake_case", deny_unknown_fields)]
 #[allow(clippy::large_enum_variant)]
@@ -40,6 +43,8 @@ pub enum RecipeNode {
     /// Rename this variant to match the `requests` field in the root and
     /// folders
     #[serde(rename = "request")]
+    // The doc comment is for developers, so don't put it in the schema
+    #[schemars(description = "A request recipe, tagged `!request`")]
     Recipe(Recipe),
 }
 
@@ -64,6 +69,11 @@ impl RecipeTree {
         Ok(new)
     }
 
+    /// Get the top level of the tree
+    pub fn into_inner(self) -> IndexMap<RecipeId, RecipeNode> {
+        self.tree
+    }
+
     /// Get a recipe/folder's tree lookup key by is unique ID
     pub fn get_lookup_key(&self, id: &RecipeId) -> Option<&RecipeLookupKey> {
         self.nodes_by_id.get(id)
@@ -93,6 +103,87 @@ impl RecipeTree {
         None
     }
 
+    /// Get the folders that contain a node, from outermost to innermost. If
+    /// the ID isn't in the tree, return an empty list
+    pub fn ancestors(&self, id: &RecipeId) -> Vec<&Folder> {
+        let Some(lookup_key) = self.nodes_by_id.get(id) else {
+            return Vec::new();
+        };
+        let mut folders = Vec::new();
+        let mut nodes = &self.tree;
+        // Every step except the last is a folder
+        for step in &lookup_key.0[..lookup_key.0.len() - 1] {
+            match nodes.get(step) {
+                Some(RecipeNode::Folder(folder)) => {
+                    folders.push(folder);
+                    nodes = &folder.children;
+                }
+                _ => panic!(
+                    "Lookup key {lookup_key:?} does not point to a folder at \
+                    `{step}`"
+                ),
+            }
+        }
+        folders
+    }
+
+    /// Get every tag that applies to a recipe/folder: its own tags, plus the
+    /// tags of every folder that contains it. If the ID isn't in the tree,
+    /// return an empty list
+    pub fn tags(&self, id: &RecipeId) -> Vec<&str> {
+        let Some(node) = self.get(id) else {
+            return Vec::new();
+        };
+        let mut tags: Vec<&str> = Vec::new();
+        let folder_tags = self
+            .ancestors(id)
+            .into_iter()
+            .flat_map(|folder| &folder.tags);
+        for tag in folder_tags.chain(node.tags()) {
+            if !tags.contains(&tag.as_str()) {
+                tags.push(tag);
+            }
+        }
+        tags
+    }
+
+    /// Get all **recipes** with the given tag, either their own or inherited
+    /// from a folder, in tree order
+    pub fn recipes_with_tag<'a>(
+        &'a self,
+        tag: &'a str,
+    ) -> impl Iterator<Item = &'a Recipe> {
+        self.iter()
+            .filter_map(|(_, node)| node.recipe())
+            .filter(move |recipe| self.tags(&recipe.id).contains(&tag))
+    }
+
+    /// Get the chain of recipes that a recipe extends, nearest first. Unknown
+    /// parents end the chain, and each recipe is included only once. Both
+    /// cases are rejected when the collection is loaded.
+    pub fn parents(&self, recipe: &Recipe) -> Vec<&Recipe> {
+        let mut parents: Vec<&Recipe> = Vec::new();
+        let mut next = recipe.extends.as_ref();
+        while let Some(parent) = next.and_then(|id| self.get_recipe(id)) {
+            if parent.id == recipe.id
+                || parents.iter().any(|other| other.id == parent.id)
+            {
+                break;
+            }
+            parents.push(parent);
+            next = parent.extends.as_ref();
+        }
+        parents
+    }
+
+    /// Merge everything that a recipe inherits from its parent recipes and
+    /// folders into it. See [Recipe::apply_inheritance]
+    pub fn apply_inheritance(&self, recipe: &mut Recipe) -> InheritedValues {
+        let parents = self.parents(recipe);
+        let folders = self.ancestors(&recipe.id);
+        recipe.apply_inheritance(&parents, &folders)
+    }
+
     /// Get a **recipe** by ID. If the ID isn't in the tree, or points to a
     /// folder, return `None`
     pub fn get_recipe(&self, id: &RecipeId) -> Option<&Recipe> {
@@ -198,6 +289,14 @@ impl RecipeNode {
         }
     }
 
+    /// Get the tags defined directly on this folder/recipe
+    pub fn tags(&self) -> &[String] {
+        match self {
+            RecipeNode::Folder(folder) => &folder.tags,
+            RecipeNode::Recipe(recipe) => &recipe.tags,
+        }
+    }
+
     /// If this node is a recipe, return it. Otherwise return `None`
     pub fn recipe(&self) -> Option<&Recipe> {
         match self {
@@ -316,6 +415,72 @@ mod tests {
         );
     }
 
+    /// Get the folders containing a node, outermost first
+    #[rstest]
+    #[case::top_level("r1", &[])]
+    #[case::folder("f2", &["f1"])]
+    #[case::nested("r2", &["f1", "f2"])]
+    #[case::unknown("unknown", &[])]
+    fn test_ancestors(
+        tree: IndexMap<RecipeId, RecipeNode>,
+        #[case] node_id: &str,
+        #[case] expected: &[&str],
+    ) {
+        let tree = RecipeTree::new(tree).unwrap();
+        assert_eq!(
+            tree.ancestors(&id(node_id))
+                .into_iter()
+                .map(|folder| folder.id.clone())
+                .collect_vec(),
+            expected.iter().copied().map(id).collect_vec()
+        );
+    }
+
+    /// Folder tags apply to everything in the folder
+    #[rstest]
+    #[case::folder("admin", &["r2", "r3"])]
+    #[case::nested_folder("users", &["r2"])]
+    #[case::recipe("public", &["r1", "r3"])]
+    #[case::unknown("unknown", &[])]
+    fn test_recipes_with_tag(#[case] tag: &str, #[case] expected: &[&str]) {
+        let tree = RecipeTree::new(indexmap! {
+            id("r1") => create!(
+                Recipe, id: id("r1"), tags: vec!["public".into()]
+            ).into(),
+            id("f1") => create!(
+                Folder,
+                id: id("f1"),
+                tags: vec!["admin".into()],
+                children: indexmap! {
+                    id("f2") => create!(
+                        Folder,
+                        id: id("f2"),
+                        tags: vec!["users".into()],
+                        children: indexmap! {
+                            id("r2") => create!(Recipe, id: id("r2")).into(),
+                        }
+                    ).into(),
+                    id("r3") => create!(
+                        Recipe,
+                        id: id("r3"),
+                        // Duplicate of the folder tag should be ignored
+                        tags: vec!["public".into(), "admin".into()],
+                    ).into(),
+                },
+            ).into(),
+            id("r4") => create!(Recipe, id: id("r4")).into(),
+        })
+        .unwrap();
+
+        assert_eq!(
+            tree.recipes_with_tag(tag)
+                .map(|recipe| recipe.id.clone())
+                .collect_vec(),
+            expected.iter().copied().map(id).collect_vec()
+        );
+        assert_eq!(tree.tags(&id("r3")), vec!["admin", "public"]);
+    }
+
     /// Deserializing with a duplicate ID anywhere in the tree should fail
     #[rstest]
     #[case::anywhere(
@@ -0,0 +1,389 @@
+//! JSON Schema generation for the collection format. Most types derive
+//! [JsonSchema] alongside their serde impls, so the schema stays in sync with
+//! the models. Types with hand-written serde impls in [cereal](super::cereal)
+//! get hand-written schemas here.
+
+use crate::{
+    collection::{
+        Collection, Method, MultipartField, Pattern, RecipeBody, RecipeId,
+        RecipeNode, RecipeTree, StatusRange,
+    },
+    http::Query,
+    template::Template,
+};
+use indexmap::IndexMap;
+use schemars::{
+    gen::{SchemaGenerator, SchemaSettings},
+    schema::{
+        ArrayValidation, InstanceType, Metadata, RootSchema, Schema,
+        SchemaObject, StringValidation, SubschemaValidation,
+    },
+    visit::{visit_schema_object, Visitor},
+    JsonSchema,
+};
+use std::marker::PhantomData;
+use strum::IntoEnumIterator;
+
+impl Collection {
+    /// Generate a JSON Schema for the collection file format. YAML tags (e.g.
+    /// `!request`) can't be represented in JSON Schema, so tagged values are
+    /// described by their content alone. This matches how YAML language
+    /// servers validate tagged nodes.
+    pub fn schema() -> RootSchema {
+        let mut settings = SchemaSettings::draft07();
+        settings.visitors.push(Box::new(StripTags));
+        SchemaGenerator::new(settings).into_root_schema_for::<Self>()
+    }
+}
+
+/// Replace externally tagged enum variants with their content. Serde maps
+/// each variant to a YAML tag, e.g. `!bearer <token>`, which a schema
+/// validator sees as just `<token>`. Unit variants are left alone, because
+/// those are plain strings in YAML.
+#[derive(Clone, Debug)]
+struct StripTags;
+
+impl Visitor for StripTags {
+    fn visit_schema_object(&mut self, schema: &mut SchemaObject) {
+        // Externally tagged enums are the only thing that generate oneOf
+        if let Some(subschemas) = &mut schema.subschemas {
+            if let Some(variants) = subschemas.one_of.take() {
+                subschemas.any_of =
+                    Some(variants.into_iter().map(strip_tag).collect());
+            }
+        }
+        visit_schema_object(self, schema);
+    }
+}
+
+/// If a schema is a tagged variant (an object with exactly one required
+/// property), get the schema of its content
+fn strip_tag(variant: Schema) -> Schema {
+    let Schema::Object(mut object) = variant else {
+        return variant;
+    };
+    let Some(validation) = object.object.as_mut() else {
+        return Schema::Object(object);
+    };
+    if validation.properties.len() != 1 || validation.required.len() != 1 {
+        return Schema::Object(object);
+    }
+    let (_, content) = validation.properties.pop_first().unwrap();
+    match (content, object.metadata) {
+        // Carry the variant's doc comment over to the content
+        (Schema::Object(mut content), Some(metadata)) => {
+            content.metadata().description = metadata.description;
+            Schema::Object(content)
+        }
+        (content, _) => content,
+    }
+}
+
+/// Schema for a duration string, e.g. `30s`. See
+/// [serde_duration](super::cereal::serde_duration)
+pub struct DurationString;
+
+impl JsonSchema for DurationString {
+    fn schema_name() -> String {
+        "Duration".into()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        SchemaObject {
+            instance_type: Some(InstanceType::String.into()),
+            string: Some(Box::new(StringValidation {
+                pattern: Some(r"^\d+[smhd]$".into()),
+                ..Default::default()
+            })),
+            metadata: description("Duration, e.g. `30s`, `5m`, `12h`, `3d`"),
+            ..Default::default()
+        }
+        .into()
+    }
+}
+
+/// Schema for a single value or a list of values. See
+/// [deserialize_one_or_many](super::cereal::deserialize_one_or_many)
+pub struct OneOrMany<T>(PhantomData<T>);
+
+impl<T: JsonSchema> JsonSchema for OneOrMany<T> {
+    fn schema_name() -> String {
+        format!("OneOrMany_{}", T::schema_name())
+    }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        any_of(vec![
+            gen.subschema_for::<T>(),
+            gen.subschema_for::<Vec<T>>(),
+        ])
+    }
+}
+
+/// Schema for a map with multiple values per key, e.g. query parameters. See
+/// [serde_multimap](super::cereal::serde_multimap)
+pub struct MultiMap;
+
+impl JsonSchema for MultiMap {
+    fn schema_name() -> String {
+        "MultiMap".into()
+    }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        let map = gen.subschema_for::<IndexMap<String, OneOrMany<Template>>>();
+        let pairs = SchemaObject {
+            instance_type: Some(InstanceType::Array.into()),
+            array: Some(Box::new(ArrayValidation {
+                items: Some(map.clone().into()),
+                ..Default::default()
+            })),
+            ..Default::default()
+        };
+        any_of(vec![map, pairs.into()])
+    }
+}
+
+/// Templates can be any primitive, not just strings
+impl JsonSchema for Template {
+    fn schema_name() -> String {
+        "Template".into()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        SchemaObject {
+            instance_type: Some(
+                vec![
+                    InstanceType::String,
+                    InstanceType::Number,
+                    InstanceType::Boolean,
+                ]
+                .into(),
+            ),
+            metadata: description(
+                "Template string, which can include `{{field}}`, \
+                `{{chains.id}}` or `{{env.VARIABLE}}` keys",
+            ),
+            ..Default::default()
+        }
+        .into()
+    }
+}
+
+impl JsonSchema for RecipeBody {
+    fn schema_name() -> String {
+        "RecipeBody".into()
+    }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        any_of(vec![
+            gen.subschema_for::<Template>(),
+            // !form_urlencoded
+            gen.subschema_for::<IndexMap<String, Template>>(),
+            // !form_multipart
+            gen.subschema_for::<IndexMap<String, MultipartField>>(),
+            // !json
+            gen.subschema_for::<serde_json::Value>(),
+        ])
+    }
+}
+
+/// Text fields and `!file` paths are both templates
+impl JsonSchema for MultipartField {
+    fn schema_name() -> String {
+        "MultipartField".into()
+    }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        gen.subschema_for::<Template>()
+    }
+}
+
+/// Methods are parsed case-insensitively, but we only list the common
+/// spellings
+impl JsonSchema for Method {
+    fn schema_name() -> String {
+        "Method".into()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        let methods = Method::iter().map(|method| method.to_string());
+        SchemaObject {
+            instance_type: Some(InstanceType::String.into()),
+            enum_values: Some(
+                methods
+                    .flat_map(|method| [method.to_lowercase(), method])
+                    .map(serde_json::Value::from)
+                    .collect(),
+            ),
+            ..Default::default()
+        }
+        .into()
+    }
+}
+
+impl JsonSchema for StatusRange {
+    fn schema_name() -> String {
+        "StatusRange".into()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        let code = SchemaObject {
+            instance_type: Some(InstanceType::Integer.into()),
+            ..Default::default()
+        };
+        let string = SchemaObject {
+            instance_type: Some(InstanceType::String.into()),
+            string: Some(Box::new(StringValidation {
+                pattern: Some(r"^(\d{3}|\dxx|\d{3}-\d{3})$".into()),
+                ..Default::default()
+            })),
+            ..Default::default()
+        };
+        let mut schema = any_of(vec![code.into(), string.into()]).into_object();
+        schema.metadata = description(
+            "Status code (`200`), class (`2xx`) or range (`200-299`)",
+        );
+        schema.into()
+    }
+}
+
+impl JsonSchema for Pattern {
+    fn schema_name() -> String {
+        "Pattern".into()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        SchemaObject {
+            instance_type: Some(InstanceType::String.into()),
+            format: Some("regex".into()),
+            ..Default::default()
+        }
+        .into()
+    }
+}
+
+impl JsonSchema for Query {
+    fn schema_name() -> String {
+        "Query".into()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        SchemaObject {
+            instance_type: Some(InstanceType::String.into()),
+            metadata: description("JSONPath query, e.g. `$.data[0].id`"),
+            ..Default::default()
+        }
+        .into()
+    }
+}
+
+/// The tree is just a map in the collection file
+impl JsonSchema for RecipeTree {
+    fn schema_name() -> String {
+        "RecipeTree".into()
+    }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        <IndexMap<RecipeId, RecipeNode>>::json_schema(gen)
+    }
+}
+
+fn any_of(schemas: Vec<Schema>) -> Schema {
+    SchemaObject {
+        subschemas: Some(Box::new(SubschemaValidation {
+            any_of: Some(schemas),
+            ..Default::default()
+        })),
+        ..Default::default()
+    }
+    .into()
+}
+
+fn description(description: &str) -> Option<Box<Metadata>> {
+    Some(Box::new(Metadata {
+        description: Some(description.into()),
+        ..Default::default()
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jsonschema::JSONSchema;
+    use serde_json::Value as JsonValue;
+    use serde_yaml::Value as YamlValue;
+
+    /// Convert YAML to JSON the way a YAML language server sees it for
+    /// validation: tags are dropped and only their content is kept
+    fn yaml_to_json(yaml: YamlValue) -> JsonValue {
+        match yaml {
+            YamlValue::Null => JsonValue::Null,
+            YamlValue::Bool(b) => b.into(),
+            YamlValue::Number(n) => serde_json::to_value(n).unwrap(),
+            YamlValue::String(s) => s.into(),
+            YamlValue::Sequence(sequence) => {
+                sequence.into_iter().map(yaml_to_json).collect()
+            }
+            YamlValue::Mapping(mapping) => JsonValue::Object(
+                mapping
+                    .into_iter()
+                    .map(|(key, value)| {
+                        let key = match key {
+                            YamlValue::String(key) => key,
+                            other => serde_yaml::to_string(&other)
+                                .unwrap()
+                                .trim()
+                                .to_owned(),
+                        };
+                        (key, yaml_to_json(value))
+                    })
+                    .collect(),
+            ),
+            YamlValue::Tagged(tagged) => yaml_to_json(tagged.value),
+        }
+    }
+
+    /// Validate a YAML collection against the schema, returning all errors
+    fn validate(yaml: &str) -> Vec<String> {
+        let schema = serde_json::to_value(Collection::schema()).unwrap();
+        let schema = JSONSchema::compile(&schema).unwrap();
+        let mut yaml: YamlValue = serde_yaml::from_str(yaml).unwrap();
+        yaml.apply_merge().unwrap();
+        let json = yaml_to_json(yaml);
+        let result = schema.validate(&json);
+        match result {
+            Ok(()) => Vec::new(),
+            Err(errors) => errors
+                .map(|error| format!("{}: {error}", error.instance_path))
+                .collect(),
+        }
+    }
+
+    /// The example collection should always be valid
+    #[test]
+    fn test_example_collection() {
+        let errors = validate(include_str!("../../slumber.yml"));
+        assert!(errors.is_empty(), "{errors:#?}");
+    }
+
+    /// Make sure the schema actually rejects things
+    #[test]
+    fn test_invalid_collection() {
+        let errors = validate(
+            r#"
+profiles:
+  local:
+    unknown_field: 3
+chains:
+  token:
+    source: !request
+      recipe: login
+      trigger: !expire 12 hours
+requests:
+  login: !request
+    method: YEET
+    url: "{{host}}/login"
+"#,
+        );
+        assert_eq!(errors.len(), 3, "{errors:#?}");
+    }
+}
@@ -9,7 +9,7 @@ use crate::{
 use anyhow::Context;
 use indexmap::IndexMap;
 use serde::{Deserialize, Serialize};
-use std::fs;
+use std::{fs, path::PathBuf};
 use tracing::info;
 
 /// App-level configuration, which is global across all sessions and
@@ -25,6 +25,9 @@ pub struct Config {
     /// Should templates be rendered inline in the UI, or should we show the
     /// raw text?
     pub preview_templates: bool,
+    /// Path to the secrets file to merge into each collection, relative to
+    /// the collection file. Defaults to `slumber.secrets.yml`
+    pub secrets_file: Option<PathBuf>,
 
     /// Overrides for default key bindings
     pub input_bindings: IndexMap<Action, InputBinding>,
@@ -69,6 +72,7 @@ impl Default for Config {
         Self {
             ignore_certificate_hosts: Vec::new(),
             preview_templates: true,
+            secrets_file: None,
             input_bindings: IndexMap::default(),
         }
     }
@@ -3,7 +3,7 @@
 
 use crate::{
     collection::{ProfileId, RecipeId},
-    http::{RequestId, RequestRecord},
+    http::{Challenge, OAuth2Token, RequestId, RequestRecord},
     util::{
         paths::{DataDirectory, FileGuard},
         ResultExt,
@@ -129,6 +129,30 @@ impl Database {
             // serialization of all binary blobs, so there's no easy way to
             // migrate it all. It's easiest just to wipe it all out.
             M::up("DELETE FROM requests; DELETE FROM ui_state;").down(""),
+            M::up(
+                // OAuth 2.0 tokens are cached per profile. The key identifies
+                // the grant that generated the token. The token itself is
+                // serialized as msgpack
+                "CREATE TABLE oauth2_tokens (
+                    collection_id   UUID NOT NULL,
+                    profile_id      TEXT,
+                    key             TEXT NOT NULL,
+                    token           BLOB NOT NULL,
+                    FOREIGN KEY(collection_id) REFERENCES collections(id)
+                )",
+            )
+            .down("DROP TABLE oauth2_tokens"),
+            // Rejected first leg of a challenge/response authentication
+            // (e.g. digest). Both are msgpack, and null if there was no
+            // challenge
+            M::up(
+                "ALTER TABLE requests ADD COLUMN challenge_request BLOB;
+                ALTER TABLE requests ADD COLUMN challenge_response BLOB;",
+            )
+            .down(
+                "ALTER TABLE requests DROP COLUMN challenge_request;
+                ALTER TABLE requests DROP COLUMN challenge_response;",
+            ),
         ]);
         migrations.to_latest(connection)?;
         Ok(())
@@ -208,6 +232,16 @@ impl Database {
             )
             .context("Error migrating table `ui_state`")
             .traced()?;
+        connection
+            .execute(
+                // Tokens from the source will take precedence over the target
+                // because they were inserted later
+                "UPDATE oauth2_tokens SET collection_id = :target
+                WHERE collection_id = :source",
+                named_params! {":source": source, ":target": target},
+            )
+            .context("Error migrating table `oauth2_tokens`")
+            .traced()?;
 
         connection
             .execute(
@@ -347,10 +381,13 @@ impl CollectionDatabase {
                     end_time,
                     request,
                     response,
-                    status_code
+                    status_code,
+                    challenge_request,
+                    challenge_response
                 )
                 VALUES (:id, :collection_id, :profile_id, :recipe_id,
-                    :start_time, :end_time, :request, :response, :status_code)",
+                    :start_time, :end_time, :request, :response, :status_code,
+                    :challenge_request, :challenge_response)",
                 named_params! {
                     ":id": record.id,
                     ":collection_id": self.collection_id,
@@ -361,6 +398,14 @@ impl CollectionDatabase {
                     ":request": &ByteEncoded(&*record.request),
                     ":response": &ByteEncoded(&*record.response),
                     ":status_code": record.response.status.as_u16(),
+                    ":challenge_request": record
+                        .challenge
+                        .as_ref()
+                        .map(|challenge| ByteEncoded(&*challenge.request)),
+                    ":challenge_response": record
+                        .challenge
+                        .as_ref()
+                        .map(|challenge| ByteEncoded(&*challenge.response)),
                 },
             )
             .context(format!("Error saving request {} to database", record.id))
@@ -368,6 +413,94 @@ impl CollectionDatabase {
         Ok(())
     }
 
+    /// Get the cached OAuth 2.0 token for a profile+grant, or `None` if there
+    /// isn't one. The token is returned even if it's expired. If the given
+    /// profile is `None`, match all tokens that have no associated profile.
+    pub fn get_oauth2_token(
+        &self,
+        profile_id: Option<&ProfileId>,
+        key: &str,
+    ) -> anyhow::Result<Option<OAuth2Token>> {
+        self.database
+            .connection()
+            .query_row(
+                // Take the most recent row, in case a collection merge left
+                // duplicates behind
+                "SELECT token FROM oauth2_tokens
+                WHERE collection_id = :collection_id
+                    AND profile_id IS :profile_id
+                    AND key = :key
+                ORDER BY rowid DESC LIMIT 1",
+                named_params! {
+                    ":collection_id": self.collection_id,
+                    ":profile_id": profile_id,
+                    ":key": key,
+                },
+                |row| {
+                    let token: ByteEncoded<OAuth2Token> = row.get("token")?;
+                    Ok(token.0)
+                },
+            )
+            .optional()
+            .context("Error fetching OAuth 2.0 token from database")
+            .traced()
+    }
+
+    /// Cache an OAuth 2.0 token for a profile+grant, replacing any existing
+    /// token
+    pub fn set_oauth2_token(
+        &self,
+        profile_id: Option<&ProfileId>,
+        key: &str,
+        token: &OAuth2Token,
+    ) -> anyhow::Result<()> {
+        debug!(?profile_id, key, "Saving OAuth 2.0 token");
+        let params = named_params! {
+            ":collection_id": self.collection_id,
+            ":profile_id": profile_id,
+            ":key": key,
+        };
+        let connection = self.database.connection();
+        // We can't upsert because profile_id is nullable, and NULLs are
+        // never equal in unique constraints. Delete and insert in one
+        // transaction instead, so the old token isn't lost if the insert
+        // fails. The connection lock guarantees nothing else is using the
+        // connection, so an unchecked transaction is safe.
+        let transaction = connection
+            .unchecked_transaction()
+            .context("Error saving OAuth 2.0 token to database")
+            .traced()?;
+        transaction
+            .execute(
+                "DELETE FROM oauth2_tokens
+                WHERE collection_id = :collection_id
+                    AND profile_id IS :profile_id
+                    AND key = :key",
+                params,
+            )
+            .context("Error deleting OAuth 2.0 token from database")
+            .traced()?;
+        transaction
+            .execute(
+                "INSERT INTO oauth2_tokens
+                    (collection_id, profile_id, key, token)
+                VALUES (:collection_id, :profile_id, :key, :token)",
+                named_params! {
+                    ":collection_id": self.collection_id,
+                    ":profile_id": profile_id,
+                    ":key": key,
+                    ":token": ByteEncoded(token),
+                },
+            )
+            .context("Error saving OAuth 2.0 token to database")
+            .traced()?;
+        transaction
+            .commit()
+            .context("Error saving OAuth 2.0 token to database")
+            .traced()?;
+        Ok(())
+    }
+
     /// Get the value of a UI state field
     pub fn get_ui<K, V>(&self, key: K) -> anyhow::Result<Option<V>>
     where
@@ -563,6 +696,16 @@ impl<'a, 'b> TryFrom<&'a Row<'b>> for RequestRecord {
             // Deserialize from bytes
             request: Arc::new(row.get::<_, ByteEncoded<_>>("request")?.0),
             response: Arc::new(row.get::<_, ByteEncoded<_>>("response")?.0),
+            challenge: match (
+                row.get::<_, Option<ByteEncoded<_>>>("challenge_request")?,
+                row.get::<_, Option<ByteEncoded<_>>>("challenge_response")?,
+            ) {
+                (Some(request), Some(response)) => Some(Challenge {
+                    request: Arc::new(request.0),
+                    response: Arc::new(response.0),
+                }),
+                _ => None,
+            },
         })
     }
 }
@@ -572,6 +715,7 @@ mod tests {
     use super::*;
     use crate::test_util::*;
     use factori::create;
+    use reqwest::StatusCode;
     use std::collections::HashMap;
 
     #[test]
@@ -723,6 +867,34 @@ mod tests {
         }
     }
 
+    /// The rejected leg of a challenge/response exchange should be persisted
+    /// along with the request
+    #[test]
+    fn test_request_challenge() {
+        let collection = CollectionDatabase::testing();
+        let request = create!(Request);
+        let request_id = request.id;
+        let record = create!(
+            RequestRecord,
+            challenge: Some(Challenge {
+                request: Arc::new(request),
+                response: Arc::new(
+                    create!(Response, status: StatusCode::UNAUTHORIZED),
+                ),
+            }),
+        );
+        collection.insert_request(&record).unwrap();
+
+        let loaded = collection
+            .get_last_request(None, &"recipe1".into())
+            .unwrap()
+            .unwrap();
+        assert_eq!(loaded.id, record.id);
+        let challenge = loaded.challenge.unwrap();
+        assert_eq!(challenge.request.id, request_id);
+        assert_eq!(challenge.response.status, StatusCode::UNAUTHORIZED);
+    }
+
     /// Test UI state storage and retrieval
     #[test]
     fn test_ui_state() {
@@ -749,4 +921,39 @@ mod tests {
             Some("value2".into())
         );
     }
+
+    /// Test OAuth 2.0 token storage and retrieval
+    #[test]
+    fn test_oauth2_token() {
+        let collection = CollectionDatabase::testing();
+        let profile_id: ProfileId = "profile1".into();
+        let token = |access_token: &str| OAuth2Token {
+            access_token: access_token.into(),
+            refresh_token: None,
+            expires_at: None,
+        };
+
+        collection
+            .set_oauth2_token(None, "key1", &token("token1"))
+            .unwrap();
+        collection
+            .set_oauth2_token(Some(&profile_id), "key1", &token("token2"))
+            .unwrap();
+        // Replace the existing token
+        collection
+            .set_oauth2_token(None, "key1", &token("token3"))
+            .unwrap();
+
+        assert_eq!(
+            collection.get_oauth2_token(None, "key1").unwrap(),
+            Some(token("token3"))
+        );
+        assert_eq!(
+            collection
+                .get_oauth2_token(Some(&profile_id), "key1")
+                .unwrap(),
+            Some(token("token2"))
+        );
+        assert_eq!(collection.get_oauth2_token(None, "key2").unwrap(), None);
+    }
 }
@@ -33,32 +33,54 @@
 //! | RequestRecord |
 //! +---------------+
 
+mod assertion;
+mod aws;
 mod content_type;
+mod digest;
+mod oauth2;
 mod query;
 mod record;
 
+pub use assertion::*;
+pub use aws::*;
 pub use content_type::*;
+pub use digest::*;
+pub use oauth2::*;
 pub use query::*;
 pub use record::*;
 
 use crate::{
-    collection::{self, Authentication, Method, Recipe},
+    collection::{
+        self, ApiKeyLocation, Authentication, HttpSettings, HttpVersion,
+        JsonTemplate, Method, MultipartField, Recipe, RecipeBody,
+        RedirectPolicy,
+    },
     config::Config,
     db::CollectionDatabase,
     template::{Template, TemplateContext},
-    util::ResultExt,
+    util::{uri_encode, ResultExt},
 };
-use anyhow::Context;
+use anyhow::{anyhow, Context};
 use base64::{prelude::BASE64_STANDARD, write::EncoderWriter};
+use bytes::Bytes;
 use chrono::Utc;
-use futures::future;
+use futures::{future, future::BoxFuture, FutureExt};
 use indexmap::IndexMap;
+use itertools::Itertools;
 use reqwest::{
     header::{self, HeaderMap, HeaderName, HeaderValue},
-    Client,
+    redirect, Client, StatusCode,
 };
-use std::{collections::HashSet, future::Future, io::Write, sync::Arc};
-use tokio::try_join;
+use serde::Serialize;
+use std::{
+    borrow::{Borrow, Cow},
+    collections::{HashMap, HashSet},
+    future::Future,
+    io::Write,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+use tokio::{fs, try_join};
 use tracing::{debug, info, info_span};
 use url::Url;
 
@@ -77,6 +99,10 @@ pub struct HttpEngine {
     /// This client ignores TLS cert errors. Only use it if the user
     /// specifically wants to ignore errors for the request!
     danger_client: Client,
+    /// Clients for requests with non-default [HttpSettings], built on demand.
+    /// Keyed by settings and whether the client ignores TLS cert errors.
+    /// Shared between clones so each client is only built once
+    custom_clients: Arc<Mutex<HashMap<(HttpSettings, bool), Client>>>,
     database: CollectionDatabase,
     danger_hostnames: HashSet<String>,
 }
@@ -84,16 +110,13 @@ pub struct HttpEngine {
 impl HttpEngine {
     /// Build a new HTTP engine, which can be used for the entire program life
     pub fn new(config: &Config, database: CollectionDatabase) -> Self {
+        let default_settings = HttpSettings::default();
         Self {
-            client: Client::builder()
-                .user_agent(USER_AGENT)
-                .build()
+            client: build_client(&default_settings, false)
                 .expect("Error building reqwest client"),
-            danger_client: Client::builder()
-                .user_agent(USER_AGENT)
-                .danger_accept_invalid_certs(true)
-                .build()
+            danger_client: build_client(&default_settings, true)
                 .expect("Error building reqwest client"),
+            custom_clients: Default::default(),
             database,
             danger_hostnames: config
                 .ignore_certificate_hosts
@@ -130,7 +153,43 @@ impl HttpEngine {
             // Technically the elapsed time will include the conversion time,
             // but that should be extremely minimal compared to network IO
             let start_time = Utc::now();
+            // Signatures include a timestamp, so they have to be generated
+            // as late as possible. The signed request is what gets recorded,
+            // so the user can see the signature headers
+            let request = match &request.authentication {
+                Some(DeferredAuthentication::AwsSigV4(credentials)) => {
+                    Arc::new(credentials.sign(&request, start_time))
+                }
+                _ => request,
+            };
             let result = self.send_request_helper(&request).await;
+            // If the server rejected our credentials and the authentication
+            // method knows how to respond, retry with new credentials. The
+            // rejected attempt is kept in the record. The retry is included
+            // in the elapsed time
+            let (request, result, challenge) = match result {
+                Ok(response) => {
+                    match self.reauthenticate(&request, &response).await {
+                        Some(authorization) => {
+                            let retry = Arc::new(
+                                request.with_headers(
+                                    [(header::AUTHORIZATION, authorization)]
+                                        .into_iter()
+                                        .collect(),
+                                ),
+                            );
+                            let result = self.send_request_helper(&retry).await;
+                            let challenge = Challenge {
+                                request,
+                                response: Arc::new(response),
+                            };
+                            (retry, result, Some(challenge))
+                        }
+                        None => (request, Ok(response), None),
+                    }
+                }
+                Err(error) => (request, Err(error), None),
+            };
             let end_time = Utc::now();
 
             // Attach metadata to the error and yeet it
@@ -145,6 +204,7 @@ impl HttpEngine {
                         response: Arc::new(response),
                         start_time,
                         end_time,
+                        challenge,
                     };
 
                     // Error here should *not* kill the request
@@ -175,19 +235,83 @@ impl HttpEngine {
         // you execute the request, and this is much easier than frontloading
         // the conversion during the build process.
         let reqwest_request = self.convert_request(request)?;
+        let client =
+            self.client(reqwest_request.url(), &request.http_settings)?;
+        let reqwest_response = client.execute(reqwest_request).await?;
+        // Load the full response and convert it to our format
+        self.convert_response(reqwest_response).await
+    }
+
+    /// If the server rejected a request with a 401, get a new `Authorization`
+    /// header to retry it with. Returns `None` if the request shouldn't be
+    /// retried, either because it wasn't rejected, its authentication can't
+    /// respond to a rejection, or getting new credentials failed. Only one
+    /// retry is attempted, so a server that keeps rejecting us can't cause a
+    /// loop.
+    async fn reauthenticate(
+        &self,
+        request: &Request,
+        response: &Response,
+    ) -> Option<HeaderValue> {
+        if response.status != StatusCode::UNAUTHORIZED {
+            return None;
+        }
+        let result = match request.authentication.as_ref()? {
+            DeferredAuthentication::OAuth2(grant) => {
+                self.reauthenticate_oauth2(request.profile_id.as_ref(), grant)
+                    .await
+            }
+            DeferredAuthentication::Digest(credentials) => credentials
+                .authorize(request, response)
+                .context("Error answering digest authentication challenge"),
+            // Signatures don't expire between sending and the response, so
+            // re-signing won't help
+            DeferredAuthentication::AwsSigV4(_) => return None,
+        };
+        result.traced().ok()
+    }
 
-        // If the user wants to ignore cert errors on this host, use the client
-        // that's set up for that
-        let host = reqwest_request.url().host_str().unwrap_or_default();
-        let client = if self.danger_hostnames.contains(host) {
+    /// Get the client that should be used for the given URL, with default
+    /// settings. If the user wants to ignore cert errors on this host, use the
+    /// client that's set up for that
+    fn default_client(&self, url: &Url) -> &Client {
+        if self.is_danger_host(url) {
             &self.danger_client
         } else {
             &self.client
-        };
+        }
+    }
 
-        let reqwest_response = client.execute(reqwest_request).await?;
-        // Load the full response and convert it to our format
-        self.convert_response(reqwest_response).await
+    /// Get a client for the given URL that matches the given settings. Clients
+    /// for non-default settings are built the first time they're needed, then
+    /// re-used. This returns an owned client because cloning is cheap.
+    fn client(
+        &self,
+        url: &Url,
+        settings: &HttpSettings,
+    ) -> reqwest::Result<Client> {
+        if settings.is_empty() {
+            return Ok(self.default_client(url).clone());
+        }
+
+        let key = (settings.clone(), self.is_danger_host(url));
+        let mut clients = self
+            .custom_clients
+            .lock()
+            .expect("Client cache lock is poisoned");
+        if let Some(client) = clients.get(&key) {
+            return Ok(client.clone());
+        }
+        debug!(?settings, "Building HTTP client");
+        let client = build_client(&key.0, key.1)?;
+        clients.insert(key, client.clone());
+        Ok(client)
+    }
+
+    /// Should cert errors be ignored for this URL's host?
+    fn is_danger_host(&self, url: &Url) -> bool {
+        let host = url.host_str().unwrap_or_default();
+        self.danger_hostnames.contains(host)
     }
 
     /// Convert from our request type to reqwest's. The input request should
@@ -258,11 +382,19 @@ pub struct RequestBuilder {
 #[derive(Clone, Debug, Default)]
 pub struct RecipeOptions {
     /// Which headers should be excluded? A blacklist allows the default to be
-    /// "include all".
-    pub disabled_headers: HashSet<String>,
+    /// "include all". A header can have multiple values, so each entry is the
+    /// header name and the index of the value.
+    pub disabled_headers: HashSet<(String, usize)>,
     /// Which query parameters should be excluded?  A blacklist allows the
-    /// default to be "include all".
-    pub disabled_query_parameters: HashSet<String>,
+    /// default to be "include all". Entries are the same as headers.
+    pub disabled_query_parameters: HashSet<(String, usize)>,
+    /// Skip authentication entirely. Authentication is toggled as one unit,
+    /// separate from headers and query parameters
+    pub disable_authentication: bool,
+    /// Values for path parameters, which replace the recipe's values. These
+    /// are used as-is, *not* rendered as templates. Params that aren't in the
+    /// recipe can be given too, to fill any matching placeholder in the URL.
+    pub path_overrides: IndexMap<String, String>,
 }
 
 impl RequestBuilder {
@@ -291,27 +423,44 @@ impl RequestBuilder {
     /// Build the request. This is async because templated values may require IO
     /// or other async actions.
     pub async fn build(
-        self,
+        mut self,
         template_context: &TemplateContext,
     ) -> Result<Request, RequestBuildError> {
+        self.apply_inheritance(template_context);
         self.apply_error(self.render_request(template_context))
             .await
     }
 
     /// Build just a request's URL
     pub async fn build_url(
-        self,
+        mut self,
         template_context: &TemplateContext,
     ) -> Result<Url, RequestBuildError> {
-        self.apply_error(self.render_url(template_context)).await
+        self.apply_inheritance(template_context);
+        self.apply_error(self.render_url(template_context))
+            .await
+            .map(|(url, _)| url)
     }
 
     /// Build just a request's body
     pub async fn build_body(
-        self,
+        mut self,
         template_context: &TemplateContext,
     ) -> Result<Option<Body>, RequestBuildError> {
-        self.apply_error(self.render_body(template_context)).await
+        self.apply_inheritance(template_context);
+        self.apply_error(self.render_body(template_context))
+            .await
+            .map(|body| body.map(|(body, _)| body))
+    }
+
+    /// Merge everything the recipe inherits from parent recipes and folders
+    /// into the recipe. Those come from the collection, which isn't available
+    /// until build
+    fn apply_inheritance(&mut self, template_context: &TemplateContext) {
+        template_context
+            .collection
+            .recipes
+            .apply_inheritance(&mut self.recipe);
     }
 
     /// Wrapper to apply a helpful error around some request build step
@@ -330,12 +479,23 @@ impl RequestBuilder {
         &self,
         template_context: &TemplateContext,
     ) -> anyhow::Result<Request> {
+        // Load-time validation should prevent this
+        let method = self
+            .recipe
+            .method
+            .ok_or_else(|| anyhow!("Recipe has no method"))?
+            .into();
         // Render everything in parallel
-        let (url, headers, body) = try_join!(
+        let (
+            (url, redacted_url),
+            (headers, redacted_headers, authentication),
+            body,
+        ) = try_join!(
             self.render_url(template_context),
             self.render_headers(template_context),
             self.render_body(template_context),
         )?;
+        let (body, redacted_body) = body.unzip();
 
         info!(
             recipe_id = %self.recipe.id,
@@ -346,127 +506,274 @@ impl RequestBuilder {
             id: self.id,
             profile_id: template_context.selected_profile.clone(),
             recipe_id: self.recipe.id.clone(),
-            method: self.recipe.method.into(),
+            method,
             url,
             headers,
             body,
+            authentication,
+            http_settings: self.http_settings(template_context),
+            assertions: self.recipe.assertions.clone(),
+            redactions: Redactions {
+                url: redacted_url,
+                headers: redacted_headers,
+                body: redacted_body.flatten(),
+            },
         })
     }
 
-    /// Render URL, including query params
+    /// Get the HTTP client settings for this request. Recipe settings take
+    /// precedence over the selected profile's
+    fn http_settings(
+        &self,
+        template_context: &TemplateContext,
+    ) -> HttpSettings {
+        let profiles = &template_context.collection.profiles;
+        let profile_settings = template_context
+            .selected_profile
+            .as_ref()
+            .and_then(|profile_id| profiles.get(profile_id))
+            .map(|profile| profile.resolved_http(profiles))
+            .unwrap_or_default();
+        self.recipe.http.clone().or(&profile_settings)
+    }
+
+    /// Render URL, including path and query params. If any part of the URL is
+    /// sensitive, a redacted copy of it is returned too.
     async fn render_url(
         &self,
         template_context: &TemplateContext,
-    ) -> anyhow::Result<Url> {
+    ) -> anyhow::Result<(Url, Option<String>)> {
         // Shitty try block
-        let (mut url, query) = try_join!(
+        let ((url, url_sensitive), path, mut query) = try_join!(
             async {
-                let url = self
+                let (url, sensitive) = self
                     .recipe
                     .url
-                    .render(template_context)
+                    .as_ref()
+                    // Load-time validation should prevent this
+                    .ok_or_else(|| anyhow!("Recipe has no URL"))?
+                    .render_sensitive(template_context)
                     .await
                     .context("Error rendering URL")?;
-                url.parse::<Url>()
-                    .with_context(|| format!("Invalid URL: `{url}`"))
+                let url = url
+                    .parse::<Url>()
+                    .with_context(|| format!("Invalid URL: `{url}`"))?;
+                Ok((url, sensitive))
             },
+            self.render_path(template_context),
             self.render_query(template_context)
         )?;
 
-        // Join query into URL. if check prevents bare ? for empty query
-        if !query.is_empty() {
-            url.query_pairs_mut().extend_pairs(&query);
+        // API key can be passed as a query param. This is applied separately
+        // from the query table, so it can't be disabled individually
+        if let Some(Authentication::ApiKey {
+            location: ApiKeyLocation::Query,
+            name,
+            value,
+        }) = self.authentication()
+        {
+            let value = value
+                .render_sensitive(template_context)
+                .await
+                .context("Error rendering API key")?;
+            query.push((name.clone(), value));
         }
 
-        Ok(url)
+        // If the URL template itself is sensitive, there's no telling which
+        // part of it is the sensitive value, so the whole thing is redacted
+        let redacted = if url_sensitive {
+            Some(Redactions::PLACEHOLDER.to_owned())
+        } else if path
+            .values()
+            .chain(query.iter().map(|(_, value)| value))
+            .any(|(_, sensitive)| *sensitive)
+        {
+            let url = fill_url(url.clone(), &path, &query, true);
+            Some(Redactions::decode_placeholder(url.as_str()))
+        } else {
+            None
+        };
+        Ok((fill_url(url, &path, &query, false), redacted))
     }
 
-    /// Render query key=value params
-    async fn render_query(
+    /// Render path params, with whether each value is sensitive. Overridden
+    /// params are taken as-is instead of being rendered
+    async fn render_path(
         &self,
         template_context: &TemplateContext,
-    ) -> anyhow::Result<IndexMap<String, String>> {
+    ) -> anyhow::Result<IndexMap<String, (String, bool)>> {
+        let overrides = &self.options.path_overrides;
         let iter = self
             .recipe
-            .query
+            .path
             .iter()
-            // Filter out disabled params
-            .filter(|(param, _)| {
-                !self.options.disabled_query_parameters.contains(*param)
-            })
-            .map(|(k, v)| async move {
+            .filter(|(param, _)| !overrides.contains_key(*param))
+            .map(|(param, template)| async move {
                 Ok::<_, anyhow::Error>((
-                    k.clone(),
-                    v.render(template_context).await.context(format!(
-                        "Error rendering query parameter `{k}`"
-                    ))?,
+                    param.clone(),
+                    template.render_sensitive(template_context).await.context(
+                        format!("Error rendering path parameter `{param}`"),
+                    )?,
                 ))
             });
-        Ok(future::try_join_all(iter)
-            .await?
-            .into_iter()
-            .collect::<IndexMap<String, String>>())
+        let mut path: IndexMap<_, _> =
+            future::try_join_all(iter).await?.into_iter().collect();
+        path.extend(
+            overrides
+                .iter()
+                .map(|(param, value)| (param.clone(), (value.clone(), false))),
+        );
+        Ok(path)
+    }
+
+    /// Render query key=value params, with whether each value is sensitive.
+    /// Repeated params are included once per value
+    async fn render_query(
+        &self,
+        template_context: &TemplateContext,
+    ) -> anyhow::Result<Vec<(String, (String, bool))>> {
+        let iter = enabled_pairs(
+            &self.recipe.query,
+            &self.options.disabled_query_parameters,
+        )
+        .map(|(k, v)| async move {
+            Ok::<_, anyhow::Error>((
+                k.clone(),
+                v.render_sensitive(template_context).await.context(format!(
+                    "Error rendering query parameter `{k}`"
+                ))?,
+            ))
+        });
+        future::try_join_all(iter).await
     }
 
     /// Render all headers. This will also render authentication and merge it
-    /// into the headers
+    /// into the headers. If any header is sensitive, a redacted copy of the
+    /// headers is returned too. If the authentication method can respond to a
+    /// 401 (OAuth 2.0 or digest), its rendered credentials are returned as
+    /// well.
     async fn render_headers(
         &self,
         template_context: &TemplateContext,
-    ) -> anyhow::Result<HeaderMap> {
+    ) -> anyhow::Result<(
+        HeaderMap,
+        Option<HeaderMap>,
+        Option<DeferredAuthentication>,
+    )> {
         // Render base headers
-        let iter = self
-            .recipe
-            .headers
-            .iter()
-            // Filter out disabled headers
-            .filter(|(header, _)| {
-                !self.options.disabled_headers.contains(*header)
-            })
-            .map(move |(header, value_template)| {
-                self.render_header(template_context, header, value_template)
-            });
-        let mut headers = future::try_join_all(iter)
-            .await?
-            .into_iter()
-            .collect::<HeaderMap>();
+        let iter =
+            enabled_pairs(&self.recipe.headers, &self.options.disabled_headers)
+                .map(move |(header, value_template)| {
+                    self.render_header(template_context, header, value_template)
+                });
+        let mut headers = HeaderMap::new();
+        let mut redacted = HeaderMap::new();
+        let mut any_sensitive = false;
+        for (name, value, sensitive) in future::try_join_all(iter).await? {
+            redacted.append(name.clone(), redacted_header(&value, sensitive));
+            headers.append(name, value);
+            any_sensitive |= sensitive;
+        }
 
         // Render auth method and modify headers accordingly
-        if let Some(authentication) = &self.recipe.authentication {
-            headers.insert(
-                header::AUTHORIZATION,
-                self.render_authentication(template_context, authentication)
-                    .await?,
-            );
+        let mut deferred_authentication = None;
+        let auth_header = if let Some(Authentication::ApiKey {
+            location: ApiKeyLocation::Header,
+            name,
+            value,
+        }) = self.authentication()
+        {
+            // The key name isn't a template, so it's taken literally
+            let (name, mut value, sensitive) = self
+                .render_header(
+                    template_context,
+                    &Template::raw(name.clone()),
+                    value,
+                )
+                .await
+                .context("Error rendering API key")?;
+            value.set_sensitive(true);
+            Some((name, value, sensitive))
+        } else if let Some(authentication) = self.authentication() {
+            let (header_value, deferred) = self
+                .render_authentication(template_context, authentication)
+                .await?;
+            deferred_authentication = deferred;
+            header_value.map(|(value, sensitive)| {
+                (header::AUTHORIZATION, value, sensitive)
+            })
+        } else {
+            None
+        };
+        if let Some((name, value, sensitive)) = auth_header {
+            redacted.insert(name.clone(), redacted_header(&value, sensitive));
+            headers.insert(name, value);
+            any_sensitive |= sensitive;
+        }
+
+        // Structured bodies imply a content type. If the user gave one
+        // explicitly, theirs takes precedence
+        if let Some(content_type) = self
+            .recipe
+            .body
+            .as_ref()
+            .and_then(|body| body.content_type(&self.multipart_boundary()))
+        {
+            if !headers.contains_key(header::CONTENT_TYPE) {
+                let value: HeaderValue = content_type
+                    .try_into()
+                    .context("Error encoding body content type")?;
+                redacted.insert(header::CONTENT_TYPE, value.clone());
+                headers.insert(header::CONTENT_TYPE, value);
+            }
         }
 
-        Ok(headers)
+        Ok((
+            headers,
+            any_sensitive.then_some(redacted),
+            deferred_authentication,
+        ))
     }
 
-    /// Render authentication and return a value for the Authorization header
+    /// Render authentication and return a value for the Authorization header,
+    /// with whether it was rendered from any sensitive values. For OAuth 2.0,
+    /// this will fetch an access token if necessary, and return the grant
+    /// that the token came from. Digest and AWS authentication don't generate
+    /// a header until the request is sent, so they just return the rendered
+    /// credentials.
     async fn render_authentication(
         &self,
         template_context: &TemplateContext,
         authentication: &Authentication,
-    ) -> anyhow::Result<HeaderValue> {
-        let mut header_value = match authentication {
+    ) -> anyhow::Result<(
+        Option<(HeaderValue, bool)>,
+        Option<DeferredAuthentication>,
+    )> {
+        let (mut header_value, sensitive) = match authentication {
             collection::Authentication::Basic { username, password } => {
                 // Encode as `username:password | base64`
                 // https://swagger.io/docs/specification/authentication/basic-authentication/
-                let (username, password) = try_join!(
+                let (
+                    (username, username_sensitive),
+                    (password, password_sensitive),
+                ) = try_join!(
                     async {
                         username
-                            .render(template_context)
+                            .render_sensitive(template_context)
                             .await
                             .context("Error rendering username")
                     },
                     async {
-                        Template::render_opt(
-                            password.as_ref(),
-                            template_context,
-                        )
-                        .await
-                        .context("Error rendering password")
+                        match password {
+                            Some(password) => password
+                                .render_sensitive(template_context)
+                                .await
+                                .context("Error rendering password")
+                                .map(|(password, sensitive)| {
+                                    (Some(password), sensitive)
+                                }),
+                            None => Ok((None, false)),
+                        }
                     },
                 )?;
 
@@ -479,61 +786,428 @@ impl RequestBuilder {
                         let _ = write!(encoder, "{password}");
                     }
                 }
-                HeaderValue::from_bytes(&buf)
-                    .context("Error encoding basic authentication credentials")
+                let header_value = HeaderValue::from_bytes(&buf).context(
+                    "Error encoding basic authentication credentials",
+                )?;
+                (header_value, username_sensitive || password_sensitive)
             }
 
             collection::Authentication::Bearer(token) => {
-                let token = token
-                    .render(template_context)
+                let (token, sensitive) = token
+                    .render_sensitive(template_context)
                     .await
                     .context("Error rendering bearer token")?;
-                format!("Bearer {token}")
+                let header_value = format!("Bearer {token}")
                     .try_into()
-                    .context("Error encoding bearer token")
+                    .context("Error encoding bearer token")?;
+                (header_value, sensitive)
+            }
+
+            collection::Authentication::Digest { username, password } => {
+                let (username, password) = try_join!(
+                    async {
+                        username
+                            .render(template_context)
+                            .await
+                            .context("Error rendering username")
+                    },
+                    async {
+                        password
+                            .render(template_context)
+                            .await
+                            .context("Error rendering password")
+                    },
+                )?;
+                return Ok((
+                    None,
+                    Some(DeferredAuthentication::Digest(DigestCredentials {
+                        username,
+                        password,
+                    })),
+                ));
+            }
+
+            collection::Authentication::Oauth2ClientCredentials {
+                token_url,
+                client_id,
+                client_secret,
+                scope,
+            } => {
+                let (header_value, grant) = self
+                    .render_oauth2(
+                        template_context,
+                        "client_credentials",
+                        token_url,
+                        &[
+                            ("client_id", Some(client_id)),
+                            ("client_secret", Some(client_secret)),
+                            ("scope", scope.as_ref()),
+                        ],
+                    )
+                    .await?;
+                // The token is fetched, so it's never from a sensitive value
+                return Ok((
+                    Some((header_value, false)),
+                    Some(DeferredAuthentication::OAuth2(grant)),
+                ));
+            }
+
+            collection::Authentication::Oauth2Password {
+                token_url,
+                client_id,
+                client_secret,
+                username,
+                password,
+                scope,
+            } => {
+                let (header_value, grant) = self
+                    .render_oauth2(
+                        template_context,
+                        "password",
+                        token_url,
+                        &[
+                            ("client_id", Some(client_id)),
+                            ("client_secret", client_secret.as_ref()),
+                            ("username", Some(username)),
+                            ("password", Some(password)),
+                            ("scope", scope.as_ref()),
+                        ],
+                    )
+                    .await?;
+                // The token is fetched, so it's never from a sensitive value
+                return Ok((
+                    Some((header_value, false)),
+                    Some(DeferredAuthentication::OAuth2(grant)),
+                ));
+            }
+
+            collection::Authentication::Oauth2RefreshToken {
+                token_url,
+                client_id,
+                client_secret,
+                refresh_token,
+                scope,
+            } => {
+                let (header_value, grant) = self
+                    .render_oauth2(
+                        template_context,
+                        "refresh_token",
+                        token_url,
+                        &[
+                            ("client_id", Some(client_id)),
+                            ("client_secret", client_secret.as_ref()),
+                            ("refresh_token", Some(refresh_token)),
+                            ("scope", scope.as_ref()),
+                        ],
+                    )
+                    .await?;
+                // The token is fetched, so it's never from a sensitive value
+                return Ok((
+                    Some((header_value, false)),
+                    Some(DeferredAuthentication::OAuth2(grant)),
+                ));
+            }
+
+            // Applied directly to the headers or URL
+            collection::Authentication::ApiKey { .. } => {
+                return Ok((None, None));
+            }
+
+            collection::Authentication::AwsSigV4 {
+                access_key,
+                secret_key,
+                session_token,
+                region,
+                service,
+            } => {
+                let (access_key, secret_key, session_token, region, service) =
+                    try_join!(
+                        async {
+                            access_key
+                                .render(template_context)
+                                .await
+                                .context("Error rendering access key")
+                        },
+                        async {
+                            secret_key
+                                .render(template_context)
+                                .await
+                                .context("Error rendering secret key")
+                        },
+                        async {
+                            Template::render_opt(
+                                session_token.as_ref(),
+                                template_context,
+                            )
+                            .await
+                            .context("Error rendering session token")
+                        },
+                        async {
+                            region
+                                .render(template_context)
+                                .await
+                                .context("Error rendering region")
+                        },
+                        async {
+                            service
+                                .render(template_context)
+                                .await
+                                .context("Error rendering service")
+                        },
+                    )?;
+                // The signature is generated when the request is sent
+                let credentials = AwsSigV4Credentials::new(
+                    access_key,
+                    secret_key,
+                    session_token,
+                    region,
+                    service,
+                )?;
+                return Ok((
+                    None,
+                    Some(DeferredAuthentication::AwsSigV4(credentials)),
+                ));
             }
-        }?;
+        };
         header_value.set_sensitive(true);
-        Ok(header_value)
+        Ok((Some((header_value, sensitive)), None))
+    }
+
+    /// Get the recipe's authentication, unless the user has disabled it
+    fn authentication(&self) -> Option<&Authentication> {
+        if self.options.disable_authentication {
+            None
+        } else {
+            self.recipe.authentication.as_ref()
+        }
     }
 
-    /// Render a single key/value header
+    /// Render a single key/value header, with whether either the name or
+    /// value was rendered from a sensitive value. If so, the value is also
+    /// marked sensitive
     async fn render_header(
         &self,
         template_context: &TemplateContext,
-        header: &str,
+        header: &Template,
         value_template: &Template,
-    ) -> anyhow::Result<(HeaderName, HeaderValue)> {
-        let value = value_template
-            .render(template_context)
-            .await
-            .context(format!("Error rendering header `{header}`"))?;
+    ) -> anyhow::Result<(HeaderName, HeaderValue, bool)> {
+        // Header names are templates too, so render both
+        let ((name, name_sensitive), (value, value_sensitive)) = try_join!(
+            async {
+                header.render_sensitive(template_context).await.context(
+                    format!("Error rendering name of header `{header}`"),
+                )
+            },
+            async {
+                value_template
+                    .render_sensitive(template_context)
+                    .await
+                    .context(format!("Error rendering header `{header}`"))
+            },
+        )?;
         // Strip leading/trailing line breaks because they're going to
         // trigger a validation error and are probably a mistake. This
         // is a balance between convenience and
         // explicitness
         let value = value.trim_matches(|c| c == '\n' || c == '\r');
         // String -> header conversions are fallible, if headers
-        // are invalid
-        Ok::<(HeaderName, HeaderValue), anyhow::Error>((
-            header
-                .try_into()
-                .context(format!("Error encoding header name `{header}`"))?,
-            value.try_into().context(format!(
-                "Error encoding value for header `{header}`"
-            ))?,
+        // are invalid. If the name was templated, include what it rendered
+        // to so the user can see what went wrong
+        let name_error = if name == header.as_str() {
+            format!("Error encoding header name `{header}`")
+        } else {
+            format!(
+                "Error encoding header name `{header}`, which rendered to \
+                `{name}`"
+            )
+        };
+        let mut value: HeaderValue = value
+            .try_into()
+            .context(format!("Error encoding value for header `{header}`"))?;
+        let sensitive = name_sensitive || value_sensitive;
+        value.set_sensitive(sensitive);
+        Ok((
+            name.as_str().try_into().context(name_error)?,
+            value,
+            sensitive,
         ))
     }
 
+    /// Render the body, encoding structured bodies according to their type.
+    /// If any part of the body is sensitive, a redacted copy of it is returned
+    /// too.
     async fn render_body(
         &self,
         template_context: &TemplateContext,
-    ) -> anyhow::Result<Option<Body>> {
+    ) -> anyhow::Result<Option<(Body, Option<Bytes>)>> {
+        let Some(body) = &self.recipe.body else {
+            return Ok(None);
+        };
+        let (rendered, redacted) = match body {
+            RecipeBody::Raw(template) => {
+                let (body, sensitive) = template
+                    .render_sensitive(template_context)
+                    .await
+                    .context("Error rendering body")?;
+                // There's no telling which part of a raw body is sensitive
+                let redacted =
+                    sensitive.then(|| Redactions::PLACEHOLDER.into());
+                (body.into_bytes(), redacted)
+            }
+            RecipeBody::FormUrlencoded(fields) => {
+                let iter = fields.iter().map(|(field, template)| async move {
+                    let value = template
+                        .render_sensitive(template_context)
+                        .await
+                        .context(format!(
+                            "Error rendering form field `{field}`"
+                        ))?;
+                    Ok::<_, anyhow::Error>((field, value))
+                });
+                let fields = future::try_join_all(iter).await?;
+                let encode = |redact: bool| {
+                    url::form_urlencoded::Serializer::new(String::new())
+                        .extend_pairs(fields.iter().map(|(field, value)| {
+                            (field, redacted_value(value, redact))
+                        }))
+                        .finish()
+                };
+                let redacted = fields
+                    .iter()
+                    .any(|(_, (_, sensitive))| *sensitive)
+                    .then(|| {
+                        Redactions::decode_placeholder(&encode(true))
+                            .into_bytes()
+                    });
+                (encode(false).into_bytes(), redacted)
+            }
+            RecipeBody::FormMultipart(fields) => {
+                self.render_multipart(template_context, fields).await?
+            }
+            RecipeBody::Json(value) => {
+                let value =
+                    render_json(template_context, value, "$".into()).await?;
+                // The body is built as a JSON value, so it's valid by
+                // construction
+                let redacted = if value.is_sensitive() {
+                    Some(
+                        serde_json::to_vec(&value.redacted())
+                            .context("Error serializing JSON body")?,
+                    )
+                } else {
+                    None
+                };
+                (
+                    serde_json::to_vec(&value)
+                        .context("Error serializing JSON body")?,
+                    redacted,
+                )
+            }
+        };
+        Ok(Some((
+            Body::new(rendered.into()),
+            redacted.map(Bytes::from),
+        )))
+    }
+
+    /// Render and encode a `multipart/form-data` body. Text fields are
+    /// rendered, and file fields are loaded from disk. If any text fields are
+    /// sensitive, a redacted copy of the body is returned too.
+    async fn render_multipart(
+        &self,
+        template_context: &TemplateContext,
+        fields: &IndexMap<String, MultipartField>,
+    ) -> anyhow::Result<(Vec<u8>, Option<Vec<u8>>)> {
+        let iter = fields.iter().map(|(field, value)| async move {
+            let name = escape_disposition(field);
+            let part = match value {
+                MultipartField::Text(template) => {
+                    let (value, sensitive) = template
+                        .render_sensitive(template_context)
+                        .await
+                        .context(format!("Error rendering form field `{field}`"))?;
+                    let part = |value: &str| {
+                        format!(
+                            "Content-Disposition: form-data; name=\"{name}\"\r\n\r\n\
+                            {value}"
+                        )
+                        .into_bytes()
+                    };
+                    (
+                        part(&value),
+                        sensitive.then(|| part(Redactions::PLACEHOLDER)),
+                    )
+                }
+                MultipartField::File(path) => {
+                    let path = path.render(template_context).await.context(
+                        format!("Error rendering path for form field `{field}`"),
+                    )?;
+                    // Relative paths are relative to the collection file,
+                    // not the directory Slumber was launched from
+                    let collection_path =
+                        template_context.database.collection_path()?;
+                    let full_path = collection_path
+                        .parent()
+                        .unwrap_or(Path::new(""))
+                        .join(&path);
+                    let content = fs::read(&full_path).await.context(format!(
+                        "Error reading file `{path}` for form field `{field}`"
+                    ))?;
+                    let file_name = Path::new(&path)
+                        .file_name()
+                        .map(|name| escape_disposition(&name.to_string_lossy()))
+                        .unwrap_or_default();
+                    let content_type =
+                        mime_guess::from_path(&path).first_or_octet_stream();
+                    let mut part = format!(
+                        "Content-Disposition: form-data; name=\"{name}\"; \
+                        filename=\"{file_name}\"\r\n\
+                        Content-Type: {content_type}\r\n\r\n"
+                    )
+                    .into_bytes();
+                    part.extend(content);
+                    (part, None)
+                }
+            };
+            Ok::<_, anyhow::Error>(part)
+        });
+        let parts = future::try_join_all(iter).await?;
+
+        // Stitch the parts together with the boundary
+        let boundary = self.multipart_boundary();
+        let stitch = |parts: Vec<&[u8]>| {
+            let mut body = Vec::new();
+            for part in parts {
+                body.extend(format!("--{boundary}\r\n").as_bytes());
+                body.extend(part);
+                body.extend(b"\r\n");
+            }
+            body.extend(format!("--{boundary}--\r\n").as_bytes());
+            body
+        };
         let body =
-            Template::render_opt(self.recipe.body.as_ref(), template_context)
-                .await
-                .context("Error rendering body")?;
-        Ok(body.map(Body::from))
+            stitch(parts.iter().map(|(part, _)| part.as_slice()).collect());
+        let redacted = parts
+            .iter()
+            .any(|(_, redacted)| redacted.is_some())
+            .then(|| {
+                stitch(
+                    parts
+                        .iter()
+                        .map(|(part, redacted)| {
+                            redacted.as_deref().unwrap_or(part)
+                        })
+                        .collect(),
+                )
+            });
+        Ok((body, redacted))
+    }
+
+    /// Boundary string for separating parts in a multipart body. This is
+    /// derived from the request ID so it's unique per request, and so the
+    /// body and `Content-Type` header can be rendered independently.
+    fn multipart_boundary(&self) -> String {
+        format!("slumber-{}", self.id)
     }
 }
 
@@ -553,16 +1227,242 @@ impl From<Method> for reqwest::Method {
     }
 }
 
+/// Flatten a multi-value map (query params or headers) into key-value pairs,
+/// skipping values that have been disabled by the user
+fn enabled_pairs<'a, K: Borrow<str>>(
+    map: &'a IndexMap<K, Vec<Template>>,
+    disabled: &'a HashSet<(String, usize)>,
+) -> impl Iterator<Item = (&'a K, &'a Template)> {
+    map.iter()
+        .flat_map(|(key, values)| {
+            values
+                .iter()
+                .enumerate()
+                .map(move |(i, value)| (key, i, value))
+        })
+        .filter(|(key, i, _)| {
+            !disabled.contains(&((*key).borrow().to_owned(), *i))
+        })
+        .map(|(key, _, value)| (key, value))
+}
+
+/// Fill rendered path and query params into a URL. If `redact` is set,
+/// sensitive values are replaced with a placeholder.
+fn fill_url(
+    mut url: Url,
+    path: &IndexMap<String, (String, bool)>,
+    query: &[(String, (String, bool))],
+    redact: bool,
+) -> Url {
+    let path = path
+        .iter()
+        .map(|(param, value)| (param.as_str(), redacted_value(value, redact)))
+        .collect();
+    fill_path_params(&mut url, &path);
+
+    // Join query into URL. if check prevents bare ? for empty query
+    if !query.is_empty() {
+        url.query_pairs_mut().extend_pairs(
+            query
+                .iter()
+                .map(|(param, value)| (param, redacted_value(value, redact))),
+        );
+    }
+    url
+}
+
+/// Get a rendered value, which is paired with whether it's sensitive. If
+/// `redact` is set and the value is sensitive, get a placeholder instead.
+fn redacted_value((value, sensitive): &(String, bool), redact: bool) -> &str {
+    if redact && *sensitive {
+        Redactions::PLACEHOLDER
+    } else {
+        value
+    }
+}
+
+/// Get a header value for the redacted copy of the headers. If the value is
+/// sensitive, get a placeholder instead.
+fn redacted_header(value: &HeaderValue, sensitive: bool) -> HeaderValue {
+    if sensitive {
+        HeaderValue::from_static(Redactions::PLACEHOLDER)
+    } else {
+        value.clone()
+    }
+}
+
+/// Replace `:param` placeholders in the URL path with their values. Only whole
+/// path segments are replaced, so a colon elsewhere (e.g. `/v1/items:batch`)
+/// is left alone. Placeholders without a value are left as-is.
+fn fill_path_params(url: &mut Url, params: &IndexMap<&str, &str>) {
+    if params.is_empty() {
+        return;
+    }
+    let path = url
+        .path()
+        .split('/')
+        .map(|segment| {
+            match segment.strip_prefix(':').and_then(|name| params.get(name)) {
+                Some(value) => Cow::Owned(uri_encode(value)),
+                None => Cow::Borrowed(segment),
+            }
+        })
+        .join("/");
+    url.set_path(&path);
+}
+
+/// Escape a field name or file name for a multipart `Content-Disposition`
+/// header. Per RFC 7578, `"`, CR and LF are percent-encoded so they can't
+/// terminate the quoted value or inject additional headers.
+fn escape_disposition(value: &str) -> String {
+    value
+        .replace('"', "%22")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+/// A rendered JSON body. This is separate from [serde_json::Value] so that
+/// objects defined in the recipe keep their field order when serialized
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+enum RenderedJson {
+    Value(serde_json::Value),
+    /// A value rendered from a sensitive template
+    Sensitive(serde_json::Value),
+    Array(Vec<RenderedJson>),
+    Object(IndexMap<String, RenderedJson>),
+}
+
+impl RenderedJson {
+    /// Does this contain any sensitive values?
+    fn is_sensitive(&self) -> bool {
+        match self {
+            Self::Value(_) => false,
+            Self::Sensitive(_) => true,
+            Self::Array(values) => values.iter().any(Self::is_sensitive),
+            Self::Object(fields) => fields.values().any(Self::is_sensitive),
+        }
+    }
+
+    /// Copy this value, with each sensitive value replaced by a placeholder
+    fn redacted(&self) -> Self {
+        match self {
+            Self::Value(value) => Self::Value(value.clone()),
+            Self::Sensitive(_) => Self::Value(Redactions::PLACEHOLDER.into()),
+            Self::Array(values) => {
+                Self::Array(values.iter().map(Self::redacted).collect())
+            }
+            Self::Object(fields) => Self::Object(
+                fields
+                    .iter()
+                    .map(|(key, value)| (key.clone(), value.redacted()))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+/// Render a JSON body template. A string that is exactly one template key is
+/// parsed as JSON after rendering, so it keeps its type (e.g. a number or a
+/// chained object). If it isn't valid JSON, it's used as a string. Any other
+/// string is rendered as a string. `path` is a JSONPath to the current value,
+/// for error messages. Boxed because it's recursive.
+fn render_json<'a>(
+    template_context: &'a TemplateContext,
+    value: &'a JsonTemplate,
+    path: String,
+) -> BoxFuture<'a, anyhow::Result<RenderedJson>> {
+    async move {
+        let rendered = match value {
+            JsonTemplate::Null => RenderedJson::Value(serde_json::Value::Null),
+            JsonTemplate::Bool(value) => RenderedJson::Value((*value).into()),
+            JsonTemplate::Number(value) => {
+                RenderedJson::Value(value.clone().into())
+            }
+            JsonTemplate::String(template) => {
+                let (rendered, sensitive) =
+                    template.render_sensitive(template_context).await.context(
+                        format!("Error rendering JSON body at `{path}`"),
+                    )?;
+                let value = if template.is_single_key() {
+                    serde_json::from_str(&rendered)
+                        .unwrap_or(serde_json::Value::String(rendered))
+                } else {
+                    rendered.into()
+                };
+                if sensitive {
+                    RenderedJson::Sensitive(value)
+                } else {
+                    RenderedJson::Value(value)
+                }
+            }
+            JsonTemplate::Array(values) => {
+                let iter = values.iter().enumerate().map(|(i, value)| {
+                    render_json(template_context, value, format!("{path}[{i}]"))
+                });
+                RenderedJson::Array(future::try_join_all(iter).await?)
+            }
+            JsonTemplate::Object(fields) => {
+                let iter = fields.iter().map(|(key, value)| {
+                    let path = format!("{path}.{key}");
+                    render_json(template_context, value, path)
+                        .map(|result| result.map(|value| (key.clone(), value)))
+                });
+                RenderedJson::Object(
+                    future::try_join_all(iter).await?.into_iter().collect(),
+                )
+            }
+        };
+        Ok(rendered)
+    }
+    .boxed()
+}
+
+/// Build an HTTP client with the given settings. Unset settings keep reqwest's
+/// defaults, except gzip, which is disabled unless requested
+fn build_client(
+    settings: &HttpSettings,
+    danger: bool,
+) -> reqwest::Result<Client> {
+    let mut builder = Client::builder()
+        .user_agent(USER_AGENT)
+        .danger_accept_invalid_certs(danger)
+        .gzip(settings.gzip.unwrap_or(false));
+    if let Some(timeout) = settings.timeout {
+        builder = builder.timeout(timeout);
+    }
+    builder = match settings.redirect {
+        None | Some(RedirectPolicy::Follow) => builder,
+        Some(RedirectPolicy::None) => {
+            builder.redirect(redirect::Policy::none())
+        }
+        Some(RedirectPolicy::Max(max)) => {
+            builder.redirect(redirect::Policy::limited(max))
+        }
+    };
+    builder = match settings.http_version {
+        None => builder,
+        Some(HttpVersion::Http1) => builder.http1_only(),
+        Some(HttpVersion::Http2) => builder.http2_prior_knowledge(),
+    };
+    builder.build()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{collection::Authentication, test_util::*};
+    use crate::{
+        collection::{Authentication, RecipeTree},
+        db::Database,
+        test_util::*,
+    };
     use factori::create;
     use indexmap::indexmap;
     use pretty_assertions::assert_eq;
     use reqwest::Method;
     use rstest::rstest;
-    use std::collections::HashMap;
+    use std::{collections::HashMap, env, time::Duration};
+    use uuid::Uuid;
 
     #[tokio::test]
     async fn test_build_request() {
@@ -585,15 +1485,15 @@ mod tests {
         );
         let recipe = create!(
             Recipe,
-            method: "POST".parse().unwrap(),
-            url: "{{host}}/users/{{user_id}}".into(),
+            method: Some("POST".parse().unwrap()),
+            url: Some("{{host}}/users/{{user_id}}".into()),
             query: indexmap! {
-                "mode".into() => "{{mode}}".into(),
-                "fast".into() => "true".into(),
+                "mode".into() => vec!["{{mode}}".into()],
+                "fast".into() => vec!["true".into()],
             },
             headers: indexmap! {
-                "Accept".into() => "application/json".into(),
-                "Content-Type".into() => "application/json".into(),
+                "Accept".into() => vec!["application/json".into()],
+                "Content-Type".into() => vec!["application/json".into()],
             },
             body: Some("{\"group_id\":\"{{group_id}}\"}".into()),
         );
@@ -619,37 +1519,119 @@ mod tests {
                     .unwrap(),
                 body: Some(Vec::from(b"{\"group_id\":\"3\"}").into()),
                 headers: header_map(expected_headers),
+                authentication: None,
+                http_settings: Default::default(),
+                assertions: Vec::new(),
+                redactions: Default::default(),
+            }
+        );
+    }
+
+    /// Sensitive values should be redacted where they're used in the request,
+    /// regardless of how they're encoded. Identical non-sensitive text should
+    /// be left alone.
+    #[tokio::test]
+    async fn test_build_redactions() {
+        let profile = create!(
+            Profile,
+            data: indexmap! {
+                "id".into() => "1".into(),
+                "password".into() => "hun\"ter\\2".into(),
+            },
+            sensitive_fields: ["id".into(), "password".into()].into(),
+        );
+        let profile_id = profile.id.clone();
+        let context = create!(
+            TemplateContext,
+            collection: create!(
+                Collection,
+                profiles: indexmap!{profile_id.clone() => profile},
+            ),
+            selected_profile: Some(profile_id),
+        );
+        let recipe = create!(
+            Recipe,
+            method: Some("POST".parse().unwrap()),
+            url: Some("http://localhost:8001/users/:id".into()),
+            path: indexmap! {"id".into() => "{{id}}".into()},
+            query: indexmap! {
+                "page".into() => vec!["1".into()],
+                "key".into() => vec!["{{id}}".into()],
+            },
+            headers: indexmap! {
+                "X-Count".into() => vec!["1".into()],
+                "X-Id".into() => vec!["{{id}}".into()],
+            },
+            authentication: Some(Authentication::Basic {
+                username: "user".into(),
+                password: Some("{{password}}".into()),
+            }),
+            body: Some(RecipeBody::Json(JsonTemplate::Object(indexmap! {
+                "count".into() => JsonTemplate::Number(1.into()),
+                "password".into() => JsonTemplate::String("{{password}}".into()),
+            }))),
+        );
+
+        let builder = RequestBuilder::new(recipe, RecipeOptions::default());
+        let request = builder.build(&context).await.unwrap();
+
+        assert_eq!(
+            request.redactions,
+            Redactions {
+                url: Some(
+                    "http://localhost:8001/users/<sensitive>\
+                    ?page=1&key=<sensitive>"
+                        .into()
+                ),
+                // Basic auth is encoded, so the whole header is redacted
+                headers: Some(header_map([
+                    ("x-count", "1"),
+                    ("x-id", "<sensitive>"),
+                    ("authorization", "<sensitive>"),
+                    ("content-type", "application/json"),
+                ])),
+                body: Some(r#"{"count":1,"password":"<sensitive>"}"#.into()),
             }
         );
+        assert!(!request.headers["x-count"].is_sensitive());
+        assert!(request.headers["x-id"].is_sensitive());
     }
 
+    /// Authentication that doesn't use any sensitive values shouldn't be
+    /// redacted
     #[rstest]
     #[case::basic(
         Authentication::Basic {
-            username: "{{username}}".into(),
-            password: Some("{{password}}".into()),
+            username: "user".into(),
+            password: Some("hunter2".into()),
         },
-        "Basic dXNlcjpodW50ZXIy"
+        "authorization",
+        "Basic dXNlcjpodW50ZXIy",
     )]
-    #[case::basic_no_password(
-        Authentication::Basic {
-            username: "{{username}}".into(),
-            password: None,
+    #[case::bearer(
+        Authentication::Bearer("{{token}}".into()),
+        "authorization",
+        "Bearer abc",
+    )]
+    #[case::api_key(
+        Authentication::ApiKey {
+            location: ApiKeyLocation::Header,
+            name: "X-Api-Key".into(),
+            value: "{{token}}".into(),
         },
-        "Basic dXNlcjo="
+        "x-api-key",
+        "abc",
     )]
-    #[case::bearer(Authentication::Bearer("{{token}}".into()), "Bearer token!")]
     #[tokio::test]
-    async fn test_authentication(
+    async fn test_build_redactions_authentication(
         #[case] authentication: Authentication,
-        #[case] expected_header: &str,
+        #[case] header: &str,
+        #[case] expected: &str,
     ) {
-        let profile_data = indexmap! {
-            "username".into() => "user".into(),
-            "password".into() => "hunter2".into(),
-            "token".into() => "token!".into(),
-        };
-        let profile = create!(Profile, data: profile_data);
+        let profile = create!(
+            Profile,
+            data: indexmap! {"token".into() => "abc".into()},
+        );
         let profile_id = profile.id.clone();
         let context = create!(
             TemplateContext,
@@ -657,32 +1639,316 @@ mod tests {
                 Collection,
                 profiles: indexmap!{profile_id.clone() => profile},
             ),
-            selected_profile: Some(profile_id.clone()),
+            selected_profile: Some(profile_id),
         );
         let recipe = create!(Recipe, authentication: Some(authentication));
-        let recipe_id = recipe.id.clone();
 
         let builder = RequestBuilder::new(recipe, RecipeOptions::default());
         let request = builder.build(&context).await.unwrap();
 
-        let expected_headers: HashMap<String, String> =
-            [("authorization", expected_header)]
-                .into_iter()
-                .map(|(k, v)| (k.to_string(), v.to_string()))
-                .collect();
+        assert_eq!(request.redactions.headers, None);
+        assert_eq!(request.headers[header], expected);
+        assert!(request
+            .to_curl()
+            .unwrap()
+            .contains(&format!("--header '{header}: {expected}'")));
+    }
 
-        assert_eq!(
-            request,
-            Request {
-                id: request.id,
-                profile_id: Some(profile_id),
-                recipe_id,
-                method: Method::GET,
-                url: "http://localhost".parse().unwrap(),
-                headers: (&expected_headers).try_into().unwrap(),
+    /// Sensitive values in each kind of body should be redacted
+    #[rstest]
+    #[case::raw(RecipeBody::Raw("id={{id}}".into()), "<sensitive>")]
+    #[case::raw_not_sensitive(RecipeBody::Raw("id=1".into()), None)]
+    #[case::form_urlencoded(
+        RecipeBody::FormUrlencoded(indexmap! {
+            "count".into() => "1".into(),
+            "id".into() => "{{id}}".into(),
+        }),
+        "count=1&id=<sensitive>"
+    )]
+    #[tokio::test]
+    async fn test_build_body_redactions(
+        #[case] body: RecipeBody,
+        #[case] expected: impl Into<Option<&'static str>>,
+    ) {
+        let profile = create!(
+            Profile,
+            data: indexmap! {"id".into() => "1".into()},
+            sensitive_fields: ["id".into()].into(),
+        );
+        let profile_id = profile.id.clone();
+        let context = create!(
+            TemplateContext,
+            collection: create!(
+                Collection,
+                profiles: indexmap!{profile_id.clone() => profile},
+            ),
+            selected_profile: Some(profile_id),
+        );
+        let recipe = create!(Recipe, body: Some(body));
+
+        let builder = RequestBuilder::new(recipe, RecipeOptions::default());
+        let request = builder.build(&context).await.unwrap();
+
+        assert_eq!(
+            request.redactions.body,
+            expected.into().map(|body| body.as_bytes().into())
+        );
+    }
+
+    #[rstest]
+    #[case::basic(
+        Authentication::Basic {
+            username: "{{username}}".into(),
+            password: Some("{{password}}".into()),
+        },
+        "Basic dXNlcjpodW50ZXIy"
+    )]
+    #[case::basic_no_password(
+        Authentication::Basic {
+            username: "{{username}}".into(),
+            password: None,
+        },
+        "Basic dXNlcjo="
+    )]
+    #[case::bearer(Authentication::Bearer("{{token}}".into()), "Bearer token!")]
+    #[tokio::test]
+    async fn test_authentication(
+        #[case] authentication: Authentication,
+        #[case] expected_header: &str,
+    ) {
+        let profile_data = indexmap! {
+            "username".into() => "user".into(),
+            "password".into() => "hunter2".into(),
+            "token".into() => "token!".into(),
+        };
+        let profile = create!(Profile, data: profile_data);
+        let profile_id = profile.id.clone();
+        let context = create!(
+            TemplateContext,
+            collection: create!(
+                Collection,
+                profiles: indexmap!{profile_id.clone() => profile},
+            ),
+            selected_profile: Some(profile_id.clone()),
+        );
+        let recipe = create!(Recipe, authentication: Some(authentication));
+        let recipe_id = recipe.id.clone();
+
+        let builder = RequestBuilder::new(recipe, RecipeOptions::default());
+        let request = builder.build(&context).await.unwrap();
+
+        let expected_headers: HashMap<String, String> =
+            [("authorization", expected_header)]
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect();
+
+        assert_eq!(
+            request,
+            Request {
+                id: request.id,
+                profile_id: Some(profile_id),
+                recipe_id,
+                method: Method::GET,
+                url: "http://localhost".parse().unwrap(),
+                headers: (&expected_headers).try_into().unwrap(),
                 body: None,
+                authentication: None,
+                http_settings: Default::default(),
+                assertions: Vec::new(),
+                redactions: Default::default(),
+            }
+        );
+    }
+
+    /// API keys go in a header or query param, and are excluded entirely when
+    /// authentication is disabled
+    #[rstest]
+    #[case::header(
+        ApiKeyLocation::Header,
+        false,
+        "http://localhost/?mode=sudo",
+        &[("x-api-key", "token!")],
+    )]
+    #[case::query(
+        ApiKeyLocation::Query,
+        false,
+        "http://localhost/?mode=sudo&X-Api-Key=token%21",
+        &[],
+    )]
+    #[case::disabled(
+        ApiKeyLocation::Header,
+        true,
+        "http://localhost/?mode=sudo",
+        &[],
+    )]
+    #[tokio::test]
+    async fn test_api_key(
+        #[case] location: ApiKeyLocation,
+        #[case] disable_authentication: bool,
+        #[case] expected_url: &str,
+        #[case] expected_headers: &[(&str, &str)],
+    ) {
+        let context = create!(TemplateContext);
+        let recipe = create!(
+            Recipe,
+            url: Some("http://localhost/".into()),
+            query: indexmap! {"mode".into() => vec!["sudo".into()]},
+            authentication: Some(Authentication::ApiKey {
+                location,
+                name: "X-Api-Key".into(),
+                value: "token!".into(),
+            }),
+        );
+
+        let builder = RequestBuilder::new(
+            recipe,
+            RecipeOptions {
+                disable_authentication,
+                ..Default::default()
+            },
+        );
+        let request = builder.build(&context).await.unwrap();
+
+        assert_eq!(request.url.as_str(), expected_url);
+        assert_eq!(
+            request.headers,
+            header_map(expected_headers.iter().copied())
+        );
+    }
+
+    /// Repeated query params and headers are sent once per value, and each
+    /// value can be disabled individually
+    #[tokio::test]
+    async fn test_repeated_headers_and_query_params() {
+        let context = create!(TemplateContext);
+        let recipe = create!(
+            Recipe,
+            query: indexmap! {
+                "id".into() => vec!["1".into(), "2".into(), "3".into()],
+            },
+            headers: indexmap! {
+                "Accept".into() => vec![
+                    "application/json".into(),
+                    "text/plain".into(),
+                ],
+            },
+        );
+
+        let builder = RequestBuilder::new(
+            recipe,
+            RecipeOptions {
+                disabled_query_parameters: [("id".to_owned(), 1)].into(),
+                ..Default::default()
+            },
+        );
+        let request = builder.build(&context).await.unwrap();
+
+        assert_eq!(request.url.as_str(), "http://localhost/?id=1&id=3");
+        assert_eq!(
+            request
+                .headers
+                .get_all("accept")
+                .iter()
+                .map(|value| value.to_str().unwrap())
+                .collect::<Vec<_>>(),
+            vec!["application/json", "text/plain"]
+        );
+    }
+
+    /// Header names are templates. If a name renders to something invalid,
+    /// the error should show what it rendered to
+    #[rstest]
+    #[case::valid("acme", Ok("abc"))]
+    #[case::invalid("a b", Err("which rendered to `X-a b-Token`"))]
+    #[tokio::test]
+    async fn test_templated_header_name(
+        #[case] tenant: &str,
+        #[case] expected: Result<&str, &str>,
+    ) {
+        let context = create!(
+            TemplateContext,
+            overrides: indexmap! {"tenant".into() => tenant.into()},
+        );
+        let recipe = create!(
+            Recipe,
+            headers: indexmap! {
+                "X-{{tenant}}-Token".into() => vec!["abc".into()],
+            },
+        );
+
+        let builder = RequestBuilder::new(recipe, RecipeOptions::default());
+        let result = builder.build(&context).await.map_err(|err| err.error);
+        match expected {
+            Ok(expected) => {
+                let request = result.unwrap();
+                assert_eq!(request.headers["x-acme-token"], expected);
             }
+            Err(expected) => assert_err!(result, expected),
+        }
+    }
+
+    /// Path params fill whole `:param` segments, and are percent-encoded
+    #[rstest]
+    #[case::basic(
+        "http://localhost/users/:user_id/orders/:order_id",
+        &[],
+        "http://localhost/users/5/orders/a%20b%2Fc",
+    )]
+    #[case::port_untouched(
+        "http://localhost:3000/users/:user_id",
+        &[],
+        "http://localhost:3000/users/5",
+    )]
+    #[case::partial_segment_untouched(
+        "http://localhost/users/:user_id:activate",
+        &[],
+        "http://localhost/users/:user_id:activate",
+    )]
+    #[case::missing_value(
+        "http://localhost/users/:user_id/:unknown",
+        &[],
+        "http://localhost/users/5/:unknown",
+    )]
+    #[case::override_(
+        "http://localhost/users/:user_id/orders/:order_id",
+        &[("user_id", "6"), ("order_id", "7")],
+        "http://localhost/users/6/orders/7",
+    )]
+    #[case::override_unknown(
+        "http://localhost/users/:user_id/:unknown",
+        &[("unknown", "?")],
+        "http://localhost/users/5/%3F",
+    )]
+    #[tokio::test]
+    async fn test_path_params(
+        #[case] url: &str,
+        #[case] path_overrides: &[(&str, &str)],
+        #[case] expected_url: &str,
+    ) {
+        let context = create!(TemplateContext);
+        let recipe = create!(
+            Recipe,
+            url: Some(url.into()),
+            path: indexmap! {
+                "user_id".into() => "5".into(),
+                "order_id".into() => "a b/c".into(),
+            },
+        );
+
+        let builder = RequestBuilder::new(
+            recipe,
+            RecipeOptions {
+                path_overrides: path_overrides
+                    .iter()
+                    .map(|(k, v)| (k.to_string(), v.to_string()))
+                    .collect(),
+                ..Default::default()
+            },
         );
+        let request = builder.build(&context).await.unwrap();
+
+        assert_eq!(request.url.as_str(), expected_url);
     }
 
     #[tokio::test]
@@ -691,12 +1957,12 @@ mod tests {
         let recipe = create!(
             Recipe,
             query: indexmap! {
-                "mode".into() => "sudo".into(),
-                "fast".into() => "true".into(),
+                "mode".into() => vec!["sudo".into()],
+                "fast".into() => vec!["true".into()],
             },
             headers: indexmap! {
-                "Accept".into() => "application/json".into(),
-                "Content-Type".into() => "application/json".into(),
+                "Accept".into() => vec!["application/json".into()],
+                "Content-Type".into() => vec!["application/json".into()],
             },
         );
         let recipe_id = recipe.id.clone();
@@ -704,8 +1970,9 @@ mod tests {
         let builder = RequestBuilder::new(
             recipe,
             RecipeOptions {
-                disabled_headers: ["Content-Type".to_owned()].into(),
-                disabled_query_parameters: ["fast".to_owned()].into(),
+                disabled_headers: [("Content-Type".to_owned(), 0)].into(),
+                disabled_query_parameters: [("fast".to_owned(), 0)].into(),
+                ..Default::default()
             },
         );
         let request = builder.build(&context).await.unwrap();
@@ -726,7 +1993,585 @@ mod tests {
                 url: "http://localhost?mode=sudo".parse().unwrap(),
                 headers: (&expected_headers).try_into().unwrap(),
                 body: None,
+                authentication: None,
+                http_settings: Default::default(),
+                assertions: Vec::new(),
+                redactions: Default::default(),
             }
         );
     }
+
+    /// Recipes should inherit from their parents via `extends`. Scalar values
+    /// come from the nearest parent, while maps are merged. Parent recipes
+    /// take precedence over folder defaults.
+    #[tokio::test]
+    async fn test_recipe_extends() {
+        let base = create!(
+            Recipe,
+            id: "base".into(),
+            method: Some("POST".parse().unwrap()),
+            url: Some("http://localhost/base".into()),
+            path: indexmap! {"id".into() => "1".into()},
+            body: Some("base".into()),
+            authentication: Some(Authentication::Bearer("base".into())),
+            query: indexmap! {"mode".into() => vec!["base".into()]},
+            headers: indexmap! {
+                "Accept".into() => vec!["application/json".into()],
+                "X-Base".into() => vec!["base".into()],
+            },
+        );
+        let middle = create!(
+            Recipe,
+            id: "middle".into(),
+            extends: Some("base".into()),
+            method: None,
+            url: Some("http://localhost/middle/:id".into()),
+            headers: indexmap! {"X-Base".into() => vec!["middle".into()]},
+        );
+        let child = create!(
+            Recipe,
+            id: "child".into(),
+            extends: Some("middle".into()),
+            method: None,
+            url: None,
+            query: indexmap! {"fast".into() => vec!["true".into()]},
+            headers: indexmap! {"accept".into() => vec!["text/plain".into()]},
+        );
+        let folder = create!(
+            Folder,
+            authentication: Some(Authentication::Bearer("folder".into())),
+            headers: indexmap! {"X-Folder".into() => vec!["folder".into()]},
+            children: indexmap! {"child".into() => child.clone().into()},
+        );
+        let context = create!(
+            TemplateContext,
+            collection: create!(
+                Collection,
+                recipes: RecipeTree::new(indexmap! {
+                    "base".into() => base.into(),
+                    "middle".into() => middle.into(),
+                    folder.id.clone() => folder.into(),
+                })
+                .unwrap(),
+            ),
+        );
+
+        let builder = RequestBuilder::new(child, RecipeOptions::default());
+        let request = builder.build(&context).await.unwrap();
+
+        assert_eq!(
+            request,
+            Request {
+                id: request.id,
+                profile_id: None,
+                recipe_id: "child".into(),
+                method: Method::POST,
+                url: "http://localhost/middle/1?mode=base&fast=true"
+                    .parse()
+                    .unwrap(),
+                headers: header_map([
+                    ("x-folder", "folder"),
+                    ("accept", "text/plain"),
+                    ("x-base", "middle"),
+                    ("authorization", "Bearer base"),
+                ]),
+                body: Some(Vec::from(b"base").into()),
+                authentication: None,
+                http_settings: Default::default(),
+                assertions: Vec::new(),
+                redactions: Default::default(),
+            }
+        );
+    }
+
+    /// Building just the body or URL should also apply inheritance
+    #[tokio::test]
+    async fn test_recipe_extends_partial() {
+        let base = create!(
+            Recipe,
+            id: "base".into(),
+            url: Some("http://localhost/base".into()),
+            query: indexmap! {"mode".into() => vec!["base".into()]},
+            body: Some("base body".into()),
+        );
+        let child = create!(
+            Recipe,
+            id: "child".into(),
+            extends: Some("base".into()),
+            url: None,
+            body: None,
+        );
+        let context = create!(
+            TemplateContext,
+            collection: create!(
+                Collection,
+                recipes: RecipeTree::new(indexmap! {
+                    "base".into() => base.into(),
+                })
+                .unwrap(),
+            ),
+        );
+
+        let builder =
+            RequestBuilder::new(child.clone(), RecipeOptions::default());
+        let url = builder.build_url(&context).await.unwrap();
+        assert_eq!(url.as_str(), "http://localhost/base?mode=base");
+
+        let builder = RequestBuilder::new(child, RecipeOptions::default());
+        let body = builder.build_body(&context).await.unwrap().unwrap();
+        assert_eq!(body.bytes(), b"base body");
+    }
+
+    /// HTTP settings are resolved field-by-field. The recipe takes precedence,
+    /// then parent recipes, then the selected profile and its parents
+    #[tokio::test]
+    async fn test_http_settings() {
+        let base_profile = create!(
+            Profile,
+            id: "base".into(),
+            http: HttpSettings {
+                timeout: Some(Duration::from_secs(1)),
+                gzip: Some(true),
+                ..Default::default()
+            },
+        );
+        let profile = create!(
+            Profile,
+            id: "profile".into(),
+            extends: vec!["base".into()],
+            http: HttpSettings {
+                timeout: Some(Duration::from_secs(2)),
+                redirect: Some(RedirectPolicy::Follow),
+                ..Default::default()
+            },
+        );
+        let parent = create!(
+            Recipe,
+            id: "parent".into(),
+            http: HttpSettings {
+                redirect: Some(RedirectPolicy::None),
+                http_version: Some(HttpVersion::Http2),
+                ..Default::default()
+            },
+        );
+        let child = create!(
+            Recipe,
+            id: "child".into(),
+            extends: Some("parent".into()),
+            http: HttpSettings {
+                http_version: Some(HttpVersion::Http1),
+                ..Default::default()
+            },
+        );
+        let context = create!(
+            TemplateContext,
+            collection: create!(
+                Collection,
+                profiles: indexmap! {
+                    "base".into() => base_profile,
+                    "profile".into() => profile,
+                },
+                recipes: RecipeTree::new(indexmap! {
+                    "parent".into() => parent.into(),
+                })
+                .unwrap(),
+            ),
+            selected_profile: Some("profile".into()),
+        );
+
+        let builder = RequestBuilder::new(child, RecipeOptions::default());
+        let request = builder.build(&context).await.unwrap();
+
+        assert_eq!(
+            request.http_settings,
+            HttpSettings {
+                timeout: Some(Duration::from_secs(2)),
+                redirect: Some(RedirectPolicy::None),
+                http_version: Some(HttpVersion::Http1),
+                gzip: Some(true),
+            }
+        );
+    }
+
+    /// The engine should send each request with a client that matches its
+    /// settings
+    #[rstest]
+    #[case::follow(HttpSettings::default(), StatusCode::OK)]
+    #[case::none(
+        HttpSettings {
+            redirect: Some(RedirectPolicy::None),
+            ..Default::default()
+        },
+        StatusCode::FOUND,
+    )]
+    #[tokio::test]
+    async fn test_send_redirect(
+        #[case] settings: HttpSettings,
+        #[case] expected_status: StatusCode,
+    ) {
+        let mut server = mockito::Server::new_async().await;
+        let url = server.url();
+        server
+            .mock("GET", "/old")
+            .with_status(302)
+            .with_header("Location", &format!("{url}/new"))
+            .create_async()
+            .await;
+        server.mock("GET", "/new").create_async().await;
+
+        let database = CollectionDatabase::testing();
+        let http_engine = HttpEngine::new(&Config::default(), database.clone());
+        let context = create!(TemplateContext, database: database);
+        let recipe = create!(
+            Recipe,
+            url: Some(format!("{url}/old").as_str().into()),
+            http: settings.clone(),
+        );
+        let request = RequestBuilder::new(recipe, RecipeOptions::default())
+            .build(&context)
+            .await
+            .unwrap();
+        let record = http_engine.send(request.into()).await.unwrap();
+
+        assert_eq!(record.response.status, expected_status);
+        assert_eq!(record.request.http_settings, settings);
+    }
+
+    /// Folder defaults should be merged into the recipe, with the recipe and
+    /// inner folders taking precedence
+    #[tokio::test]
+    async fn test_folder_defaults() {
+        let recipe = create!(
+            Recipe,
+            url: Some("/users".into()),
+            query: indexmap! {"mode".into() => vec!["recipe".into()]},
+            headers: indexmap! {
+                // Header names are case-insensitive
+                "accept".into() => vec!["text/plain".into()],
+            },
+        );
+        let recipe_id = recipe.id.clone();
+        let inner = create!(
+            Folder,
+            id: "inner".into(),
+            url_prefix: Some("/v1".into()),
+            query: indexmap! {
+                "mode".into() => vec!["inner".into()],
+                "fast".into() => vec!["true".into()],
+            },
+            headers: indexmap! {"X-Folder".into() => vec!["inner".into()]},
+            children: indexmap! {recipe_id.clone() => recipe.clone().into()},
+        );
+        let outer = create!(
+            Folder,
+            id: "outer".into(),
+            url_prefix: Some("{{host}}".into()),
+            authentication: Some(Authentication::Bearer("token".into())),
+            headers: indexmap! {
+                "Accept".into() => vec!["application/json".into()],
+                "X-Folder".into() => vec!["outer".into()],
+            },
+            children: indexmap! {"inner".into() => inner.into()},
+        );
+        let profile = create!(
+            Profile,
+            data: indexmap! {"host".into() => "http://localhost".into()},
+        );
+        let profile_id = profile.id.clone();
+        let context = create!(
+            TemplateContext,
+            collection: create!(
+                Collection,
+                profiles: indexmap! {profile_id.clone() => profile},
+                recipes: RecipeTree::new(
+                    indexmap! {"outer".into() => outer.into()}
+                )
+                .unwrap(),
+            ),
+            selected_profile: Some(profile_id.clone()),
+        );
+
+        let builder = RequestBuilder::new(recipe, RecipeOptions::default());
+        let request = builder.build(&context).await.unwrap();
+
+        assert_eq!(
+            request,
+            Request {
+                id: request.id,
+                profile_id: Some(profile_id),
+                recipe_id,
+                method: Method::GET,
+                url: "http://localhost/v1/users?mode=recipe&fast=true"
+                    .parse()
+                    .unwrap(),
+                headers: header_map([
+                    ("accept", "text/plain"),
+                    ("x-folder", "inner"),
+                    ("authorization", "Bearer token"),
+                ]),
+                body: None,
+                authentication: None,
+                http_settings: Default::default(),
+                assertions: Vec::new(),
+                redactions: Default::default(),
+            }
+        );
+    }
+
+    /// Structured bodies should be encoded and get an implicit `Content-Type`,
+    /// unless the user provided their own
+    #[rstest]
+    #[case::form_urlencoded(
+        RecipeBody::FormUrlencoded(indexmap! {
+            "user_id".into() => "{{user_id}}".into(),
+            "token".into() => "a b&c".into(),
+        }),
+        None,
+        "application/x-www-form-urlencoded",
+        "user_id=1&token=a+b%26c",
+    )]
+    #[case::form_urlencoded_content_type_override(
+        RecipeBody::FormUrlencoded(indexmap! {
+            "user_id".into() => "{{user_id}}".into(),
+        }),
+        Some("text/plain"),
+        "text/plain",
+        "user_id=1",
+    )]
+    #[case::form_multipart(
+        RecipeBody::FormMultipart(indexmap! {
+            "user_id".into() => MultipartField::Text("{{user_id}}".into()),
+        }),
+        None,
+        "multipart/form-data; boundary={boundary}",
+        "--{boundary}\r\n\
+        Content-Disposition: form-data; name=\"user_id\"\r\n\r\n\
+        1\r\n\
+        --{boundary}--\r\n",
+    )]
+    // Quotes and line breaks can't break out of the Content-Disposition
+    #[case::form_multipart_escaped(
+        RecipeBody::FormMultipart(indexmap! {
+            "a\"b\r\nX-Injected: 1".into() =>
+                MultipartField::Text("{{user_id}}".into()),
+        }),
+        None,
+        "multipart/form-data; boundary={boundary}",
+        "--{boundary}\r\n\
+        Content-Disposition: form-data; name=\"a%22b%0D%0AX-Injected: 1\"\
+        \r\n\r\n\
+        1\r\n\
+        --{boundary}--\r\n",
+    )]
+    #[case::json(
+        serde_yaml::from_str(
+            "!json
+            user_id: '{{user_id}}'
+            name: 'user {{user_id}}'
+            tags: [a, 2, true, null]"
+        ).unwrap(),
+        None,
+        "application/json",
+        r#"{"user_id":1,"name":"user 1","tags":["a",2,true,null]}"#,
+    )]
+    #[tokio::test]
+    async fn test_structured_body(
+        #[case] body: RecipeBody,
+        #[case] content_type_header: Option<&str>,
+        #[case] expected_content_type: &str,
+        #[case] expected_body: &str,
+    ) {
+        let profile_data = indexmap! {"user_id".into() => "1".into()};
+        let profile = create!(Profile, data: profile_data);
+        let profile_id = profile.id.clone();
+        let context = create!(
+            TemplateContext,
+            collection: create!(
+                Collection,
+                profiles: indexmap!{profile_id.clone() => profile},
+            ),
+            selected_profile: Some(profile_id.clone()),
+        );
+        let headers = content_type_header
+            .into_iter()
+            .map(|value| ("Content-Type".into(), vec![value.into()]))
+            .collect();
+        let recipe = create!(Recipe, body: Some(body), headers: headers);
+
+        let builder = RequestBuilder::new(recipe, RecipeOptions::default());
+        let boundary = builder.multipart_boundary();
+        let request = builder.build(&context).await.unwrap();
+
+        assert_eq!(
+            request.headers.get(header::CONTENT_TYPE).unwrap(),
+            &expected_content_type.replace("{boundary}", &boundary)
+        );
+        assert_eq!(
+            request.body.unwrap().bytes(),
+            expected_body.replace("{boundary}", &boundary).as_bytes()
+        );
+    }
+
+    /// Multipart fields can be loaded from files
+    #[tokio::test]
+    async fn test_form_multipart_file() {
+        // Use a unique directory so concurrent runs don't collide
+        let directory =
+            env::temp_dir().join(format!("slumber-{}", Uuid::new_v4()));
+        fs::create_dir_all(&directory).await.unwrap();
+        let path = directory.join("up\"load.json");
+        fs::write(&path, "{\"hello\": \"world\"}").await.unwrap();
+
+        let context = create!(TemplateContext);
+        let recipe = create!(
+            Recipe,
+            body: Some(RecipeBody::FormMultipart(indexmap! {
+                "name".into() => MultipartField::Text("upload".into()),
+                "file".into() => MultipartField::File(
+                    path.to_str().unwrap().into(),
+                ),
+            })),
+        );
+
+        let builder = RequestBuilder::new(recipe, RecipeOptions::default());
+        let boundary = builder.multipart_boundary();
+        let body = builder.build_body(&context).await.unwrap().unwrap();
+
+        let expected = format!(
+            "--{boundary}\r\n\
+            Content-Disposition: form-data; name=\"name\"\r\n\r\n\
+            upload\r\n\
+            --{boundary}\r\n\
+            Content-Disposition: form-data; name=\"file\"; \
+            filename=\"up%22load.json\"\r\n\
+            Content-Type: application/json\r\n\r\n\
+            {{\"hello\": \"world\"}}\r\n\
+            --{boundary}--\r\n"
+        );
+        assert_eq!(std::str::from_utf8(body.bytes()).unwrap(), expected);
+    }
+
+    /// Relative paths for multipart files are resolved against the
+    /// collection file's directory, not the current directory
+    #[tokio::test]
+    async fn test_form_multipart_file_relative() {
+        let directory =
+            env::temp_dir().join(format!("slumber-{}", Uuid::new_v4()));
+        fs::create_dir_all(directory.join("files")).await.unwrap();
+        let collection_path = directory.join("slumber.yml");
+        fs::write(&collection_path, "").await.unwrap();
+        fs::write(directory.join("files/upload.txt"), "hello")
+            .await
+            .unwrap();
+
+        // The test runs from the repo root, so this relative path only works
+        // if it's resolved against the collection file
+        let context = create!(
+            TemplateContext,
+            database: Database::testing()
+                .into_collection(&collection_path)
+                .unwrap(),
+        );
+        let recipe = create!(
+            Recipe,
+            body: Some(RecipeBody::FormMultipart(indexmap! {
+                "file".into() =>
+                    MultipartField::File("./files/upload.txt".into()),
+            })),
+        );
+
+        let builder = RequestBuilder::new(recipe, RecipeOptions::default());
+        let boundary = builder.multipart_boundary();
+        let body = builder.build_body(&context).await.unwrap().unwrap();
+
+        let expected = format!(
+            "--{boundary}\r\n\
+            Content-Disposition: form-data; name=\"file\"; \
+            filename=\"upload.txt\"\r\n\
+            Content-Type: text/plain\r\n\r\n\
+            hello\r\n\
+            --{boundary}--\r\n"
+        );
+        assert_eq!(std::str::from_utf8(body.bytes()).unwrap(), expected);
+    }
+
+    /// A JSON body value that is a single key should keep the JSON type of
+    /// what it rendered to. Anything that isn't valid JSON is a string.
+    #[rstest]
+    #[case::number("3", "3")]
+    #[case::bool("true", "true")]
+    #[case::null("null", "null")]
+    #[case::object(r#"{"a": [1, "b"]}"#, r#"{"a":[1,"b"]}"#)]
+    #[case::string("hello", r#""hello""#)]
+    #[case::string_quoted(r#""3""#, r#""3""#)]
+    #[case::string_empty("", "\"\"")]
+    #[case::string_escaped(r#"say "hi"\"#, r#""say \"hi\"\\""#)]
+    #[tokio::test]
+    async fn test_json_body_types(#[case] value: &str, #[case] expected: &str) {
+        let profile = create!(
+            Profile,
+            data: indexmap! {"value".into() => Template::raw(value.into())},
+        );
+        let profile_id = profile.id.clone();
+        let context = create!(
+            TemplateContext,
+            collection: create!(
+                Collection,
+                profiles: indexmap!{profile_id.clone() => profile},
+            ),
+            selected_profile: Some(profile_id),
+        );
+        let recipe = create!(
+            Recipe,
+            body: Some(RecipeBody::Json(JsonTemplate::Array(vec![
+                JsonTemplate::String("{{value}}".into()),
+                JsonTemplate::String("{{value}}!".into()),
+            ]))),
+        );
+
+        let builder = RequestBuilder::new(recipe, RecipeOptions::default());
+        let body = builder.build_body(&context).await.unwrap().unwrap();
+        // The second value always gets stringified
+        let string = serde_json::Value::String(format!("{value}!"));
+        assert_eq!(
+            std::str::from_utf8(body.bytes()).unwrap(),
+            format!("[{expected},{string}]")
+        );
+    }
+
+    /// Errors in a JSON body should point to the value that failed
+    #[tokio::test]
+    async fn test_json_body_error() {
+        let context = create!(TemplateContext);
+        let recipe = create!(
+            Recipe,
+            body: Some(serde_yaml::from_str(
+                "!json {user: {ids: [1, '{{unknown}}']}}"
+            ).unwrap()),
+        );
+
+        let builder = RequestBuilder::new(recipe, RecipeOptions::default());
+        assert_err!(
+            builder.build_body(&context).await.map_err(|err| err.error),
+            "Error rendering JSON body at `$.user.ids[1]`"
+        );
+    }
+
+    /// Missing file for a multipart field should give a helpful error
+    #[tokio::test]
+    async fn test_form_multipart_file_error() {
+        let context = create!(TemplateContext);
+        let recipe = create!(
+            Recipe,
+            body: Some(RecipeBody::FormMultipart(indexmap! {
+                "file".into() => MultipartField::File("not-real".into()),
+            })),
+        );
+
+        let builder = RequestBuilder::new(recipe, RecipeOptions::default());
+        assert_err!(
+            builder.build_body(&context).await.map_err(|err| err.error),
+            "Error reading file `not-real` for form field `file`"
+        );
+    }
 }
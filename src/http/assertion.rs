@@ -0,0 +1,364 @@
+//! Response assertions. Assertions are defined on recipes, and checked against
+//! every response received for the recipe.
+
+use crate::{
+    collection::{Assertion, Expectation, JsonType},
+    http::{ContentType, RequestRecord},
+};
+use reqwest::header::HeaderValue;
+use serde_json::Value;
+use std::{
+    borrow::Cow,
+    fmt::{self, Display, Formatter},
+};
+
+/// The outcome of checking a single assertion against a response
+#[derive(Debug)]
+pub struct AssertionResult<'a> {
+    pub assertion: &'a Assertion,
+    /// Why the assertion failed. `None` if it passed
+    pub failure: Option<String>,
+}
+
+impl<'a> AssertionResult<'a> {
+    pub fn passed(&self) -> bool {
+        self.failure.is_none()
+    }
+}
+
+impl RequestRecord {
+    /// Check the response against each assertion attached to the request.
+    /// Results are in the same order as the assertions.
+    pub fn check_assertions(&self) -> Vec<AssertionResult<'_>> {
+        let assertions = &self.request.assertions;
+        // Only parse the body if something needs it
+        let body = assertions
+            .iter()
+            .any(|assertion| matches!(assertion, Assertion::Body { .. }))
+            .then(|| {
+                ContentType::parse_response(&self.response)
+                    .map(|content| content.to_json().into_owned())
+                    .map_err(|error| format!("Error parsing body: {error}"))
+            });
+
+        assertions
+            .iter()
+            .map(|assertion| AssertionResult {
+                assertion,
+                failure: self.check_assertion(assertion, body.as_ref()).err(),
+            })
+            .collect()
+    }
+
+    /// Check a single assertion. `body` must be populated for body assertions
+    fn check_assertion(
+        &self,
+        assertion: &Assertion,
+        body: Option<&Result<Value, String>>,
+    ) -> Result<(), String> {
+        match assertion {
+            Assertion::Status(range) => {
+                let status = self.response.status.as_u16();
+                if range.contains(status) {
+                    Ok(())
+                } else {
+                    Err(format!("Got {status}"))
+                }
+            }
+
+            Assertion::Header { name, expect } => {
+                let check_value = |value: &HeaderValue| {
+                    let value = value
+                        .to_str()
+                        .map_err(|_| "Value is not valid UTF-8".to_owned())?;
+                    match expect {
+                        // Header values are always strings, so compare
+                        // against the stringified expectation. Otherwise
+                        // `!equals 3` would never match
+                        Expectation::Equals(expected) => {
+                            let expected = stringify(expected);
+                            if value == expected {
+                                Ok(())
+                            } else {
+                                Err(format!(
+                                    "Expected `{expected}`, got `{value}`"
+                                ))
+                            }
+                        }
+                        _ => expect.check(Some(&Value::String(value.into()))),
+                    }
+                };
+
+                // A header can be repeated (e.g. `set-cookie`), in which case
+                // any one of its values can pass
+                let mut errors = Vec::new();
+                for value in self.response.headers.get_all(name.as_str()) {
+                    match check_value(value) {
+                        Ok(()) => return Ok(()),
+                        Err(error) => errors.push(error),
+                    }
+                }
+                match errors.as_slice() {
+                    [] => expect.check(None),
+                    [error] => Err(error.clone()),
+                    errors => Err(format!(
+                        "No value passed out of {}: {}",
+                        errors.len(),
+                        errors.join("; ")
+                    )),
+                }
+            }
+
+            Assertion::Body { query, expect } => {
+                let body = body
+                    .expect("Body must be parsed for body assertions")
+                    .as_ref()
+                    .map_err(String::clone)?;
+                match query.query_json(body).as_slice() {
+                    [] => expect.check(None),
+                    [value] => expect.check(Some(value)),
+                    _ if matches!(expect, Expectation::Exists) => Ok(()),
+                    values => Err(format!(
+                        "Query matched {} values; expected exactly one",
+                        values.len()
+                    )),
+                }
+            }
+
+            Assertion::Duration(max) => {
+                let duration = self.duration().num_milliseconds();
+                if duration <= *max as i64 {
+                    Ok(())
+                } else {
+                    Err(format!("Took {duration}ms"))
+                }
+            }
+        }
+    }
+}
+
+impl Expectation {
+    /// Check a value against this expectation. `None` means the value is
+    /// missing
+    fn check(&self, actual: Option<&Value>) -> Result<(), String> {
+        let actual = actual.ok_or_else(|| "Value is missing".to_owned())?;
+        match self {
+            Self::Exists => Ok(()),
+            Self::Equals(expected) => {
+                if actual == expected {
+                    Ok(())
+                } else {
+                    Err(format!("Expected {expected}, got {actual}"))
+                }
+            }
+            Self::Matches(pattern) => {
+                let actual = stringify(actual);
+                if pattern.is_match(&actual) {
+                    Ok(())
+                } else {
+                    Err(format!("`{actual}` does not match `{pattern}`"))
+                }
+            }
+            Self::Type(expected) => {
+                let actual = JsonType::of(actual);
+                if actual == *expected {
+                    Ok(())
+                } else {
+                    Err(format!("Expected {expected}, got {actual}"))
+                }
+            }
+        }
+    }
+}
+
+impl JsonType {
+    /// Get the type of a JSON value
+    fn of(value: &Value) -> Self {
+        match value {
+            Value::Null => Self::Null,
+            Value::Bool(_) => Self::Boolean,
+            Value::Number(_) => Self::Number,
+            Value::String(_) => Self::String,
+            Value::Array(_) => Self::Array,
+            Value::Object(_) => Self::Object,
+        }
+    }
+}
+
+impl Display for Assertion {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Status(range) => write!(f, "Status is {range}"),
+            Self::Header { name, expect } => {
+                write!(f, "Header `{name}` {expect}")
+            }
+            Self::Body { query, expect } => {
+                write!(f, "Body `{query}` {expect}")
+            }
+            Self::Duration(max) => {
+                write!(f, "Response time is at most {max}ms")
+            }
+        }
+    }
+}
+
+impl Display for Expectation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Exists => write!(f, "exists"),
+            Self::Equals(value) => write!(f, "equals {value}"),
+            Self::Matches(pattern) => write!(f, "matches `{pattern}`"),
+            Self::Type(json_type) => write!(f, "is of type {json_type}"),
+        }
+    }
+}
+
+/// Convert a JSON value to a string for comparison. Strings are used as-is,
+/// everything else is converted to JSON
+fn stringify(value: &Value) -> Cow<'_, str> {
+    match value {
+        Value::String(s) => s.into(),
+        other => other.to_string().into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::*;
+    use chrono::{Duration, Utc};
+    use factori::create;
+    use rstest::rstest;
+    use serde_json::json;
+
+    /// Parse an assertion from YAML, for brevity
+    fn assertion(yaml: &str) -> Assertion {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    #[rstest]
+    #[case::status_code("!status 200", None)]
+    #[case::status_class("!status 2xx", None)]
+    #[case::status_range("!status 400-499", Some("Got 200"))]
+    #[case::header_exists("!header {name: Content-Type, expect: exists}", None)]
+    #[case::header_missing(
+        "!header {name: X-Missing, expect: exists}",
+        Some("Value is missing")
+    )]
+    #[case::header_equals("!header {name: x-count, expect: !equals 3}", None)]
+    #[case::header_matches(
+        "!header {name: content-type, expect: !matches ^text/}",
+        Some("`application/json` does not match `^text/`")
+    )]
+    #[case::header_repeated(
+        "!header {name: set-cookie, expect: !matches ^b=}",
+        None
+    )]
+    #[case::header_repeated_equals(
+        "!header {name: set-cookie, expect: !equals b=2}",
+        None
+    )]
+    #[case::header_repeated_mismatch(
+        "!header {name: set-cookie, expect: !matches ^c=}",
+        Some(
+            "No value passed out of 2: `a=1` does not match `^c=`; \
+            `b=2` does not match `^c=`"
+        )
+    )]
+    #[case::body_equals("!body {query: $.id, expect: !equals 3}", None)]
+    #[case::body_equals_type_mismatch(
+        "!body {query: $.id, expect: !equals '3'}",
+        Some("Expected \"3\", got 3")
+    )]
+    #[case::body_matches("!body {query: $.name, expect: !matches ^Jim}", None)]
+    #[case::body_exists_many(
+        "!body {query: '$.tags[*]', expect: exists}",
+        None
+    )]
+    #[case::body_many(
+        "!body {query: '$.tags[*]', expect: !type string}",
+        Some("Query matched 2 values; expected exactly one")
+    )]
+    #[case::body_missing(
+        "!body {query: $.missing, expect: exists}",
+        Some("Value is missing")
+    )]
+    #[case::body_type("!body {query: $.tags, expect: !type array}", None)]
+    #[case::body_type_mismatch(
+        "!body {query: $.tags, expect: !type object}",
+        Some("Expected object, got array")
+    )]
+    #[case::duration("!duration 500", None)]
+    #[case::duration_exceeded("!duration 50", Some("Took 100ms"))]
+    fn test_check_assertions(
+        #[case] yaml: &str,
+        #[case] expected_failure: Option<&str>,
+    ) {
+        let assertion = assertion(yaml);
+        let start_time = Utc::now();
+        let record = create!(
+            RequestRecord,
+            request: create!(Request, assertions: vec![assertion]).into(),
+            response: create!(
+                Response,
+                headers: header_map([
+                    ("content-type", "application/json"),
+                    ("x-count", "3"),
+                    ("set-cookie", "a=1"),
+                    ("set-cookie", "b=2"),
+                ]),
+                body: json!({"id": 3, "name": "Jimmy", "tags": ["a", "b"]})
+                    .to_string()
+                    .into(),
+            )
+            .into(),
+            start_time: start_time,
+            end_time: start_time
+                + Duration::try_milliseconds(100).unwrap(),
+        );
+
+        let results = record.check_assertions();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].failure.as_deref(), expected_failure);
+    }
+
+    /// A body that can't be parsed fails every body assertion
+    #[test]
+    fn test_check_assertions_invalid_body() {
+        let record = create!(
+            RequestRecord,
+            request: create!(
+                Request,
+                assertions: vec![
+                    assertion("!status 200"),
+                    assertion("!body {query: $.id, expect: exists}"),
+                ],
+            )
+            .into(),
+            response: create!(Response, body: "not json".into()).into(),
+        );
+
+        let results = record.check_assertions();
+        assert!(results[0].passed());
+        assert_eq!(
+            results[1].failure.as_deref(),
+            Some("Error parsing body: Response has no content-type header")
+        );
+    }
+
+    #[rstest]
+    #[case::status("!status 2xx", "Status is 2xx")]
+    #[case::status_range("!status 200-204", "Status is 200-204")]
+    #[case::header(
+        "!header {name: Accept, expect: !matches json}",
+        "Header `Accept` matches `json`"
+    )]
+    #[case::body(
+        "!body {query: $.id, expect: !equals 3}",
+        "Body `$.id` equals 3"
+    )]
+    #[case::duration("!duration 500", "Response time is at most 500ms")]
+    fn test_display(#[case] yaml: &str, #[case] expected: &str) {
+        assert_eq!(assertion(yaml).to_string(), expected);
+    }
+}
@@ -0,0 +1,358 @@
+//! AWS [Signature Version 4](https://docs.aws.amazon.com/IAM/latest/UserGuide/reference_aws-signing.html)
+//! request signing. The signature covers the method, URL, headers and body,
+//! so it can only be generated once the request is fully rendered. It also
+//! includes a timestamp, so the HTTP engine signs the request immediately
+//! before sending it.
+
+use crate::{http::Request, util::uri_encode};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use itertools::Itertools;
+use reqwest::header::{self, HeaderMap, HeaderName, HeaderValue};
+use sha2::{Digest, Sha256};
+use std::fmt::{Debug, Write};
+
+const ALGORITHM: &str = "AWS4-HMAC-SHA256";
+
+/// Rendered credentials for signing a request. All fields except the secret
+/// key are validated as header values on creation, so signing is infallible.
+#[derive(Clone)]
+#[cfg_attr(test, derive(PartialEq))]
+pub struct AwsSigV4Credentials {
+    access_key: String,
+    secret_key: String,
+    session_token: Option<HeaderValue>,
+    region: String,
+    service: String,
+}
+
+impl AwsSigV4Credentials {
+    pub fn new(
+        access_key: String,
+        secret_key: String,
+        session_token: Option<String>,
+        region: String,
+        service: String,
+    ) -> anyhow::Result<Self> {
+        for (label, value) in [
+            ("access key", &access_key),
+            ("region", &region),
+            ("service", &service),
+        ] {
+            HeaderValue::try_from(value.as_str())
+                .with_context(|| format!("Error encoding AWS {label}"))?;
+        }
+        let session_token = session_token
+            .map(|session_token| {
+                let mut header_value = HeaderValue::try_from(session_token)
+                    .context("Error encoding AWS session token")?;
+                header_value.set_sensitive(true);
+                Ok::<_, anyhow::Error>(header_value)
+            })
+            .transpose()?;
+        Ok(Self {
+            access_key,
+            secret_key,
+            session_token,
+            region,
+            service,
+        })
+    }
+
+    /// Sign a request, returning a copy of it with the `Authorization` and
+    /// `X-Amz-*` headers added. Every header already on the request is
+    /// included in the signature.
+    pub fn sign(&self, request: &Request, now: DateTime<Utc>) -> Request {
+        let timestamp = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let date = now.format("%Y%m%d").to_string();
+        let scope =
+            format!("{date}/{}/{}/aws4_request", self.region, self.service);
+        let payload_hash = hex::encode(Sha256::digest(
+            request
+                .body
+                .as_ref()
+                .map(|body| body.bytes())
+                .unwrap_or_default(),
+        ));
+
+        // Headers that we add. These need to be signed too, so build the full
+        // set of headers before signing. Header values here are all ASCII,
+        // so these conversions can't fail
+        let mut new_headers = HeaderMap::new();
+        if !request.headers.contains_key(header::HOST) {
+            let host = match request.url.port() {
+                Some(port) => format!(
+                    "{}:{port}",
+                    request.url.host_str().unwrap_or_default()
+                ),
+                None => request.url.host_str().unwrap_or_default().to_owned(),
+            };
+            if let Ok(host) = host.try_into() {
+                new_headers.insert(header::HOST, host);
+            }
+        }
+        new_headers.insert(
+            HeaderName::from_static("x-amz-date"),
+            HeaderValue::from_str(&timestamp).expect("Timestamp is ASCII"),
+        );
+        // S3 requires the payload hash as a header. Other services don't
+        if self.service == "s3" {
+            new_headers.insert(
+                HeaderName::from_static("x-amz-content-sha256"),
+                HeaderValue::from_str(&payload_hash).expect("Hash is ASCII"),
+            );
+        }
+        if let Some(session_token) = &self.session_token {
+            new_headers.insert(
+                HeaderName::from_static("x-amz-security-token"),
+                session_token.clone(),
+            );
+        }
+        let mut signed = request.with_headers(new_headers);
+
+        let (canonical_request, signed_headers) =
+            self.canonical_request(&signed, &payload_hash);
+        let string_to_sign = format!(
+            "{ALGORITHM}\n{timestamp}\n{scope}\n{}",
+            hex::encode(Sha256::digest(canonical_request.as_bytes()))
+        );
+        let signing_key =
+            [date.as_str(), &self.region, &self.service, "aws4_request"]
+                .into_iter()
+                .fold(
+                    format!("AWS4{}", self.secret_key).into_bytes(),
+                    |key, data| hmac(&key, data.as_bytes()),
+                );
+        let signature =
+            hex::encode(hmac(&signing_key, string_to_sign.as_bytes()));
+
+        let mut authorization = HeaderValue::try_from(format!(
+            "{ALGORITHM} Credential={}/{scope}, \
+            SignedHeaders={signed_headers}, Signature={signature}",
+            self.access_key
+        ))
+        // Access key, region, and service were validated on creation
+        .expect("Authorization header is valid");
+        authorization.set_sensitive(true);
+        signed.headers.insert(header::AUTHORIZATION, authorization);
+        signed
+    }
+
+    /// Build the canonical form of a request, which is what actually gets
+    /// signed. Returns the canonical request and the list of signed headers.
+    fn canonical_request(
+        &self,
+        request: &Request,
+        payload_hash: &str,
+    ) -> (String, String) {
+        // S3 paths are encoded once, everything else is encoded twice. The
+        // URL path is already encoded once
+        let path = request.url.path();
+        let canonical_uri = if self.service == "s3" {
+            path.to_owned()
+        } else {
+            path.split('/').map(uri_encode).join("/")
+        };
+
+        let canonical_query = request
+            .url
+            .query_pairs()
+            .map(|(key, value)| (uri_encode(&key), uri_encode(&value)))
+            .sorted()
+            .map(|(key, value)| format!("{key}={value}"))
+            .join("&");
+
+        // Sign every header except the one we're about to generate. Header
+        // names in the map are already lowercase
+        let headers: Vec<(&str, String)> = request
+            .headers
+            .keys()
+            .filter(|name| *name != header::AUTHORIZATION)
+            .map(|name| {
+                let value = request
+                    .headers
+                    .get_all(name)
+                    .iter()
+                    .map(|value| {
+                        String::from_utf8_lossy(value.as_bytes())
+                            .split_whitespace()
+                            .join(" ")
+                    })
+                    .join(",");
+                (name.as_str(), value)
+            })
+            .sorted()
+            .collect();
+        let mut canonical_headers = String::new();
+        for (name, value) in &headers {
+            // Writes to a string are infallible
+            let _ = writeln!(canonical_headers, "{name}:{value}");
+        }
+        let signed_headers = headers.iter().map(|(name, _)| name).join(";");
+
+        let canonical_request = format!(
+            "{}\n{canonical_uri}\n{canonical_query}\n{canonical_headers}\n\
+            {signed_headers}\n{payload_hash}",
+            request.method
+        );
+        (canonical_request, signed_headers)
+    }
+}
+
+/// Don't print the secrets
+impl Debug for AwsSigV4Credentials {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AwsSigV4Credentials")
+            .field("access_key", &self.access_key)
+            .field("region", &self.region)
+            .field("service", &self.service)
+            .finish_non_exhaustive()
+    }
+}
+
+fn hmac(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts any key size");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        collection::Authentication,
+        config::Config,
+        db::CollectionDatabase,
+        http::{HttpEngine, RecipeOptions, RequestBuilder},
+        test_util::*,
+    };
+    use chrono::TimeZone;
+    use factori::create;
+    use mockito::Matcher;
+    use pretty_assertions::assert_eq;
+    use reqwest::StatusCode;
+    use rstest::rstest;
+
+    fn credentials(session_token: Option<&str>) -> AwsSigV4Credentials {
+        AwsSigV4Credentials::new(
+            "AKIDEXAMPLE".into(),
+            "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY".into(),
+            session_token.map(String::from),
+            "us-east-1".into(),
+            "service".into(),
+        )
+        .unwrap()
+    }
+
+    /// Test cases from the AWS SigV4 test suite
+    #[rstest]
+    #[case::vanilla(
+        "https://example.amazonaws.com/",
+        None,
+        "host;x-amz-date",
+        "5fa00fa31553b73ebf1942676e86291e8372ff2a2260956d9b8aae1d763fbf31"
+    )]
+    #[case::query_order(
+        "https://example.amazonaws.com/?Param2=value2&Param1=value1",
+        None,
+        "host;x-amz-date",
+        "b97d918cfa904a5beff61c982a1b6f458b799221646efd99d3219ec94cdf2500"
+    )]
+    #[case::session_token(
+        "https://example.amazonaws.com/",
+        Some("token"),
+        "host;x-amz-date;x-amz-security-token",
+        "74e6e438e6f4460297db2935c12e2d008496363238dc6820b0e964cae4f33903"
+    )]
+    fn test_sign(
+        #[case] url: &str,
+        #[case] session_token: Option<&str>,
+        #[case] expected_signed_headers: &str,
+        #[case] expected_signature: &str,
+    ) {
+        let request = create!(Request, url: url.parse().unwrap());
+        let now = Utc.with_ymd_and_hms(2015, 8, 30, 12, 36, 0).unwrap();
+        let signed = credentials(session_token).sign(&request, now);
+
+        assert_eq!(signed.id, request.id);
+        assert_eq!(signed.headers["host"], "example.amazonaws.com");
+        assert_eq!(signed.headers["x-amz-date"], "20150830T123600Z");
+        assert_eq!(
+            signed.headers[header::AUTHORIZATION].to_str().unwrap(),
+            format!(
+                "AWS4-HMAC-SHA256 \
+                Credential=AKIDEXAMPLE/20150830/us-east-1/service/aws4_request, \
+                SignedHeaders={expected_signed_headers}, \
+                Signature={expected_signature}"
+            )
+        );
+    }
+
+    #[test]
+    fn test_credentials_error() {
+        assert_err!(
+            AwsSigV4Credentials::new(
+                "access\nkey".into(),
+                "secret".into(),
+                None,
+                "us-east-1".into(),
+                "service".into(),
+            ),
+            "Error encoding AWS access key"
+        );
+    }
+
+    /// The request should be signed by the HTTP engine, and the recorded
+    /// request should include the signature
+    #[tokio::test]
+    async fn test_sign_on_send() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/data")
+            .match_header("x-amz-date", Matcher::Any)
+            .match_header(
+                "authorization",
+                Matcher::Regex(
+                    "^AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/\\d{8}/\
+                    us-east-1/service/aws4_request, \
+                    SignedHeaders=content-type;host;x-amz-date, \
+                    Signature=[0-9a-f]{64}$"
+                        .into(),
+                ),
+            )
+            .create_async()
+            .await;
+
+        let database = CollectionDatabase::testing();
+        let http_engine = HttpEngine::new(&Config::default(), database.clone());
+        let context = create!(TemplateContext, database: database);
+        let recipe = create!(
+            Recipe,
+            method: Some("POST".parse().unwrap()),
+            url: Some(format!("{}/data", server.url()).as_str().into()),
+            headers: [("content-type".into(), vec!["text/plain".into()])].into(),
+            body: Some("hello!".into()),
+            authentication: Some(Authentication::AwsSigV4 {
+                access_key: "AKIDEXAMPLE".into(),
+                secret_key: "secret".into(),
+                session_token: None,
+                region: "us-east-1".into(),
+                service: "service".into(),
+            }),
+        );
+        let request = RequestBuilder::new(recipe, RecipeOptions::default())
+            .build(&context)
+            .await
+            .unwrap();
+        // Signature isn't generated until the request is sent
+        assert!(!request.headers.contains_key(header::AUTHORIZATION));
+        let record = http_engine.send(request.into()).await.unwrap();
+
+        assert_eq!(record.response.status, StatusCode::OK);
+        assert!(record.request.headers.contains_key(header::AUTHORIZATION));
+        assert!(record.request.headers.contains_key("x-amz-date"));
+        mock.assert();
+    }
+}
@@ -10,6 +10,7 @@ use anyhow::{anyhow, Context};
 use derive_more::{Deref, Display, From};
 use mime::{Mime, APPLICATION, JSON};
 use reqwest::header::{self, HeaderValue};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::{borrow::Cow, ffi::OsStr, fmt::Debug, path::Path};
 
@@ -24,7 +25,7 @@ use std::{borrow::Cow, ffi::OsStr, fmt::Debug, path::Path};
 ///
 /// For the serialization string, obviously use serde. For the others, use
 /// the corresponding methods/associated functions.
-#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum ContentType {
     Json,
@@ -0,0 +1,721 @@
+//! HTTP Digest authentication, as defined by
+//! [RFC 7616](https://datatracker.ietf.org/doc/html/rfc7616). The request is
+//! first sent without credentials. The server responds with a 401 and a
+//! challenge in the `WWW-Authenticate` header, which we answer by resending
+//! the request with a computed `Authorization` header.
+
+use crate::{
+    http::{Request, Response},
+    util::uri_encode,
+};
+use anyhow::{anyhow, Context};
+use md5::Md5;
+use reqwest::header::{self, HeaderValue};
+use sha2::{Digest, Sha256};
+use std::fmt::{Debug, Write};
+use uuid::Uuid;
+
+/// Rendered credentials for digest authentication. These are attached to the
+/// built [Request], so the HTTP engine can answer the server's challenge.
+#[derive(Clone)]
+#[cfg_attr(test, derive(PartialEq))]
+pub struct DigestCredentials {
+    pub username: String,
+    pub password: String,
+}
+
+/// A parsed `WWW-Authenticate: Digest ...` challenge
+#[derive(Debug, PartialEq)]
+struct DigestChallenge {
+    realm: String,
+    nonce: String,
+    opaque: Option<String>,
+    algorithm: Algorithm,
+    qop: Option<Qop>,
+}
+
+/// Hash algorithm for the digest. The `-sess` variants hash the client nonce
+/// into the credentials
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Algorithm {
+    Md5,
+    Md5Sess,
+    Sha256,
+    Sha256Sess,
+}
+
+/// Quality of protection. `auth-int` includes the body in the digest
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Qop {
+    Auth,
+    AuthInt,
+}
+
+impl DigestCredentials {
+    /// Compute the `Authorization` header that answers the challenge in a 401
+    /// response. If the server offers multiple challenges, the first one we
+    /// support is used. Challenges we can't handle are skipped, and only
+    /// reported if there's nothing else to use.
+    pub fn authorize(
+        &self,
+        request: &Request,
+        response: &Response,
+    ) -> anyhow::Result<HeaderValue> {
+        let mut error = None;
+        let challenge = response
+            .headers
+            .get_all(header::WWW_AUTHENTICATE)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(split_challenges)
+            .find_map(|challenge| match DigestChallenge::parse(challenge) {
+                Ok(challenge) => challenge,
+                Err(err) => {
+                    error.get_or_insert(err);
+                    None
+                }
+            })
+            .ok_or_else(|| {
+                let message = "Response does not contain a supported \
+                    `WWW-Authenticate: Digest` challenge";
+                match error {
+                    Some(error) => error.context(message),
+                    None => anyhow!(message),
+                }
+            })?;
+        let cnonce = Uuid::new_v4().simple().to_string();
+        let mut header_value: HeaderValue = self
+            .authorization(&challenge, request, &cnonce)
+            .try_into()
+            .context("Error encoding digest authorization")?;
+        header_value.set_sensitive(true);
+        Ok(header_value)
+    }
+
+    /// Build the `Authorization` header value for a challenge
+    fn authorization(
+        &self,
+        challenge: &DigestChallenge,
+        request: &Request,
+        cnonce: &str,
+    ) -> String {
+        let algorithm = challenge.algorithm;
+        // Only one request is sent per nonce, so the count is always 1
+        let nc = "00000001";
+        // The URI is the request target, i.e. path+query
+        let uri = match request.url.query() {
+            Some(query) => format!("{}?{query}", request.url.path()),
+            None => request.url.path().to_owned(),
+        };
+
+        let mut ha1 = algorithm.hash(
+            format!("{}:{}:{}", self.username, challenge.realm, self.password)
+                .as_bytes(),
+        );
+        if algorithm.is_session() {
+            ha1 = algorithm
+                .hash(format!("{ha1}:{}:{cnonce}", challenge.nonce).as_bytes());
+        }
+        let ha2 = match challenge.qop {
+            Some(Qop::AuthInt) => {
+                let body = request
+                    .body
+                    .as_ref()
+                    .map(|body| body.bytes())
+                    .unwrap_or_default();
+                algorithm.hash(
+                    format!(
+                        "{}:{uri}:{}",
+                        request.method,
+                        algorithm.hash(body)
+                    )
+                    .as_bytes(),
+                )
+            }
+            Some(Qop::Auth) | None => {
+                algorithm.hash(format!("{}:{uri}", request.method).as_bytes())
+            }
+        };
+        let response = match challenge.qop {
+            Some(qop) => algorithm.hash(
+                format!(
+                    "{ha1}:{}:{nc}:{cnonce}:{}:{ha2}",
+                    challenge.nonce,
+                    qop.as_str()
+                )
+                .as_bytes(),
+            ),
+            // Legacy RFC 2069 digest
+            None => algorithm
+                .hash(format!("{ha1}:{}:{ha2}", challenge.nonce).as_bytes()),
+        };
+
+        // Header values can only contain ASCII, so non-ASCII usernames are
+        // sent in the extended format from RFC 5987 instead
+        let username = if self.username.is_ascii() {
+            format!("username={}", quote(&self.username))
+        } else {
+            format!("username*=UTF-8''{}", uri_encode(&self.username))
+        };
+        // Writes to a string are infallible
+        let mut authorization = format!(
+            "Digest {username}, realm={}, nonce={}, uri=\"{uri}\", \
+            algorithm={}, response=\"{response}\"",
+            quote(&challenge.realm),
+            quote(&challenge.nonce),
+            algorithm.as_str(),
+        );
+        if let Some(qop) = challenge.qop {
+            let _ = write!(
+                authorization,
+                ", qop={}, nc={nc}, cnonce=\"{cnonce}\"",
+                qop.as_str()
+            );
+        }
+        if let Some(opaque) = &challenge.opaque {
+            let _ = write!(authorization, ", opaque={}", quote(opaque));
+        }
+        authorization
+    }
+}
+
+/// Don't print the password
+impl Debug for DigestCredentials {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DigestCredentials")
+            .field("username", &self.username)
+            .finish_non_exhaustive()
+    }
+}
+
+impl DigestChallenge {
+    /// Parse a `WWW-Authenticate` header value. Returns `Ok(None)` if the
+    /// challenge is for a scheme other than digest. Returns an error if it's
+    /// a digest challenge that we can't handle.
+    fn parse(header_value: &str) -> anyhow::Result<Option<Self>> {
+        let Some(params) = header_value
+            .trim_start()
+            .strip_prefix("Digest ")
+            .or_else(|| header_value.trim_start().strip_prefix("digest "))
+        else {
+            return Ok(None);
+        };
+
+        let mut realm = None;
+        let mut nonce = None;
+        let mut opaque = None;
+        let mut algorithm = Algorithm::Md5;
+        let mut qop = None;
+        for (key, value) in parse_params(params)? {
+            match key.to_ascii_lowercase().as_str() {
+                "realm" => realm = Some(value),
+                "nonce" => nonce = Some(value),
+                "opaque" => opaque = Some(value),
+                "algorithm" => algorithm = value.parse()?,
+                "qop" => {
+                    // Server can offer multiple options. Prefer plain auth
+                    let options: Vec<&str> =
+                        value.split(',').map(str::trim).collect();
+                    qop = if options.contains(&"auth") {
+                        Some(Qop::Auth)
+                    } else if options.contains(&"auth-int") {
+                        Some(Qop::AuthInt)
+                    } else {
+                        return Err(anyhow!(
+                            "Unsupported digest qop `{value}`"
+                        ));
+                    };
+                }
+                // Ignore anything else (stale, domain, charset, etc.)
+                _ => {}
+            }
+        }
+
+        Ok(Some(Self {
+            realm: realm
+                .ok_or_else(|| anyhow!("Digest challenge missing `realm`"))?,
+            nonce: nonce
+                .ok_or_else(|| anyhow!("Digest challenge missing `nonce`"))?,
+            opaque,
+            algorithm,
+            qop,
+        }))
+    }
+}
+
+/// Split a `WWW-Authenticate` header value into individual challenges. Commas
+/// separate both challenges and the params within a challenge, so a new
+/// challenge starts at any comma-separated item that begins with a scheme name,
+/// i.e. a token that isn't followed by `=`.
+fn split_challenges(header_value: &str) -> Vec<&str> {
+    let is_scheme = |item: &str| {
+        let item = item.trim_start();
+        let token_end = item
+            .find(|c: char| c.is_whitespace() || c == '=' || c == ',')
+            .unwrap_or(item.len());
+        token_end > 0 && !item[token_end..].trim_start().starts_with('=')
+    };
+
+    let mut challenges = Vec::new();
+    let mut start = 0;
+    let mut in_quotes = false;
+    let mut escaped = false;
+    for (i, c) in header_value.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_quotes => escaped = true,
+            '"' => in_quotes = !in_quotes,
+            ',' if !in_quotes && is_scheme(&header_value[i + 1..]) => {
+                challenges.push(header_value[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    challenges.push(header_value[start..].trim());
+    challenges.retain(|challenge| !challenge.is_empty());
+    challenges
+}
+
+/// Wrap a value in quotes, escaping any quotes and backslashes within it. This
+/// is the inverse of the quoted-string parsing in [parse_params]
+fn quote(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        if c == '"' || c == '\\' {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Parse comma-separated `key=value` params. Values can be quoted, in which
+/// case they may contain commas and escaped characters.
+fn parse_params(input: &str) -> anyhow::Result<Vec<(String, String)>> {
+    let mut params = Vec::new();
+    let mut chars = input.chars().peekable();
+    loop {
+        // Skip separators between params
+        while chars.next_if(|c| c.is_whitespace() || *c == ',').is_some() {}
+        if chars.peek().is_none() {
+            break;
+        }
+
+        let key: String = chars
+            .by_ref()
+            .take_while(|c| *c != '=')
+            .collect::<String>()
+            .trim()
+            .to_owned();
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        let mut value = String::new();
+        if chars.next_if_eq(&'"').is_some() {
+            loop {
+                match chars.next() {
+                    Some('"') => break,
+                    Some('\\') => value.extend(chars.next()),
+                    Some(c) => value.push(c),
+                    None => {
+                        return Err(anyhow!(
+                            "Unterminated quoted string for `{key}` in \
+                            digest challenge"
+                        ))
+                    }
+                }
+            }
+        } else {
+            while let Some(c) = chars.next_if(|c| *c != ',') {
+                value.push(c);
+            }
+            value = value.trim().to_owned();
+        }
+        params.push((key, value));
+    }
+    Ok(params)
+}
+
+impl Algorithm {
+    /// Hash some bytes, and return the digest as a hex string
+    fn hash(self, data: &[u8]) -> String {
+        match self {
+            Self::Md5 | Self::Md5Sess => hex::encode(Md5::digest(data)),
+            Self::Sha256 | Self::Sha256Sess => {
+                hex::encode(Sha256::digest(data))
+            }
+        }
+    }
+
+    fn is_session(self) -> bool {
+        matches!(self, Self::Md5Sess | Self::Sha256Sess)
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Md5 => "MD5",
+            Self::Md5Sess => "MD5-sess",
+            Self::Sha256 => "SHA-256",
+            Self::Sha256Sess => "SHA-256-sess",
+        }
+    }
+}
+
+impl std::str::FromStr for Algorithm {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_uppercase().as_str() {
+            "MD5" => Ok(Self::Md5),
+            "MD5-SESS" => Ok(Self::Md5Sess),
+            "SHA-256" => Ok(Self::Sha256),
+            "SHA-256-SESS" => Ok(Self::Sha256Sess),
+            _ => Err(anyhow!("Unsupported digest algorithm `{s}`")),
+        }
+    }
+}
+
+impl Qop {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Auth => "auth",
+            Self::AuthInt => "auth-int",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        collection::Authentication,
+        config::Config,
+        db::CollectionDatabase,
+        http::{HttpEngine, RecipeOptions, RequestBuilder},
+        test_util::*,
+    };
+    use factori::create;
+    use mockito::Matcher;
+    use pretty_assertions::assert_eq;
+    use reqwest::StatusCode;
+    use rstest::rstest;
+
+    /// Test the examples from RFC 7616 section 3.9.1
+    #[rstest]
+    #[case::md5(Algorithm::Md5, "8ca523f5e9506fed4657c9700eebdbec")]
+    #[case::sha256(
+        Algorithm::Sha256,
+        "753927fa0e85d155564e2e272a28d1802ca10daf4496794697cf8db5856cb6c1"
+    )]
+    fn test_authorization(
+        #[case] algorithm: Algorithm,
+        #[case] expected_response: &str,
+    ) {
+        let credentials = DigestCredentials {
+            username: "Mufasa".into(),
+            password: "Circle of Life".into(),
+        };
+        let challenge = DigestChallenge {
+            realm: "http-auth@example.org".into(),
+            nonce: "7ypf/xlj9XXwfDPEoM4URrv/xwf94BcCAzFZH4GiTo0v".into(),
+            opaque: Some("FQhe/qaU925kfnzjCev0ciny7QMkPqMAFRtzCUYo5tdS".into()),
+            algorithm,
+            qop: Some(Qop::Auth),
+        };
+        let request = create!(
+            Request,
+            url: "http://www.example.org/dir/index.html".parse().unwrap(),
+        );
+        let cnonce = "f2/wE4q74E6zIJEtWaHKaf5wv/H5QzzpXusqGemxURZJ";
+        assert_eq!(
+            credentials.authorization(&challenge, &request, cnonce),
+            format!(
+                "Digest username=\"Mufasa\", realm=\"http-auth@example.org\", \
+                nonce=\"7ypf/xlj9XXwfDPEoM4URrv/xwf94BcCAzFZH4GiTo0v\", \
+                uri=\"/dir/index.html\", algorithm={}, \
+                response=\"{expected_response}\", qop=auth, nc=00000001, \
+                cnonce=\"{cnonce}\", \
+                opaque=\"FQhe/qaU925kfnzjCev0ciny7QMkPqMAFRtzCUYo5tdS\"",
+                algorithm.as_str()
+            )
+        );
+    }
+
+    /// Quotes and backslashes in quoted values must be escaped, and non-ASCII
+    /// usernames use the extended `username*` param
+    #[rstest]
+    #[case::escaped(
+        "a\"b\\c",
+        "re\"alm",
+        "Digest username=\"a\\\"b\\\\c\", realm=\"re\\\"alm\", nonce=\"abc\""
+    )]
+    #[case::non_ascii(
+        "Jäsøn",
+        "test",
+        "Digest username*=UTF-8''J%C3%A4s%C3%B8n, realm=\"test\", nonce=\"abc\""
+    )]
+    fn test_authorization_escape(
+        #[case] username: &str,
+        #[case] realm: &str,
+        #[case] expected_prefix: &str,
+    ) {
+        let credentials = DigestCredentials {
+            username: username.into(),
+            password: "hunter2".into(),
+        };
+        let challenge = DigestChallenge {
+            realm: realm.into(),
+            nonce: "abc".into(),
+            opaque: None,
+            algorithm: Algorithm::Md5,
+            qop: None,
+        };
+        let request = create!(Request);
+        let authorization =
+            credentials.authorization(&challenge, &request, "cnonce");
+        assert!(
+            authorization.starts_with(expected_prefix),
+            "{authorization}"
+        );
+        // Must be encodable as a header
+        HeaderValue::try_from(authorization).unwrap();
+    }
+
+    #[rstest]
+    #[case::basic("Basic realm=\"test\"", None)]
+    #[case::minimal(
+        "Digest realm=\"test\", nonce=\"abc\"",
+        Some(DigestChallenge {
+            realm: "test".into(),
+            nonce: "abc".into(),
+            opaque: None,
+            algorithm: Algorithm::Md5,
+            qop: None,
+        })
+    )]
+    #[case::full(
+        "Digest realm=\"a, \\\"b\\\"\", qop=\"auth-int,auth\", \
+        algorithm=SHA-256-sess, nonce=\"abc\", opaque=\"xyz\", stale=FALSE",
+        Some(DigestChallenge {
+            realm: "a, \"b\"".into(),
+            nonce: "abc".into(),
+            opaque: Some("xyz".into()),
+            algorithm: Algorithm::Sha256Sess,
+            qop: Some(Qop::Auth),
+        })
+    )]
+    #[case::auth_int(
+        "Digest realm=\"test\", nonce=\"abc\", qop=\"auth-int\"",
+        Some(DigestChallenge {
+            realm: "test".into(),
+            nonce: "abc".into(),
+            opaque: None,
+            algorithm: Algorithm::Md5,
+            qop: Some(Qop::AuthInt),
+        })
+    )]
+    fn test_parse_challenge(
+        #[case] header_value: &str,
+        #[case] expected: Option<DigestChallenge>,
+    ) {
+        assert_eq!(DigestChallenge::parse(header_value).unwrap(), expected);
+    }
+
+    #[rstest]
+    #[case::single("Digest realm=\"test\", nonce=\"abc\"", &[
+        "Digest realm=\"test\", nonce=\"abc\"",
+    ])]
+    #[case::multiple(
+        "Basic realm=\"a, Digest b\", Digest realm=\"test\", nonce=\"abc\", \
+        Negotiate, Bearer abc==",
+        &[
+            "Basic realm=\"a, Digest b\"",
+            "Digest realm=\"test\", nonce=\"abc\"",
+            "Negotiate",
+            "Bearer abc==",
+        ],
+    )]
+    #[case::spaced_params(
+        "Digest realm = \"test\" , nonce=abc",
+        &["Digest realm = \"test\" , nonce=abc"],
+    )]
+    fn test_split_challenges(
+        #[case] header_value: &str,
+        #[case] expected: &[&str],
+    ) {
+        assert_eq!(split_challenges(header_value), expected);
+    }
+
+    /// Unsupported challenges should be skipped in favor of a later one we
+    /// can handle, whether they're in separate headers or the same one
+    #[rstest]
+    #[case::separate_headers(&[
+        "Digest realm=\"test\", nonce=\"abc\", algorithm=SHA-512-256",
+        "Digest realm=\"test\", nonce=\"abc\", algorithm=SHA-256",
+    ])]
+    #[case::same_header(&[
+        "Digest realm=\"test\", nonce=\"abc\", algorithm=SHA-512-256, \
+        Digest realm=\"test\", nonce=\"abc\", algorithm=SHA-256",
+    ])]
+    fn test_authorize_skip_unsupported(#[case] challenges: &[&str]) {
+        let credentials = DigestCredentials {
+            username: "user".into(),
+            password: "hunter2".into(),
+        };
+        let response = create!(
+            Response,
+            status: StatusCode::UNAUTHORIZED,
+            headers: header_map(
+                challenges.iter().map(|value| ("www-authenticate", *value)),
+            ),
+        );
+        let authorization =
+            credentials.authorize(&create!(Request), &response).unwrap();
+        assert!(authorization
+            .to_str()
+            .unwrap()
+            .contains("algorithm=SHA-256,"));
+    }
+
+    /// If no challenge is usable, the first error should be reported
+    #[test]
+    fn test_authorize_unsupported() {
+        let credentials = DigestCredentials {
+            username: "user".into(),
+            password: "hunter2".into(),
+        };
+        let response = create!(
+            Response,
+            status: StatusCode::UNAUTHORIZED,
+            headers: header_map([(
+                "www-authenticate",
+                "Basic realm=\"test\", \
+                Digest realm=\"test\", nonce=\"abc\", algorithm=SHA-512-256",
+            )]),
+        );
+        let error = credentials
+            .authorize(&create!(Request), &response)
+            .unwrap_err();
+        assert_eq!(
+            format!("{error:#}"),
+            "Response does not contain a supported `WWW-Authenticate: Digest` \
+            challenge: Unsupported digest algorithm `SHA-512-256`"
+        );
+    }
+
+    #[rstest]
+    #[case::missing_nonce("Digest realm=\"test\"", "missing `nonce`")]
+    #[case::algorithm(
+        "Digest realm=\"test\", nonce=\"abc\", algorithm=SHA-512",
+        "Unsupported digest algorithm `SHA-512`"
+    )]
+    #[case::qop(
+        "Digest realm=\"test\", nonce=\"abc\", qop=\"other\"",
+        "Unsupported digest qop `other`"
+    )]
+    #[case::unterminated(
+        "Digest realm=\"test",
+        "Unterminated quoted string for `realm`"
+    )]
+    fn test_parse_challenge_error(
+        #[case] header_value: &str,
+        #[case] expected_error: &str,
+    ) {
+        assert_err!(DigestChallenge::parse(header_value), expected_error);
+    }
+
+    /// Test the full challenge/response exchange. Both legs should be
+    /// included in the record
+    #[tokio::test]
+    async fn test_digest_exchange() {
+        let mut server = mockito::Server::new_async().await;
+        let challenge_mock = server
+            .mock("GET", "/data")
+            .match_header("authorization", Matcher::Missing)
+            .with_status(401)
+            .with_header(
+                "WWW-Authenticate",
+                "Digest realm=\"test\", nonce=\"abc\", qop=\"auth\"",
+            )
+            .create_async()
+            .await;
+        let accepted_mock = server
+            .mock("GET", "/data")
+            .match_header(
+                "authorization",
+                Matcher::Regex(
+                    "^Digest username=\"user\", realm=\"test\", \
+                    nonce=\"abc\", uri=\"/data\", algorithm=MD5, "
+                        .into(),
+                ),
+            )
+            .with_body("hello!")
+            .create_async()
+            .await;
+
+        let database = CollectionDatabase::testing();
+        let http_engine = HttpEngine::new(&Config::default(), database.clone());
+        let context = create!(TemplateContext, database: database);
+        let recipe = create!(
+            Recipe,
+            url: Some(format!("{}/data", server.url()).as_str().into()),
+            authentication: Some(Authentication::Digest {
+                username: "user".into(),
+                password: "hunter2".into(),
+            }),
+        );
+        let request = RequestBuilder::new(recipe, RecipeOptions::default())
+            .build(&context)
+            .await
+            .unwrap();
+        assert!(!request.headers.contains_key(header::AUTHORIZATION));
+        let record = http_engine.send(request.into()).await.unwrap();
+
+        assert_eq!(record.response.status, StatusCode::OK);
+        assert!(record.request.headers.contains_key(header::AUTHORIZATION));
+        let challenge = record.challenge.unwrap();
+        assert_eq!(challenge.response.status, StatusCode::UNAUTHORIZED);
+        assert!(!challenge
+            .request
+            .headers
+            .contains_key(header::AUTHORIZATION));
+        challenge_mock.assert();
+        accepted_mock.assert();
+    }
+
+    /// If the 401 doesn't include a challenge we can answer, the 401 should
+    /// be returned as-is
+    #[tokio::test]
+    async fn test_digest_no_challenge() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("GET", "/data")
+            .with_status(401)
+            .with_header("WWW-Authenticate", "Basic realm=\"test\"")
+            .expect(1)
+            .create_async()
+            .await;
+
+        let database = CollectionDatabase::testing();
+        let http_engine = HttpEngine::new(&Config::default(), database.clone());
+        let context = create!(TemplateContext, database: database);
+        let recipe = create!(
+            Recipe,
+            url: Some(format!("{}/data", server.url()).as_str().into()),
+            authentication: Some(Authentication::Digest {
+                username: "user".into(),
+                password: "hunter2".into(),
+            }),
+        );
+        let request = RequestBuilder::new(recipe, RecipeOptions::default())
+            .build(&context)
+            .await
+            .unwrap();
+        let record = http_engine.send(request.into()).await.unwrap();
+
+        assert_eq!(record.response.status, StatusCode::UNAUTHORIZED);
+        assert!(record.challenge.is_none());
+        mock.assert();
+    }
+}
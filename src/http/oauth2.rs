@@ -0,0 +1,659 @@
+//! OAuth 2.0 token retrieval and caching. Tokens are fetched from the token
+//! endpoint during the request build, then cached in the database per
+//! profile. Cached tokens are refreshed when they expire, or when the server
+//! rejects them with a 401.
+
+use crate::{
+    collection::ProfileId,
+    http::{HttpEngine, RequestBuilder},
+    template::{Template, TemplateContext, TriggeredRequestError},
+};
+use anyhow::{anyhow, Context};
+use chrono::{DateTime, Duration, Utc};
+use futures::future;
+use indexmap::IndexMap;
+use reqwest::header::{self, HeaderValue};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{fmt::Debug, ops::Deref};
+use tracing::{info, warn};
+use url::Url;
+
+/// Form parameters that contain secrets. These are only included in the cache
+/// key as a hash
+const SECRET_PARAMS: &[&str] = &["client_secret", "password", "refresh_token"];
+
+/// Treat tokens as expired slightly before they actually are, so they don't
+/// expire while the request is in flight
+const EXPIRATION_MARGIN_SECONDS: i64 = 10;
+
+/// A fully rendered OAuth 2.0 grant, which can be exchanged for an access
+/// token at the token endpoint. This is attached to the built [Request] so the
+/// HTTP engine can fetch a new token if the server rejects the current one.
+#[derive(Clone)]
+#[cfg_attr(test, derive(PartialEq))]
+pub struct OAuth2Grant {
+    pub token_url: Url,
+    /// Form parameters for the token request, including `grant_type`
+    pub params: IndexMap<&'static str, String>,
+}
+
+/// An access token, as returned by the token endpoint. This is what gets
+/// cached in the database.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(test, derive(PartialEq))]
+pub struct OAuth2Token {
+    pub access_token: String,
+    /// Used to get a new access token once this one expires. Not all servers
+    /// issue refresh tokens
+    pub refresh_token: Option<String>,
+    /// `None` if the server didn't tell us when the token expires. In that
+    /// case, it will be reused until the server rejects it.
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// Successful response from the token endpoint. Defined by
+/// [RFC 6749](https://datatracker.ietf.org/doc/html/rfc6749#section-5.1)
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    refresh_token: Option<String>,
+    /// Lifetime of the token, in seconds
+    expires_in: Option<i64>,
+}
+
+impl OAuth2Grant {
+    /// Key that identifies this grant in the token cache. Secrets are only
+    /// included as a hash, so we don't store them unnecessarily, but changing
+    /// credentials still invalidates the cached token.
+    pub fn cache_key(&self) -> String {
+        let encode = |secret: bool| {
+            url::form_urlencoded::Serializer::new(String::new())
+                .extend_pairs(self.params.iter().filter(|(param, _)| {
+                    SECRET_PARAMS.contains(param) == secret
+                }))
+                .finish()
+        };
+        let secrets_hash = hex::encode(Sha256::digest(encode(true)));
+        format!("{} {} {secrets_hash}", self.token_url, encode(false))
+    }
+
+    /// Get a grant that exchanges a refresh token for a new access token,
+    /// using the same client as this grant
+    fn refresh(&self, refresh_token: String) -> Self {
+        let mut params = IndexMap::new();
+        params.insert("grant_type", "refresh_token".to_owned());
+        params.insert("refresh_token", refresh_token);
+        for param in ["client_id", "client_secret", "scope"] {
+            if let Some(value) = self.params.get(param) {
+                params.insert(param, value.clone());
+            }
+        }
+        Self {
+            token_url: self.token_url.clone(),
+            params,
+        }
+    }
+}
+
+/// Don't print params, because they contain secrets
+impl Debug for OAuth2Grant {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OAuth2Grant")
+            .field("token_url", &self.token_url.as_str())
+            .field("grant_type", &self.params.get("grant_type"))
+            .finish_non_exhaustive()
+    }
+}
+
+impl OAuth2Token {
+    /// Is this token expired, or about to be?
+    pub fn is_expired(&self) -> bool {
+        let margin = Duration::try_seconds(EXPIRATION_MARGIN_SECONDS)
+            .unwrap_or_default();
+        self.expires_at
+            .is_some_and(|expires_at| expires_at <= Utc::now() + margin)
+    }
+
+    /// Get the value of the `Authorization` header for this token
+    pub fn header_value(&self) -> anyhow::Result<HeaderValue> {
+        let mut header_value: HeaderValue =
+            format!("Bearer {}", self.access_token)
+                .try_into()
+                .context("Error encoding OAuth 2.0 access token")?;
+        header_value.set_sensitive(true);
+        Ok(header_value)
+    }
+}
+
+impl From<TokenResponse> for OAuth2Token {
+    fn from(response: TokenResponse) -> Self {
+        Self {
+            access_token: response.access_token,
+            refresh_token: response.refresh_token,
+            expires_at: response
+                .expires_in
+                .and_then(Duration::try_seconds)
+                .map(|expires_in| Utc::now() + expires_in),
+        }
+    }
+}
+
+impl RequestBuilder {
+    /// Render an OAuth 2.0 grant, then get an access token for it. A cached
+    /// token will be used if available, otherwise a new one is fetched from
+    /// the token endpoint. Fetching requires an HTTP engine in the template
+    /// context.
+    ///
+    /// Returns the value for the `Authorization` header, as well as the
+    /// rendered grant.
+    pub(super) async fn render_oauth2(
+        &self,
+        template_context: &TemplateContext,
+        grant_type: &'static str,
+        token_url: &Template,
+        params: &[(&'static str, Option<&Template>)],
+    ) -> anyhow::Result<(HeaderValue, OAuth2Grant)> {
+        let token_url = async {
+            let token_url = token_url
+                .render(template_context)
+                .await
+                .context("Error rendering OAuth 2.0 token URL")?;
+            token_url
+                .parse::<Url>()
+                .with_context(|| format!("Invalid token URL: `{token_url}`"))
+        };
+        let params = future::try_join_all(params.iter().filter_map(
+            |(param, template)| {
+                let template = (*template)?;
+                Some(async move {
+                    let value =
+                        template.render(template_context).await.context(
+                            format!("Error rendering OAuth 2.0 `{param}`"),
+                        )?;
+                    Ok::<_, anyhow::Error>((*param, value))
+                })
+            },
+        ));
+        let (token_url, params) = tokio::try_join!(token_url, params)?;
+
+        let mut grant = OAuth2Grant {
+            token_url,
+            params: IndexMap::new(),
+        };
+        grant.params.insert("grant_type", grant_type.to_owned());
+        grant.params.extend(params);
+
+        let profile_id = template_context.selected_profile.as_ref();
+        let cached = template_context
+            .database
+            .get_oauth2_token(profile_id, &grant.cache_key())?;
+        let token = match cached {
+            Some(token) if !token.is_expired() => token,
+            cached => {
+                let http_engine = template_context
+                    .http_engine
+                    .as_ref()
+                    .ok_or(TriggeredRequestError::NotAllowed)
+                    .context("Error fetching OAuth 2.0 token")?;
+                http_engine
+                    .fetch_oauth2_token(
+                        profile_id,
+                        &grant,
+                        cached.and_then(|token| token.refresh_token),
+                    )
+                    .await?
+            }
+        };
+
+        Ok((token.header_value()?, grant))
+    }
+}
+
+impl HttpEngine {
+    /// Fetch a new access token from the token endpoint, and cache it in the
+    /// database. If a refresh token is given, it will be tried first. If the
+    /// refresh fails, we'll fall back to the original grant.
+    pub(super) async fn fetch_oauth2_token(
+        &self,
+        profile_id: Option<&ProfileId>,
+        grant: &OAuth2Grant,
+        refresh_token: Option<String>,
+    ) -> anyhow::Result<OAuth2Token> {
+        let token = match refresh_token {
+            Some(refresh_token) => {
+                let refresh_grant = grant.refresh(refresh_token.clone());
+                match self.request_oauth2_token(&refresh_grant).await {
+                    Ok(mut token) => {
+                        // Servers aren't required to issue a new refresh
+                        // token, in which case we keep using the old one
+                        token.refresh_token.get_or_insert(refresh_token);
+                        token
+                    }
+                    Err(error) => {
+                        warn!(
+                            error = error.deref(),
+                            "Error refreshing OAuth 2.0 token, \
+                            requesting a new one"
+                        );
+                        self.request_oauth2_token(grant).await?
+                    }
+                }
+            }
+            None => self.request_oauth2_token(grant).await?,
+        };
+
+        self.database.set_oauth2_token(
+            profile_id,
+            &grant.cache_key(),
+            &token,
+        )?;
+        Ok(token)
+    }
+
+    /// Send a single request to the token endpoint
+    async fn request_oauth2_token(
+        &self,
+        grant: &OAuth2Grant,
+    ) -> anyhow::Result<OAuth2Token> {
+        let url = &grant.token_url;
+        info!(%url, ?grant, "Fetching OAuth 2.0 token");
+        let response = self
+            .default_client(url)
+            .post(url.clone())
+            .header(header::ACCEPT, "application/json")
+            .form(&grant.params)
+            .send()
+            .await
+            .with_context(|| {
+                format!("Error fetching OAuth 2.0 token from `{url}`")
+            })?;
+        let status = response.status();
+        let body = response
+            .bytes()
+            .await
+            .context("Error loading OAuth 2.0 token response")?;
+        if !status.is_success() {
+            return Err(anyhow!(
+                "Token endpoint `{url}` returned status {status}: {}",
+                String::from_utf8_lossy(&body)
+            ));
+        }
+        let response: TokenResponse = serde_json::from_slice(&body)
+            .context("Error parsing OAuth 2.0 token response")?;
+        Ok(response.into())
+    }
+
+    /// If the server rejected a request that was authenticated with OAuth 2.0,
+    /// the token may have been revoked or expired early. Fetch a new token
+    /// and return the new `Authorization` header, so the request can be
+    /// retried.
+    pub(super) async fn reauthenticate_oauth2(
+        &self,
+        profile_id: Option<&ProfileId>,
+        grant: &OAuth2Grant,
+    ) -> anyhow::Result<HeaderValue> {
+        info!("Request was rejected with 401, refreshing OAuth 2.0 token");
+        let refresh_token = self
+            .database
+            .get_oauth2_token(profile_id, &grant.cache_key())
+            .ok()
+            .flatten()
+            .and_then(|token| token.refresh_token);
+        self.fetch_oauth2_token(profile_id, grant, refresh_token)
+            .await?
+            .header_value()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        collection::Authentication, config::Config, db::CollectionDatabase,
+        http::RecipeOptions, test_util::*,
+    };
+    use factori::create;
+    use mockito::Matcher;
+    use pretty_assertions::assert_eq;
+    use reqwest::StatusCode;
+    use rstest::rstest;
+
+    /// Build a recipe with the given authentication, and return the
+    /// `Authorization` header from the built request
+    async fn build_authorization(
+        authentication: Authentication,
+        context: &TemplateContext,
+    ) -> anyhow::Result<String> {
+        let recipe = create!(Recipe, authentication: Some(authentication));
+        let request = RequestBuilder::new(recipe, RecipeOptions::default())
+            .build(context)
+            .await?;
+        Ok(request.headers[header::AUTHORIZATION]
+            .to_str()
+            .unwrap()
+            .to_owned())
+    }
+
+    /// Each grant type should send the correct params to the token endpoint,
+    /// and the token should be cached for subsequent requests
+    #[rstest]
+    #[case::client_credentials(
+        Authentication::Oauth2ClientCredentials {
+            token_url: "{{host}}/token".into(),
+            client_id: "client".into(),
+            client_secret: "secret".into(),
+            scope: Some("read".into()),
+        },
+        &[
+            ("grant_type", "client_credentials"),
+            ("client_id", "client"),
+            ("client_secret", "secret"),
+            ("scope", "read"),
+        ],
+    )]
+    #[case::password(
+        Authentication::Oauth2Password {
+            token_url: "{{host}}/token".into(),
+            client_id: "client".into(),
+            client_secret: None,
+            username: "user".into(),
+            password: "hunter2".into(),
+            scope: None,
+        },
+        &[
+            ("grant_type", "password"),
+            ("client_id", "client"),
+            ("username", "user"),
+            ("password", "hunter2"),
+        ],
+    )]
+    #[case::refresh_token(
+        Authentication::Oauth2RefreshToken {
+            token_url: "{{host}}/token".into(),
+            client_id: "client".into(),
+            client_secret: Some("secret".into()),
+            refresh_token: "refresh".into(),
+            scope: None,
+        },
+        &[
+            ("grant_type", "refresh_token"),
+            ("client_id", "client"),
+            ("client_secret", "secret"),
+            ("refresh_token", "refresh"),
+        ],
+    )]
+    #[tokio::test]
+    async fn test_fetch_token(
+        #[case] authentication: Authentication,
+        #[case] expected_params: &[(&str, &str)],
+    ) {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/token")
+            .match_body(Matcher::AllOf(
+                expected_params
+                    .iter()
+                    .map(|(param, value)| {
+                        Matcher::UrlEncoded(
+                            (*param).to_owned(),
+                            (*value).to_owned(),
+                        )
+                    })
+                    .collect(),
+            ))
+            .with_body(r#"{"access_token":"token1","expires_in":3600}"#)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let database = CollectionDatabase::testing();
+        let profile = create!(
+            Profile,
+            data: [("host".into(), server.url().as_str().into())].into(),
+        );
+        let profile_id = profile.id.clone();
+        let context = create!(
+            TemplateContext,
+            collection: create!(
+                Collection,
+                profiles: [(profile_id.clone(), profile)].into(),
+            ),
+            selected_profile: Some(profile_id),
+            http_engine: Some(HttpEngine::new(
+                &Config::default(),
+                database.clone(),
+            )),
+            database: database,
+        );
+
+        // Second build should hit the cache
+        for _ in 0..2 {
+            assert_eq!(
+                build_authorization(authentication.clone(), &context)
+                    .await
+                    .unwrap(),
+                "Bearer token1"
+            );
+        }
+        mock.assert();
+    }
+
+    /// Changing a secret should change the cache key, without the secret
+    /// itself appearing in it
+    #[test]
+    fn test_cache_key() {
+        let key =
+            client_credentials_grant("http://localhost", "hunter2").cache_key();
+        assert!(key.starts_with(
+            "http://localhost/token \
+            grant_type=client_credentials&client_id=client "
+        ));
+        assert!(!key.contains("hunter2"), "{key}");
+        assert_ne!(
+            key,
+            client_credentials_grant("http://localhost", "hunter3").cache_key()
+        );
+    }
+
+    /// Build the grant for a client credentials flow with client ID `client`
+    fn client_credentials_grant(host: &str, secret: &str) -> OAuth2Grant {
+        OAuth2Grant {
+            token_url: format!("{host}/token").parse().unwrap(),
+            params: [
+                ("grant_type", "client_credentials".to_owned()),
+                ("client_id", "client".to_owned()),
+                ("client_secret", secret.to_owned()),
+            ]
+            .into_iter()
+            .collect(),
+        }
+    }
+
+    /// Cache key for [client_credentials_grant] with the secret `secret`
+    fn client_credentials_key(host: &str) -> String {
+        client_credentials_grant(host, "secret").cache_key()
+    }
+
+    /// An expired token should be refreshed with its refresh token. If the
+    /// server doesn't issue a new refresh token, the old one is kept
+    #[tokio::test]
+    async fn test_refresh_expired_token() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/token")
+            .match_body(Matcher::AllOf(vec![
+                Matcher::UrlEncoded(
+                    "grant_type".into(),
+                    "refresh_token".into(),
+                ),
+                Matcher::UrlEncoded("refresh_token".into(), "refresh1".into()),
+                Matcher::UrlEncoded("client_id".into(), "client".into()),
+            ]))
+            .with_body(r#"{"access_token":"token2","expires_in":3600}"#)
+            .create_async()
+            .await;
+
+        let database = CollectionDatabase::testing();
+        let authentication = Authentication::Oauth2ClientCredentials {
+            token_url: format!("{}/token", server.url()).as_str().into(),
+            client_id: "client".into(),
+            client_secret: "secret".into(),
+            scope: None,
+        };
+        let key = client_credentials_key(&server.url());
+        database
+            .set_oauth2_token(
+                None,
+                &key,
+                &OAuth2Token {
+                    access_token: "token1".into(),
+                    refresh_token: Some("refresh1".into()),
+                    expires_at: Some(Utc::now()),
+                },
+            )
+            .unwrap();
+        let context = create!(
+            TemplateContext,
+            http_engine: Some(HttpEngine::new(
+                &Config::default(),
+                database.clone(),
+            )),
+            database: database.clone(),
+        );
+
+        assert_eq!(
+            build_authorization(authentication, &context).await.unwrap(),
+            "Bearer token2"
+        );
+        mock.assert();
+        let token = database.get_oauth2_token(None, &key).unwrap().unwrap();
+        assert_eq!(token.access_token, "token2");
+        assert_eq!(token.refresh_token.as_deref(), Some("refresh1"));
+    }
+
+    /// Fetching a token is a triggered request, so it's not allowed without an
+    /// HTTP engine
+    #[tokio::test]
+    async fn test_fetch_token_not_allowed() {
+        let context = create!(TemplateContext);
+        let authentication = Authentication::Oauth2ClientCredentials {
+            token_url: "http://localhost/token".into(),
+            client_id: "client".into(),
+            client_secret: "secret".into(),
+            scope: None,
+        };
+        assert_err!(
+            build_authorization(authentication, &context).await,
+            "Triggered request execution not allowed in this context"
+        );
+    }
+
+    /// Error responses from the token endpoint should be shown to the user
+    #[tokio::test]
+    async fn test_fetch_token_error() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("POST", "/token")
+            .with_status(400)
+            .with_body(r#"{"error":"invalid_client"}"#)
+            .create_async()
+            .await;
+
+        let database = CollectionDatabase::testing();
+        let context = create!(
+            TemplateContext,
+            http_engine: Some(HttpEngine::new(
+                &Config::default(),
+                database.clone(),
+            )),
+            database: database,
+        );
+        let authentication = Authentication::Oauth2ClientCredentials {
+            token_url: format!("{}/token", server.url()).as_str().into(),
+            client_id: "client".into(),
+            client_secret: "secret".into(),
+            scope: None,
+        };
+        assert_err!(
+            build_authorization(authentication, &context).await,
+            "returned status 400 Bad Request: {\"error\":\"invalid_client\"}"
+        );
+    }
+
+    /// If the server rejects the token, the engine should get a new one and
+    /// retry the request
+    #[tokio::test]
+    async fn test_retry_unauthorized() {
+        let mut server = mockito::Server::new_async().await;
+        let token_mock = server
+            .mock("POST", "/token")
+            .with_body(r#"{"access_token":"token2"}"#)
+            .create_async()
+            .await;
+        let rejected_mock = server
+            .mock("GET", "/data")
+            .match_header("authorization", "Bearer token1")
+            .with_status(401)
+            .create_async()
+            .await;
+        let accepted_mock = server
+            .mock("GET", "/data")
+            .match_header("authorization", "Bearer token2")
+            .with_body("hello!")
+            .create_async()
+            .await;
+
+        let database = CollectionDatabase::testing();
+        let http_engine = HttpEngine::new(&Config::default(), database.clone());
+        let authentication = Authentication::Oauth2ClientCredentials {
+            token_url: format!("{}/token", server.url()).as_str().into(),
+            client_id: "client".into(),
+            client_secret: "secret".into(),
+            scope: None,
+        };
+        // Token doesn't have an expiration, so it will be used until rejected
+        database
+            .set_oauth2_token(
+                None,
+                &client_credentials_key(&server.url()),
+                &OAuth2Token {
+                    access_token: "token1".into(),
+                    refresh_token: None,
+                    expires_at: None,
+                },
+            )
+            .unwrap();
+        let context = create!(
+            TemplateContext,
+            http_engine: Some(http_engine.clone()),
+            database: database,
+        );
+        let recipe = create!(
+            Recipe,
+            url: Some(format!("{}/data", server.url()).as_str().into()),
+            authentication: Some(authentication),
+        );
+        let request = RequestBuilder::new(recipe, RecipeOptions::default())
+            .build(&context)
+            .await
+            .unwrap();
+        let record = http_engine.send(request.into()).await.unwrap();
+
+        assert_eq!(record.response.status, StatusCode::OK);
+        assert_eq!(
+            record.request.headers[header::AUTHORIZATION],
+            "Bearer token2"
+        );
+        // The rejected attempt is recorded too
+        let challenge = record.challenge.unwrap();
+        assert_eq!(
+            challenge.request.headers[header::AUTHORIZATION],
+            "Bearer token1"
+        );
+        assert_eq!(challenge.response.status, StatusCode::UNAUTHORIZED);
+        token_mock.assert();
+        rejected_mock.assert();
+        accepted_mock.assert();
+    }
+}
@@ -41,6 +41,14 @@ impl Query {
         content_type.parse_json(Cow::Owned(queried))
     }
 
+    /// Apply a query to a JSON value, returning every matching node
+    pub fn query_json<'a>(
+        &self,
+        value: &'a serde_json::Value,
+    ) -> Vec<&'a serde_json::Value> {
+        self.0.query(value).all()
+    }
+
     /// Apply a query to some content, returning a string. The query should
     /// return a single result. If it's a scalar, that will be stringified. If
     /// it's an array/object, it'll be converted back into its input format,
@@ -1,8 +1,14 @@
 //! HTTP-related data types
 
 use crate::{
-    collection::{ProfileId, RecipeId},
-    http::{ContentType, ResponseContent},
+    collection::{
+        Assertion, HttpSettings, HttpVersion, ProfileId, RecipeId,
+        RedirectPolicy,
+    },
+    http::{
+        AwsSigV4Credentials, ContentType, DigestCredentials, OAuth2Grant,
+        ResponseContent,
+    },
     util::ResultExt,
 };
 use anyhow::Context;
@@ -85,6 +91,18 @@ pub struct RequestRecord {
     pub start_time: DateTime<Utc>,
     /// When did we finish receiving the *entire* response?
     pub end_time: DateTime<Utc>,
+    /// If the first attempt at this request was rejected with a 401 and then
+    /// retried with new credentials, this holds the rejected attempt.
+    /// `request`/`response` always hold the final attempt.
+    pub challenge: Option<Challenge>,
+}
+
+/// The first leg of a challenge/response authentication exchange: a request
+/// that the server rejected with a 401, before being retried
+#[derive(Debug)]
+pub struct Challenge {
+    pub request: Arc<Request>,
+    pub response: Arc<Response>,
 }
 
 impl RequestRecord {
@@ -119,32 +137,153 @@ pub struct Request {
     pub headers: HeaderMap,
     /// Body content as bytes. This should be decoded as needed
     pub body: Option<Body>,
+    /// Rendered authentication that the HTTP engine has to finish at send
+    /// time. Not persisted, because it contains secrets.
+    #[serde(skip)]
+    pub authentication: Option<DeferredAuthentication>,
+    /// Settings for the HTTP client that sent this request
+    #[serde(default)]
+    pub http_settings: HttpSettings,
+    /// Checks to run against the response. Stored with the request so
+    /// history is checked against the assertions it was sent with
+    #[serde(default)]
+    pub assertions: Vec<Assertion>,
+    /// Redacted copies of any parts of this request that were rendered from
+    /// sensitive values (e.g. secrets), to be used when exporting the request.
+    /// Not persisted, for obvious reasons.
+    #[serde(skip)]
+    pub redactions: Redactions,
+}
+
+/// Redacted versions of the parts of a request that contain sensitive values,
+/// with each sensitive value replaced by [Redactions::PLACEHOLDER]. `None`
+/// means that part has nothing to hide.
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(test, derive(PartialEq))]
+pub struct Redactions {
+    /// URL, including query params
+    pub url: Option<String>,
+    /// All headers, with the value of each sensitive header replaced
+    pub headers: Option<HeaderMap>,
+    /// Encoded body
+    pub body: Option<Bytes>,
+}
+
+impl Redactions {
+    /// Text that replaces each sensitive value
+    pub const PLACEHOLDER: &'static str = "<sensitive>";
+
+    /// Restore the placeholder in a URL or form body, where it's been
+    /// percent-encoded along with everything else
+    pub fn decode_placeholder(encoded: &str) -> String {
+        encoded.replace("%3Csensitive%3E", Self::PLACEHOLDER)
+    }
+}
+
+/// Authentication that can't be fully applied while building the request.
+/// The HTTP engine applies it when the request is sent, or when the server
+/// rejects the request with a 401.
+#[derive(Clone, Debug)]
+#[cfg_attr(test, derive(PartialEq))]
+pub enum DeferredAuthentication {
+    /// On 401, fetch a new OAuth 2.0 access token and retry
+    OAuth2(OAuth2Grant),
+    /// On 401, answer the server's digest challenge and retry
+    Digest(DigestCredentials),
+    /// Sign the request immediately before sending it
+    AwsSigV4(AwsSigV4Credentials),
 }
 
 impl Request {
+    /// Copy this request with additional headers, e.g. to add authentication.
+    /// Existing headers with the same names are replaced. The ID stays the
+    /// same, because it's still the same request as far as the user is
+    /// concerned.
+    pub(super) fn with_headers(&self, new_headers: HeaderMap) -> Self {
+        let mut headers = self.headers.clone();
+        let mut redactions = self.redactions.clone();
+        if let Some(redacted) = &mut redactions.headers {
+            redacted.extend(new_headers.clone());
+        }
+        headers.extend(new_headers);
+        Self {
+            id: self.id,
+            profile_id: self.profile_id.clone(),
+            recipe_id: self.recipe_id.clone(),
+            method: self.method.clone(),
+            url: self.url.clone(),
+            headers,
+            body: self
+                .body
+                .as_ref()
+                .map(|body| Body::new(body.bytes().to_owned().into())),
+            authentication: self.authentication.clone(),
+            http_settings: self.http_settings.clone(),
+            assertions: self.assertions.clone(),
+            redactions,
+        }
+    }
+
     /// Generate a cURL command equivalent to this request
     ///
     /// This only fails if one of the headers or body is binary and can't be
-    /// converted to UTF-8.
+    /// converted to UTF-8. Sensitive values are replaced with `<sensitive>`.
     pub fn to_curl(&self) -> anyhow::Result<String> {
         let mut buf = String::new();
+        let redactions = &self.redactions;
 
         // These writes are all infallible because we're writing to a string,
         // but use ? because it's shorter than unwrap().
         let method = &self.method;
-        let url = &self.url;
+        let url = match &redactions.url {
+            Some(url) => url.as_str(),
+            None => self.url.as_str(),
+        };
         write!(&mut buf, "curl -X{method} --url '{url}'")?;
 
-        for (header, value) in &self.headers {
+        let headers = redactions.headers.as_ref().unwrap_or(&self.headers);
+        for (header, value) in headers {
             let value =
                 value.to_str().context("Error decoding header value")?;
             write!(&mut buf, " --header '{header}: {value}'")?;
         }
 
-        if let Some(body) = &self.body_str()? {
+        let body = match &redactions.body {
+            Some(body) => {
+                Some(std::str::from_utf8(body).context("Error decoding body")?)
+            }
+            None => self.body_str()?,
+        };
+        if let Some(body) = body {
             write!(&mut buf, " --data '{body}'")?;
         }
 
+        // Client settings. Only settings that were given explicitly are
+        // included. `follow` means the HTTP client's limit of 10 redirects
+        let settings = &self.http_settings;
+        if let Some(timeout) = settings.timeout {
+            write!(&mut buf, " --max-time {}", timeout.as_secs())?;
+        }
+        match settings.redirect {
+            None | Some(RedirectPolicy::None) => {}
+            Some(RedirectPolicy::Follow) => {
+                write!(&mut buf, " --location --max-redirs 10")?
+            }
+            Some(RedirectPolicy::Max(max)) => {
+                write!(&mut buf, " --location --max-redirs {max}")?
+            }
+        }
+        match settings.http_version {
+            None => {}
+            Some(HttpVersion::Http1) => write!(&mut buf, " --http1.1")?,
+            Some(HttpVersion::Http2) => {
+                write!(&mut buf, " --http2-prior-knowledge")?
+            }
+        }
+        if settings.gzip == Some(true) {
+            write!(&mut buf, " --compressed")?;
+        }
+
         Ok(buf)
     }
 
@@ -404,6 +543,7 @@ mod tests {
     use crate::test_util::*;
     use factori::create;
     use indexmap::indexmap;
+    use rstest::rstest;
     use serde_json::json;
 
     #[test]
@@ -428,4 +568,83 @@ mod tests {
             --data '{\"data\":\"value\"}'"
         );
     }
+
+    #[test]
+    fn test_to_curl_http_settings() {
+        let request = create!(
+            Request,
+            http_settings: HttpSettings {
+                timeout: Some(std::time::Duration::from_secs(10)),
+                redirect: Some(RedirectPolicy::Max(3)),
+                http_version: Some(HttpVersion::Http1),
+                gzip: Some(true),
+            },
+        );
+
+        assert_eq!(
+            request.to_curl().unwrap(),
+            "curl -XGET --url 'http://localhost/url' --max-time 10 \
+            --location --max-redirs 3 --http1.1 --compressed"
+        );
+    }
+
+    /// Redirect flags are only included when the redirect policy is set
+    #[rstest]
+    #[case::default(None, "")]
+    #[case::follow(Some(RedirectPolicy::Follow), " --location --max-redirs 10")]
+    #[case::none(Some(RedirectPolicy::None), "")]
+    #[case::max(Some(RedirectPolicy::Max(3)), " --location --max-redirs 3")]
+    fn test_to_curl_redirect(
+        #[case] redirect: Option<RedirectPolicy>,
+        #[case] expected: &str,
+    ) {
+        let request = create!(
+            Request,
+            http_settings: HttpSettings {
+                redirect,
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(
+            request.to_curl().unwrap(),
+            format!("curl -XGET --url 'http://localhost/url'{expected}")
+        );
+    }
+
+    /// Redacted parts of the request should be used in place of the real
+    /// ones. Headers marked sensitive for transport (e.g. authorization)
+    /// aren't redacted unless they were rendered from a sensitive value
+    #[test]
+    fn test_to_curl_sensitive() {
+        let mut authorization = HeaderValue::from_static("Basic dXNlcjox");
+        authorization.set_sensitive(true);
+        let mut headers = header_map([("x-count", "1"), ("x-key", "1")]);
+        headers.insert(header::AUTHORIZATION, authorization);
+        let mut redacted_headers =
+            header_map([("x-count", "1"), ("x-key", "<sensitive>")]);
+        redacted_headers.insert(
+            header::AUTHORIZATION,
+            HeaderValue::from_static("Basic dXNlcjox"),
+        );
+        let request = create!(
+            Request,
+            url: "http://localhost:8001/url?key=1".parse().unwrap(),
+            headers: headers,
+            body: Some("{\"count\":1,\"key\":\"1\"}".into()),
+            redactions: Redactions {
+                url: Some("http://localhost:8001/url?key=<sensitive>".into()),
+                headers: Some(redacted_headers),
+                body: Some(r#"{"count":1,"key":"<sensitive>"}"#.into()),
+            },
+        );
+
+        assert_eq!(
+            request.to_curl().unwrap(),
+            "curl -XGET --url 'http://localhost:8001/url?key=<sensitive>' \
+            --header 'x-count: 1' --header 'x-key: <sensitive>' \
+            --header 'authorization: Basic dXNlcjox' \
+            --data '{\"count\":1,\"key\":\"<sensitive>\"}'"
+        );
+    }
 }
@@ -1,9 +1,11 @@
 mod error;
+mod expression;
 mod parse;
 mod prompt;
 mod render;
 
 pub use error::{ChainError, TemplateError, TriggeredRequestError};
+pub use expression::{Expression, Filter, Function, FunctionError, Operand};
 pub use parse::Span;
 pub use prompt::{Prompt, PromptChannel, Prompter};
 
@@ -13,13 +15,18 @@ use crate::{
     http::HttpEngine,
     template::{
         error::TemplateParseError,
-        parse::{TemplateInputChunk, CHAIN_PREFIX, ENV_PREFIX},
+        parse::{TemplateBlock, TemplateInputChunk, CHAIN_PREFIX, ENV_PREFIX},
     },
 };
 use derive_more::Display;
 use indexmap::IndexMap;
 use serde::Serialize;
-use std::{fmt::Debug, sync::atomic::AtomicU8};
+use std::{
+    borrow::Borrow,
+    fmt::Debug,
+    hash::{Hash, Hasher},
+    sync::atomic::AtomicU8,
+};
 
 /// Maximum number of layers of nested templates
 const RECURSION_LIMIT: u8 = 10;
@@ -60,8 +67,10 @@ pub struct TemplateContext {
 
 /// An immutable string that can contain templated content. The string is parsed
 /// during creation to identify template keys, hence the immutability.
-#[derive(Clone, Debug, Display, Serialize)]
-#[cfg_attr(test, derive(PartialEq))]
+///
+/// Equality and hashing are based on the raw template text, so templates can
+/// be used as map keys (e.g. header names).
+#[derive(Clone, Debug, Default, Display, Serialize)]
 #[display("{template}")]
 #[serde(into = "String", try_from = "String")]
 pub struct Template {
@@ -83,6 +92,100 @@ impl Template {
         &self.template[span.start()..span.end()]
     }
 
+    /// Get every key in this template, in the order they appear. This
+    /// includes keys inside blocks, as well as block conditions and iterables
+    pub fn keys(&self) -> impl Iterator<Item = TemplateKey<&str>> {
+        let mut keys = Vec::new();
+        self.collect_keys(&self.chunks, &mut keys);
+        keys.into_iter()
+    }
+
+    fn collect_keys<'a>(
+        &'a self,
+        chunks: &[TemplateInputChunk<Span>],
+        keys: &mut Vec<TemplateKey<&'a str>>,
+    ) {
+        for chunk in chunks {
+            match chunk {
+                TemplateInputChunk::Raw(_) => {}
+                TemplateInputChunk::Key(key) => {
+                    keys.push(key.map(|span| self.substring(span)));
+                }
+                TemplateInputChunk::Block(block) => match &**block {
+                    TemplateBlock::If {
+                        condition,
+                        body,
+                        else_body,
+                        ..
+                    } => {
+                        keys.push(condition.map(|span| self.substring(span)));
+                        self.collect_keys(body, keys);
+                        self.collect_keys(else_body, keys);
+                    }
+                    TemplateBlock::For { iterable, body, .. } => {
+                        keys.push(iterable.map(|span| self.substring(span)));
+                        self.collect_keys(body, keys);
+                    }
+                },
+            }
+        }
+    }
+
+    /// Is this template exactly one key, with no surrounding text? E.g.
+    /// `{{user_id}}`
+    pub fn is_single_key(&self) -> bool {
+        matches!(self.chunks.as_slice(), [TemplateInputChunk::Key(_)])
+    }
+
+    /// Replace every key in this template with a static value, without
+    /// rendering anything. Useful for checking the static parts of a template.
+    /// Blocks are filled as if every condition is true and every loop has a
+    /// single element.
+    pub fn fill_keys(&self, value: &str) -> String {
+        let mut output = String::new();
+        self.fill_chunks(&self.chunks, value, &mut output);
+        output
+    }
+
+    fn fill_chunks(
+        &self,
+        chunks: &[TemplateInputChunk<Span>],
+        value: &str,
+        output: &mut String,
+    ) {
+        for chunk in chunks {
+            match chunk {
+                TemplateInputChunk::Raw(span) => {
+                    output.push_str(self.substring(*span))
+                }
+                TemplateInputChunk::Key(_) => output.push_str(value),
+                TemplateInputChunk::Block(block) => match &**block {
+                    TemplateBlock::If { body, .. }
+                    | TemplateBlock::For { body, .. } => {
+                        self.fill_chunks(body, value, output)
+                    }
+                },
+            }
+        }
+    }
+
+    /// Join multiple templates end to end. Each template has already been
+    /// parsed, so this just shifts their chunks rather than re-parsing.
+    pub fn concat<'a>(templates: impl IntoIterator<Item = &'a Self>) -> Self {
+        let mut template = String::new();
+        let mut chunks = Vec::new();
+        for other in templates {
+            let offset = template.len();
+            template.push_str(&other.template);
+            chunks.extend(other.chunks.iter().map(|chunk| {
+                chunk.map(|span| {
+                    Span::new(span.start() + offset, span.end() - span.start())
+                })
+            }));
+        }
+        Self { template, chunks }
+    }
+
     /// Create a new template **without parsing**. The created template should
     /// *never* be rendered. This is only useful when creating templates purely
     /// for the purpose of being serialized, e.g. when importing an external
@@ -94,6 +197,13 @@ impl Template {
     /// the extra code for something that is very unlikely to happen. It says
     /// "dangerous", don't be stupid.
     pub(crate) fn dangerous(template: String) -> Self {
+        Self::raw(template)
+    }
+
+    /// Create a template that renders to exactly the given string. Nothing in
+    /// the string is treated as a key, so this is safe for values that come
+    /// from outside the collection, e.g. a dotenv file.
+    pub fn raw(template: String) -> Self {
         // Create one raw chunk for everything
         let chunk = TemplateInputChunk::Raw(Span::new(0, template.len()));
         Self {
@@ -103,6 +213,28 @@ impl Template {
     }
 }
 
+impl PartialEq for Template {
+    fn eq(&self, other: &Self) -> bool {
+        self.template == other.template
+    }
+}
+
+impl Eq for Template {}
+
+impl Hash for Template {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.template.hash(state);
+    }
+}
+
+/// Allow looking up templates in maps by their raw text. This is consistent
+/// with [Hash] and [Eq]
+impl Borrow<str> for Template {
+    fn borrow(&self) -> &str {
+        &self.template
+    }
+}
+
 /// For deserialization
 impl TryFrom<String> for Template {
     type Error = TemplateParseError;
@@ -131,7 +263,12 @@ pub enum TemplateChunk {
     /// large block of text.
     Raw(Span),
     /// Outcome of rendering a template key
-    Rendered { value: String, sensitive: bool },
+    Rendered {
+        value: String,
+        sensitive: bool,
+        /// The key failed to render, and its fallback value was used instead
+        fallback: bool,
+    },
     /// An error occurred while rendering a template key
     Error(TemplateError),
 }
@@ -148,11 +285,12 @@ pub enum TemplateChunk {
 /// `Template`, because it would create a self-referential pointer. In that
 /// case, we can store a `Span` which points back to its source in the template.
 ///
-/// The `Display` impl here should return exactly what this was parsed from.
-/// This is important for matching override keys during rendering.
-#[derive(Copy, Clone, Debug, Display)]
+/// The `Display` impl here should return exactly what this was parsed from,
+/// minus any surrounding whitespace. This is important for matching override
+/// keys during rendering.
+#[derive(Clone, Debug, Display)]
 #[cfg_attr(test, derive(PartialEq))]
-enum TemplateKey<T> {
+pub enum TemplateKey<T> {
     /// A plain field, which can come from the profile or an override
     Field(T),
     /// A value from a predefined chain of another recipe
@@ -161,16 +299,73 @@ enum TemplateKey<T> {
     /// A value pulled from the process environment
     #[display("{ENV_PREFIX}{_0}")]
     Environment(T),
+    /// A function call, fallback, and/or filters, e.g. `uuid()`,
+    /// `port ?? "8080"`, or `username | upper`. Plain keys without fallbacks
+    /// or filters are parsed as one of the other variants.
+    #[display("{_0}")]
+    Expression(Box<Expression<T>>),
+    /// A variable bound by an enclosing `{% for %}` block: either the loop
+    /// item, or loop metadata such as `loop.index`
+    Variable(T),
 }
 
-impl<T> TemplateKey<T> {
+impl<T: Copy> TemplateKey<T> {
     /// Map the internal data using the given function. Useful for mapping
     /// string slices to spans and vice versa.
-    fn map<U>(self, f: impl Fn(T) -> U) -> TemplateKey<U> {
+    fn map<U>(&self, f: impl Fn(T) -> U) -> TemplateKey<U> {
+        self.map_dyn(&f)
+    }
+
+    /// [Self::map], but with a trait object so it can recurse into
+    /// expressions
+    fn map_dyn<U>(&self, f: &dyn Fn(T) -> U) -> TemplateKey<U> {
+        match self {
+            Self::Field(value) => TemplateKey::Field(f(*value)),
+            Self::Chain(value) => TemplateKey::Chain(f(*value)),
+            Self::Environment(value) => TemplateKey::Environment(f(*value)),
+            Self::Expression(expression) => {
+                TemplateKey::Expression(Box::new(expression.map(f)))
+            }
+            Self::Variable(value) => TemplateKey::Variable(f(*value)),
+        }
+    }
+
+    /// Get every field, chain, and environment variable used by this key. For
+    /// a plain key, this is just the key itself. For an expression, this is
+    /// every key used in it, including within function arguments and
+    /// fallbacks. The returned keys are never expressions.
+    pub fn references(&self) -> Vec<TemplateKey<T>> {
+        self.collect_references(false)
+    }
+
+    /// Same as [Self::references], but exclude keys that have a fallback. If
+    /// any of these keys fails to render, the entire key fails.
+    pub fn required_references(&self) -> Vec<TemplateKey<T>> {
+        self.collect_references(true)
+    }
+
+    fn collect_references(&self, required_only: bool) -> Vec<TemplateKey<T>> {
+        match self {
+            Self::Expression(expression) => {
+                let mut keys = Vec::new();
+                expression.collect_keys(&mut keys, required_only);
+                keys
+            }
+            _ => vec![self.clone()],
+        }
+    }
+}
+
+impl<'a> TemplateKey<&'a str> {
+    /// If this is a field with the given name, or an expression that uses
+    /// that field, convert those references to loop variables
+    fn bind_variable(&mut self, variable: &str) {
         match self {
-            Self::Field(value) => TemplateKey::Field(f(value)),
-            Self::Chain(value) => TemplateKey::Chain(f(value)),
-            Self::Environment(value) => TemplateKey::Environment(f(value)),
+            Self::Field(field) if *field == variable => {
+                *self = Self::Variable(field);
+            }
+            Self::Expression(expression) => expression.bind_variable(variable),
+            _ => {}
         }
     }
 }
@@ -179,14 +374,18 @@ impl<T> TemplateKey<T> {
 mod tests {
     use super::*;
     use crate::{
-        collection::{Chain, ChainRequestTrigger, ChainSource, RecipeId},
+        collection::{
+            Chain, ChainRequestSection, ChainRequestTrigger, ChainSource,
+            RecipeId,
+        },
         config::Config,
-        http::{ContentType, RequestRecord},
+        http::{ContentType, RequestRecord, Response},
         test_util::*,
     };
     use chrono::Utc;
     use factori::create;
     use indexmap::indexmap;
+    use reqwest::StatusCode;
     use rstest::rstest;
     use serde_json::json;
     use std::{env, time::Duration};
@@ -270,6 +469,118 @@ mod tests {
         );
     }
 
+    /// Secret fields, and any fields that use them, are sensitive. The whole
+    /// rendered template is sensitive if any part of it is, so it can be
+    /// redacted later
+    #[tokio::test]
+    async fn test_field_sensitive() {
+        let profile = create!(
+            Profile,
+            data: indexmap! {
+                "password".into() => "hunter2".into(),
+                "auth".into() => "user:{{password}}".into(),
+                "user".into() => "user".into(),
+            },
+            sensitive_fields: ["password".into()].into(),
+        );
+        let profile_id = profile.id.clone();
+        let context = create!(
+            TemplateContext,
+            collection: create!(
+                Collection,
+                profiles: indexmap!{profile_id.clone() => profile},
+            ),
+            selected_profile: Some(profile_id),
+        );
+
+        assert_eq!(
+            Template::from("{{user}} {{auth}}")
+                .render_chunks(&context)
+                .await,
+            vec![
+                TemplateChunk::Rendered {
+                    value: "user".into(),
+                    sensitive: false,
+                    fallback: false
+                },
+                TemplateChunk::Raw(Span::new(8, 1)),
+                TemplateChunk::Rendered {
+                    value: "user:hunter2".into(),
+                    sensitive: true,
+                    fallback: false
+                },
+            ]
+        );
+        assert_eq!(
+            Template::from("{{user}} {{auth}}")
+                .render_sensitive(&context)
+                .await
+                .unwrap(),
+            ("user user:hunter2".into(), true)
+        );
+        assert_eq!(
+            Template::from("{{user}}")
+                .render_sensitive(&context)
+                .await
+                .unwrap(),
+            ("user".into(), false)
+        );
+    }
+
+    /// Fields can be inherited from parent profiles. The child takes
+    /// precedence, then each parent in order, depth-first
+    #[tokio::test]
+    async fn test_field_inherited() {
+        let base = create!(
+            Profile,
+            id: "base".into(),
+            data: indexmap! {
+                "host".into() => "base".into(),
+                "user_id".into() => "base".into(),
+                "group_id".into() => "base".into(),
+            },
+        );
+        let middle = create!(
+            Profile,
+            id: "middle".into(),
+            extends: vec!["base".into()],
+            data: indexmap! {"user_id".into() => "middle".into()},
+        );
+        let other = create!(
+            Profile,
+            id: "other".into(),
+            data: indexmap! {
+                "user_id".into() => "other".into(),
+                "group_id".into() => "other".into(),
+            },
+        );
+        let child = create!(
+            Profile,
+            id: "child".into(),
+            extends: vec!["middle".into(), "other".into()],
+            // Nested fields are rendered against the child profile
+            data: indexmap! {
+                "url".into() => "{{host}}/{{user_id}}".into()
+            },
+        );
+        let context = create!(
+            TemplateContext,
+            collection: create!(
+                Collection,
+                profiles: [base, middle, other, child]
+                    .into_iter()
+                    .map(|profile| (profile.id.clone(), profile))
+                    .collect(),
+            ),
+            selected_profile: Some("child".into()),
+        );
+
+        assert_eq!(
+            &render!("{{url}} {{group_id}}", context).unwrap(),
+            "base/middle base"
+        );
+    }
+
     /// Potential error cases for a profile field
     #[rstest]
     #[case::unknown_field("{{onion_id}}", "Unknown field `onion_id`")]
@@ -343,6 +654,7 @@ mod tests {
             source: ChainSource::Request {
                 recipe: recipe_id.clone(),
                 trigger: Default::default(),
+                section: Default::default(),
             },
             selector: selector,
             content_type: Some(ContentType::Json),
@@ -363,6 +675,62 @@ mod tests {
         );
     }
 
+    /// Chained values can come from the response status or headers instead
+    /// of the body
+    #[rstest]
+    #[case::status(ChainRequestSection::Status, None, "201")]
+    #[case::header(
+        ChainRequestSection::Header("Location".into()),
+        None,
+        "/users/3"
+    )]
+    #[case::headers(ChainRequestSection::Headers, Some("$.etag"), "\"v1\"")]
+    #[tokio::test]
+    async fn test_chain_request_section(
+        #[case] section: ChainRequestSection,
+        #[case] selector: Option<&str>,
+        #[case] expected_value: &str,
+    ) {
+        let recipe_id: RecipeId = "recipe1".into();
+        let database = CollectionDatabase::testing();
+        let response = Response {
+            status: StatusCode::CREATED,
+            headers: header_map([("location", "/users/3"), ("etag", "\"v1\"")]),
+            body: "body".into(),
+        };
+        database
+            .insert_request(&create!(
+                RequestRecord,
+                request: create!(Request, recipe_id: recipe_id.clone()).into(),
+                response: response.into(),
+            ))
+            .unwrap();
+        let recipe = create!(Recipe, id: recipe_id.clone());
+        let chain = create!(
+            Chain,
+            source: ChainSource::Request {
+                recipe: recipe_id.clone(),
+                trigger: Default::default(),
+                section,
+            },
+            selector: selector.map(|s| s.parse().unwrap()),
+        );
+        let context = create!(
+            TemplateContext,
+            collection: create!(
+                Collection,
+                recipes: indexmap! {recipe.id.clone() => recipe}.into(),
+                chains: indexmap! {chain.id.clone() => chain},
+            ),
+            database: database,
+        );
+
+        assert_eq!(
+            render!("{{chains.chain1}}", context).unwrap(),
+            expected_value
+        );
+    }
+
     /// Test all possible error cases for chained requests. This covers all
     /// chain-specific error variants
     #[rstest]
@@ -376,6 +744,7 @@ mod tests {
             source: ChainSource::Request {
                 recipe: "unknown".into(),
                 trigger: Default::default(),
+                section: Default::default(),
             }
         ),
         None,
@@ -390,6 +759,7 @@ mod tests {
             source: ChainSource::Request {
                 recipe: "recipe1".into(),
                 trigger: Default::default(),
+                section: Default::default(),
             }
         ),
         Some("recipe1"),
@@ -404,6 +774,7 @@ mod tests {
             source: ChainSource::Request {
                 recipe: "recipe1".into(),
                 trigger: ChainRequestTrigger::Always,
+                section: Default::default(),
             }
         ),
         Some("recipe1"),
@@ -418,6 +789,7 @@ mod tests {
             source: ChainSource::Request {
                 recipe: "recipe1".into(),
                 trigger: Default::default(),
+                section: Default::default(),
             },
             selector: Some("$.message".parse().unwrap()),
         ),
@@ -436,6 +808,7 @@ mod tests {
             source: ChainSource::Request {
                 recipe: "recipe1".into(),
                 trigger: Default::default(),
+                section: Default::default(),
             },
             selector: Some("$.message".parse().unwrap()),
             content_type: Some(ContentType::Json),
@@ -447,6 +820,21 @@ mod tests {
         )),
         "Parsing response: expected ident at line 1 column 2",
     )]
+    // Requested header isn't in the response
+    #[case::missing_header(
+        "chain1",
+        create!(
+            Chain,
+            source: ChainSource::Request {
+                recipe: "recipe1".into(),
+                trigger: Default::default(),
+                section: ChainRequestSection::Header("ETag".into()),
+            },
+        ),
+        Some("recipe1"),
+        Some(create!(RequestRecord)),
+        "Header `ETag` not in response",
+    )]
     // Query returned multiple results
     #[case::query_multiple_results(
         "chain1",
@@ -455,6 +843,7 @@ mod tests {
             source: ChainSource::Request {
                 recipe: "recipe1".into(),
                 trigger: Default::default(),
+                section: Default::default(),
             },
             selector: Some("$.*".parse().unwrap()),
             content_type: Some(ContentType::Json),
@@ -540,12 +929,14 @@ mod tests {
             .create_async()
             .await;
 
-        let recipe = create!(Recipe, url: format!("{url}/get").as_str().into());
+        let recipe =
+            create!(Recipe, url: Some(format!("{url}/get").as_str().into()));
         let chain = create!(
             Chain,
             source: ChainSource::Request {
                 recipe: recipe.id.clone(),
                 trigger,
+                section: Default::default(),
             },
         );
         let http_engine = HttpEngine::new(&Config::default(), database.clone());
@@ -606,6 +997,78 @@ mod tests {
         assert_err!(render!("{{chains.chain1}}", context), expected_error);
     }
 
+    /// Test transforms on a chained value. The command output includes a
+    /// trailing newline, which most of these trim first.
+    #[rstest]
+    #[case::none("[]", "  hello!\n")]
+    #[case::trim("[trim]", "hello!")]
+    #[case::base64_encode("[trim, base64_encode]", "aGVsbG8h")]
+    #[case::base64_round_trip("[base64_encode, base64_decode, trim]", "hello!")]
+    #[case::url_encode("[trim, url_encode]", "hello%21")]
+    #[case::md5("[trim, !hash md5]", "5a8dd3ad0756a93ded72b823b19dd877")]
+    #[case::sha256(
+        "[trim, !hash sha256]",
+        "ce06092fb948d9ffac7d1a376e404b26b7575bcc11ee05a4615fef4fec3a308b"
+    )]
+    #[case::regex_group("[!regex 'h(.+)o']", "ell")]
+    #[case::regex_no_group("[!regex 'l+']", "ll")]
+    #[case::json_escape("[json_escape]", "  hello!\\n")]
+    #[tokio::test]
+    async fn test_chain_transforms(
+        #[case] transforms: &str,
+        #[case] expected: &str,
+    ) {
+        let source = ChainSource::Command {
+            command: vec!["echo".into(), "  hello!".into()],
+        };
+        let chain = create!(
+            Chain,
+            source: source,
+            transforms: serde_yaml::from_str(transforms).unwrap(),
+        );
+        let context = create!(
+            TemplateContext,
+            collection: create!(
+                Collection,
+                chains: indexmap! {chain.id.clone() => chain},
+            ),
+        );
+
+        assert_eq!(render!("{{chains.chain1}}", context).unwrap(), expected);
+    }
+
+    /// Test failures in chain transforms
+    #[rstest]
+    #[case::base64_decode("[!regex '=+', base64_decode]", "Decoding base64")]
+    #[case::base64_invalid_utf8(
+        "[base64_decode]",
+        "Error decoding content as UTF-8"
+    )]
+    #[case::regex_no_match("[!regex '\\d+']", "Regex `\\d+` did not match")]
+    #[tokio::test]
+    async fn test_chain_transforms_error(
+        #[case] transforms: &str,
+        #[case] expected_error: &str,
+    ) {
+        let source = ChainSource::Command {
+            command: vec!["echo".into(), "/w==".into()],
+        };
+        let chain = create!(
+            Chain,
+            source: source,
+            transforms: serde_yaml::from_str(transforms).unwrap(),
+        );
+        let context = create!(
+            TemplateContext,
+            collection: create!(
+                Collection,
+                chains: indexmap! {chain.id.clone() => chain},
+            ),
+        );
+
+        assert_err!(render!("{{chains.chain1}}", context), expected_error);
+    }
+
     /// Test success with chained file
     #[tokio::test]
     async fn test_chain_file() {
@@ -725,7 +1188,8 @@ mod tests {
                 .await,
             vec![TemplateChunk::Rendered {
                 value: "hello!".into(),
-                sensitive: true
+                sensitive: true,
+                fallback: false
             }]
         );
     }
@@ -824,6 +1288,356 @@ mod tests {
         );
     }
 
+    /// Test deterministic expressions
+    #[rstest]
+    #[case::filter("{{ user | upper }}", "SLUMBER")]
+    #[case::filters("{{ padded | trim | lower }}", "slumber")]
+    #[case::function_field("{{ base64(user) }}", "U2x1bWJlcg==")]
+    #[case::function_string("{{ base64(\"hi\") | lower }}", "agk=")]
+    #[case::random_int("{{ random_int(min, 7) }}", "7")]
+    #[case::override_in_expression("{{ overridden | upper }}", "OVERRIDE")]
+    #[case::override_expression("{{ base64(user)|upper }}", "override2")]
+    #[case::fallback_unused("{{ user ?? \"default\" }}", "Slumber")]
+    #[case::fallback_string("{{ missing ?? \"default\" }}", "default")]
+    #[case::fallback_integer("{{ env.MISSING_PORT ?? 8080 }}", "8080")]
+    #[case::fallback_chain(
+        "{{ missing ?? env.MISSING ?? user | upper }}",
+        "SLUMBER"
+    )]
+    #[tokio::test]
+    async fn test_expression(#[case] template: &str, #[case] expected: &str) {
+        let profile = create!(
+            Profile,
+            data: indexmap! {
+                "user".into() => "Slumber".into(),
+                "padded".into() => "  Slumber\n".into(),
+                "min".into() => "7".into(),
+            },
+        );
+        let profile_id = profile.id.clone();
+        let context = create!(
+            TemplateContext,
+            collection: create!(
+                Collection,
+                profiles: indexmap!{profile_id.clone() => profile},
+            ),
+            selected_profile: Some(profile_id),
+            overrides: indexmap! {
+                "overridden".into() => "override".into(),
+                "base64(user) | upper".into() => "override2".into(),
+            },
+        );
+
+        assert_eq!(render!(template, context).unwrap(), expected);
+    }
+
+    /// Test non-deterministic functions by checking the shape of the output
+    #[tokio::test]
+    async fn test_expression_functions() {
+        let context = create!(TemplateContext);
+
+        let uuid = render!("{{ uuid() }}", context).unwrap();
+        assert!(uuid.parse::<uuid::Uuid>().is_ok(), "Invalid UUID {uuid}");
+        let timestamp = render!("{{ timestamp() }}", context).unwrap();
+        assert!(timestamp.parse::<i64>().unwrap() > 0);
+        let now = render!("{{ now() }}", context).unwrap();
+        assert!(
+            chrono::DateTime::parse_from_rfc3339(&now).is_ok(),
+            "Invalid date {now}"
+        );
+        assert_eq!(
+            render!("{{ now(\"%Y\") }}", context).unwrap(),
+            Utc::now().format("%Y").to_string()
+        );
+        let random = render!("{{ random_int(-1, 1) }}", context).unwrap();
+        assert!(["-1", "0", "1"].contains(&random.as_str()));
+    }
+
+    /// Sensitive values are still sensitive after being transformed
+    #[tokio::test]
+    async fn test_expression_sensitive() {
+        let profile = create!(
+            Profile,
+            data: indexmap! {"password".into() => "hunter2".into()},
+            sensitive_fields: ["password".into()].into(),
+        );
+        let profile_id = profile.id.clone();
+        let context = create!(
+            TemplateContext,
+            collection: create!(
+                Collection,
+                profiles: indexmap!{profile_id.clone() => profile},
+            ),
+            selected_profile: Some(profile_id),
+        );
+
+        assert_eq!(
+            Template::from("{{ base64(password) | lower }}")
+                .render_chunks(&context)
+                .await,
+            vec![TemplateChunk::Rendered {
+                value: "ahvudgvymg==".into(),
+                sensitive: true,
+                fallback: false
+            }]
+        );
+    }
+
+    #[rstest]
+    #[case::function(
+        "{{ random_int(\"a\", 1) }}",
+        "Calling function `random_int`: Invalid integer `a`"
+    )]
+    #[case::argument("{{ base64(unknown) }}", "Unknown field `unknown`")]
+    // If every fallback fails, the first error is returned
+    #[case::fallback(
+        "{{ unknown ?? env.MISSING ?? unknown2 }}",
+        "Unknown field `unknown`"
+    )]
+    #[tokio::test]
+    async fn test_expression_error(
+        #[case] template: &str,
+        #[case] expected_error: &str,
+    ) {
+        let context = create!(
+            TemplateContext,
+            collection: create!(
+                Collection,
+                profiles: indexmap!{"p".into() => create!(Profile)},
+            ),
+            selected_profile: Some("p".into()),
+        );
+        assert_err!(render!(template, context), expected_error);
+    }
+
+    /// Fallbacks apply to chain errors too. The rendered chunk should be
+    /// marked as a fallback, so it can be shown in the preview
+    #[tokio::test]
+    async fn test_fallback_chain() {
+        let recipe = create!(Recipe);
+        let chain = create!(
+            Chain,
+            source: ChainSource::Request {
+                recipe: recipe.id.clone(),
+                trigger: Default::default(),
+                section: Default::default(),
+            },
+        );
+        let context = create!(
+            TemplateContext,
+            collection: create!(
+                Collection,
+                recipes: indexmap! {recipe.id.clone() => recipe}.into(),
+                chains: indexmap! {chain.id.clone() => chain},
+            ),
+        );
+
+        assert_eq!(
+            Template::from("{{ chains.chain1 ?? \"none\" }}")
+                .render_chunks(&context)
+                .await,
+            vec![TemplateChunk::Rendered {
+                value: "none".into(),
+                sensitive: false,
+                fallback: true,
+            }]
+        );
+    }
+
+    /// Test `if` and `for` blocks
+    #[rstest]
+    #[case::if_true("{% if user %}hi {{user}}{% endif %}", "hi Slumber")]
+    #[case::if_false("a{% if empty %}b{% endif %}c", "ac")]
+    #[case::if_else("{% if zero %}yes{% else %}no{% endif %}", "no")]
+    #[case::if_not("{% if not empty %}empty{% endif %}", "empty")]
+    #[case::if_expression(
+        "{% if missing ?? \"\" %}yes{% else %}no{% endif %}",
+        "no"
+    )]
+    #[case::for_loop("{% for id in ids %}<{{id}}>{% endfor %}", "<1><two><3>")]
+    #[case::for_empty("[{% for id in empty_array %}{{id}}{% endfor %}]", "[]")]
+    #[case::for_objects(
+        "{% for item in objects %}{{item}};{% endfor %}",
+        "{\"a\":1};null;"
+    )]
+    #[case::for_expression(
+        "{% for id in ids %}{{ id | upper }}{% endfor %}",
+        "1TWO3"
+    )]
+    #[case::for_shadows_field(
+        "{% for user in ids %}{{user}}{% endfor %}",
+        "1two3"
+    )]
+    #[case::for_loop_metadata(
+        "[{% for id in ids %}{{loop.index}}:{{id}}\
+        {% if not loop.last %},{% endif %}{% endfor %}]",
+        "[1:1,2:two,3:3]"
+    )]
+    #[case::for_nested(
+        "{% for a in ids %}{% for b in objects %}\
+        {% if loop.first %}{{a}}{{loop.index}}{% endif %}\
+        {% endfor %}{% endfor %}",
+        "11two131"
+    )]
+    #[case::unknown_block("{% response 'body' %}", "{% response 'body' %}")]
+    #[tokio::test]
+    async fn test_block(#[case] template: &str, #[case] expected: &str) {
+        let profile = create!(
+            Profile,
+            data: indexmap! {
+                "user".into() => "Slumber".into(),
+                "empty".into() => "".into(),
+                "zero".into() => "0".into(),
+                "ids".into() => "[1, \"two\", 3]".into(),
+                "empty_array".into() => " [] ".into(),
+                "objects".into() => "[{\"a\": 1}, null]".into(),
+            },
+        );
+        let profile_id = profile.id.clone();
+        let context = create!(
+            TemplateContext,
+            collection: create!(
+                Collection,
+                profiles: indexmap!{profile_id.clone() => profile},
+            ),
+            selected_profile: Some(profile_id),
+        );
+
+        assert_eq!(render!(template, context).unwrap(), expected);
+    }
+
+    /// Loop variables take precedence over overrides with the same name,
+    /// while other keys can still be overridden within the loop
+    #[tokio::test]
+    async fn test_block_override() {
+        let profile = create!(
+            Profile,
+            data: indexmap! {
+                "ids".into() => "[1, 2]".into(),
+                "user".into() => "Slumber".into(),
+            },
+        );
+        let profile_id = profile.id.clone();
+        let context = create!(
+            TemplateContext,
+            collection: create!(
+                Collection,
+                profiles: indexmap!{profile_id.clone() => profile},
+            ),
+            selected_profile: Some(profile_id),
+            overrides: indexmap! {
+                "id".into() => "override".into(),
+                "user".into() => "Override".into(),
+            },
+        );
+
+        assert_eq!(
+            render!("{% for id in ids %}{{user}}{{id}};{% endfor %}", context)
+                .unwrap(),
+            "Override1;Override2;"
+        );
+    }
+
+    #[rstest]
+    #[case::condition(
+        "{% if unknown %}yes{% endif %}",
+        "Unknown field `unknown`"
+    )]
+    #[case::iterable(
+        "{% for x in unknown %}{{x}}{% endfor %}",
+        "Unknown field `unknown`"
+    )]
+    #[case::not_array(
+        "{% for x in user %}{{x}}{% endfor %}",
+        "Value of `user` is not a JSON array"
+    )]
+    #[case::body(
+        "{% if user %}{{unknown}}{% endif %}",
+        "Unknown field `unknown`"
+    )]
+    #[case::variable_outside_loop(
+        "{{loop.index}}",
+        "Unknown variable `loop.index`"
+    )]
+    #[case::unknown_loop_variable(
+        "{% for x in ids %}{{loop.bogus}}{% endfor %}",
+        "Unknown variable `loop.bogus`"
+    )]
+    #[tokio::test]
+    async fn test_block_error(
+        #[case] template: &str,
+        #[case] expected_error: &str,
+    ) {
+        let profile = create!(
+            Profile,
+            data: indexmap! {
+                "user".into() => "Slumber".into(),
+                "ids".into() => "[1]".into(),
+            },
+        );
+        let profile_id = profile.id.clone();
+        let context = create!(
+            TemplateContext,
+            collection: create!(
+                Collection,
+                profiles: indexmap!{profile_id.clone() => profile},
+            ),
+            selected_profile: Some(profile_id),
+        );
+        assert_err!(render!(template, context), expected_error);
+    }
+
+    /// Blocks are expanded into the chunks of their body, so previews can
+    /// highlight keys within blocks. Loop items from a sensitive value are
+    /// sensitive too
+    #[tokio::test]
+    async fn test_render_chunks_block() {
+        let profile = create!(
+            Profile,
+            data: indexmap! {"ids".into() => "[1, 2]".into()},
+            sensitive_fields: ["ids".into()].into(),
+        );
+        let profile_id = profile.id.clone();
+        let context = create!(
+            TemplateContext,
+            collection: create!(
+                Collection,
+                profiles: indexmap!{profile_id.clone() => profile},
+            ),
+            selected_profile: Some(profile_id),
+        );
+
+        let chunks = Template::from(
+            "{% for id in ids %}id={{id}} #{{loop.index}}\n{% endfor %}\
+            {% if bogus %}{% endif %}",
+        )
+        .render_chunks(&context)
+        .await;
+        let rendered = |value: &str, sensitive| TemplateChunk::Rendered {
+            value: value.into(),
+            sensitive,
+            fallback: false,
+        };
+        assert_eq!(
+            chunks,
+            vec![
+                TemplateChunk::Raw(Span::new(19, 3)),
+                rendered("1", true),
+                TemplateChunk::Raw(Span::new(28, 2)),
+                rendered("1", false),
+                TemplateChunk::Raw(Span::new(44, 1)),
+                TemplateChunk::Raw(Span::new(19, 3)),
+                rendered("2", true),
+                TemplateChunk::Raw(Span::new(28, 2)),
+                rendered("2", false),
+                TemplateChunk::Raw(Span::new(44, 1)),
+                TemplateChunk::Error(TemplateError::FieldUnknown {
+                    field: "bogus".into()
+                }),
+            ]
+        );
+    }
+
     /// Test rendering into individual chunks with complex unicode
     #[tokio::test]
     async fn test_render_chunks() {
@@ -849,7 +1663,8 @@ mod tests {
                 TemplateChunk::Raw(Span::new(0, 6)),
                 TemplateChunk::Rendered {
                     value: "🧡💛".into(),
-                    sensitive: false
+                    sensitive: false,
+                    fallback: false
                 },
                 // Each emoji is 4 bytes
                 TemplateChunk::Raw(Span::new(17, 14)),
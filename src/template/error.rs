@@ -1,7 +1,7 @@
 use crate::{
     collection::{ChainId, ProfileId, RecipeId},
     http::{QueryError, RequestBuildError, RequestError},
-    template::RECURSION_LIMIT,
+    template::{Function, FunctionError, RECURSION_LIMIT},
     util::doc_link,
 };
 use nom::error::VerboseError;
@@ -68,6 +68,23 @@ pub enum TemplateError {
         error: ChainError,
     },
 
+    /// A function call in a template expression failed
+    #[error("Calling function `{function}`")]
+    Function {
+        function: Function,
+        #[source]
+        error: FunctionError,
+    },
+
+    /// A loop variable that isn't bound by any enclosing `{% for %}` block,
+    /// e.g. `loop.index` outside of a loop
+    #[error("Unknown variable `{variable}`")]
+    VariableUnknown { variable: String },
+
+    /// The value given to a `{% for %}` block can't be iterated over
+    #[error("Value of `{key}` is not a JSON array")]
+    InvalidIterable { key: String },
+
     /// Variable either didn't exist or had non-unicode content
     #[error("Accessing environment variable `{variable}`")]
     EnvironmentVariable {
@@ -107,6 +124,10 @@ pub enum ChainError {
     #[error("No response available")]
     NoResponse,
 
+    /// Chain requested a header that isn't in the response
+    #[error("Header `{header}` not in response")]
+    MissingHeader { header: String },
+
     /// Couldn't guess content type from request/file/etc. metadata
     #[error(
         "Selector cannot be applied; content type not provided and could not \
@@ -147,6 +168,17 @@ pub enum ChainError {
         error: io::Error,
     },
 
+    /// Chained value couldn't be decoded by a `base64_decode` transform
+    #[error("Decoding base64")]
+    Base64Decode {
+        #[source]
+        error: base64::DecodeError,
+    },
+
+    /// Regex in a `regex` transform didn't match the chained value
+    #[error("Regex `{pattern}` did not match value")]
+    RegexNoMatch { pattern: String },
+
     /// Error opening/reading a file
     #[error("Reading file `{path}`")]
     File {
@@ -203,6 +235,11 @@ impl TemplateError {
                     },
                     ..
                 })
+            ) || matches!(
+                // Some non-template requests (e.g. OAuth 2.0 token fetches)
+                // use this error directly
+                error.downcast_ref(),
+                Some(TriggeredRequestError::NotAllowed)
             )
         })
     }
@@ -0,0 +1,427 @@
+//! Template expressions: function calls, fallbacks, and filters, e.g.
+//! `{{ uuid() }}`, `{{ port ?? "8080" }}` or `{{ username | upper }}`
+
+use crate::{template::TemplateKey, util::uri_encode};
+use base64::{prelude::BASE64_STANDARD, Engine};
+use chrono::Utc;
+use derive_more::Display;
+use itertools::Itertools;
+use rand::Rng;
+use std::{
+    fmt::{self, Write},
+    iter,
+    num::ParseIntError,
+    ops::RangeInclusive,
+};
+use strum::EnumString;
+use thiserror::Error;
+use uuid::Uuid;
+
+/// A value computed from function calls, fallbacks, and/or filters. Generic
+/// over string storage the same way as [TemplateKey].
+#[derive(Clone, Debug)]
+#[cfg_attr(test, derive(PartialEq))]
+pub struct Expression<T> {
+    /// The initial value, before any filters are applied
+    pub operand: Operand<T>,
+    /// Values to try, in order, if the operand fails to render. Filters are
+    /// applied to whichever value is used
+    pub fallbacks: Vec<Operand<T>>,
+    /// Filters to apply to the value, in order
+    pub filters: Vec<Filter>,
+}
+
+/// A single value within an expression
+#[derive(Clone, Debug)]
+#[cfg_attr(test, derive(PartialEq))]
+pub enum Operand<T> {
+    /// A field, chain, or environment variable. This is never an expression
+    Key(TemplateKey<T>),
+    /// A string literal. Stores the contents without the quotes
+    String(T),
+    /// An integer literal. Only allowed as a function argument, because a
+    /// bare number is a field name
+    Integer(T),
+    /// A call to a built-in function
+    Call {
+        function: Function,
+        arguments: Vec<Operand<T>>,
+    },
+}
+
+/// A built-in function that can be called in a template
+#[derive(Copy, Clone, Debug, Display, EnumString, Eq, PartialEq)]
+pub enum Function {
+    /// Generate a random v4 UUID
+    #[display("uuid")]
+    #[strum(serialize = "uuid")]
+    Uuid,
+    /// The current UTC time, as RFC 3339 or with a custom `strftime` format
+    #[display("now")]
+    #[strum(serialize = "now")]
+    Now,
+    /// The current Unix timestamp, in seconds
+    #[display("timestamp")]
+    #[strum(serialize = "timestamp")]
+    Timestamp,
+    /// A random integer between `min` and `max`, inclusive
+    #[display("random_int")]
+    #[strum(serialize = "random_int")]
+    RandomInt,
+    /// Encode a value as standard base64
+    #[display("base64")]
+    #[strum(serialize = "base64")]
+    Base64,
+}
+
+/// A transformation applied to a value via `|`
+#[derive(Copy, Clone, Debug, Display, EnumString, Eq, PartialEq)]
+pub enum Filter {
+    /// Convert to uppercase
+    #[display("upper")]
+    #[strum(serialize = "upper")]
+    Upper,
+    /// Convert to lowercase
+    #[display("lower")]
+    #[strum(serialize = "lower")]
+    Lower,
+    /// Remove leading and trailing whitespace
+    #[display("trim")]
+    #[strum(serialize = "trim")]
+    Trim,
+    /// Encode as standard base64
+    #[display("base64")]
+    #[strum(serialize = "base64")]
+    Base64,
+    /// Percent-encode everything except unreserved URL characters
+    #[display("url_encode")]
+    #[strum(serialize = "url_encode")]
+    UrlEncode,
+}
+
+/// An error while calling a function with rendered arguments
+#[derive(Debug, Error)]
+#[cfg_attr(test, derive(PartialEq))]
+pub enum FunctionError {
+    /// An argument that should be an integer isn't
+    #[error("Invalid integer `{value}`")]
+    InvalidInteger {
+        value: String,
+        #[source]
+        error: ParseIntError,
+    },
+
+    /// `random_int` was given a minimum larger than its maximum
+    #[error("Invalid range: minimum `{min}` is greater than maximum `{max}`")]
+    EmptyRange { min: i64, max: i64 },
+
+    /// `now` was given an invalid `strftime` format
+    #[error("Invalid date format `{format}`")]
+    InvalidDateFormat { format: String },
+}
+
+impl<T: Copy> Expression<T> {
+    /// Map the internal data using the given function. Useful for mapping
+    /// string slices to spans and vice versa.
+    pub(super) fn map<U>(&self, f: &dyn Fn(T) -> U) -> Expression<U> {
+        Expression {
+            operand: self.operand.map(f),
+            fallbacks: self
+                .fallbacks
+                .iter()
+                .map(|fallback| fallback.map(f))
+                .collect(),
+            filters: self.filters.clone(),
+        }
+    }
+
+    /// Collect every key (field, chain, or environment variable) used in this
+    /// expression. If `required_only` is set, exclude keys that are covered
+    /// by a fallback. That leaves just the keys in the final fallback, because
+    /// if those fail, the whole expression fails.
+    pub(super) fn collect_keys(
+        &self,
+        keys: &mut Vec<TemplateKey<T>>,
+        required_only: bool,
+    ) {
+        let operands = iter::once(&self.operand).chain(&self.fallbacks);
+        if required_only {
+            // There's always at least one operand
+            operands.last().unwrap().collect_keys(keys);
+        } else {
+            for operand in operands {
+                operand.collect_keys(keys);
+            }
+        }
+    }
+}
+
+impl<'a> Expression<&'a str> {
+    /// Bind references to a loop variable. See
+    /// [TemplateKey::bind_variable]
+    pub(super) fn bind_variable(&mut self, variable: &str) {
+        for operand in iter::once(&mut self.operand).chain(&mut self.fallbacks)
+        {
+            operand.bind_variable(variable);
+        }
+    }
+}
+
+impl<T: Copy> Operand<T> {
+    fn map<U>(&self, f: &dyn Fn(T) -> U) -> Operand<U> {
+        match self {
+            Self::Key(key) => Operand::Key(key.map_dyn(f)),
+            Self::String(value) => Operand::String(f(*value)),
+            Self::Integer(value) => Operand::Integer(f(*value)),
+            Self::Call {
+                function,
+                arguments,
+            } => Operand::Call {
+                function: *function,
+                arguments: arguments
+                    .iter()
+                    .map(|argument| argument.map(f))
+                    .collect(),
+            },
+        }
+    }
+
+    /// Collect every key (field, chain, environment variable, or loop
+    /// variable) used in this operand, including in nested function arguments
+    fn collect_keys(&self, keys: &mut Vec<TemplateKey<T>>) {
+        match self {
+            Self::Key(key) => keys.push(key.clone()),
+            Self::String(_) | Self::Integer(_) => {}
+            Self::Call { arguments, .. } => {
+                for argument in arguments {
+                    argument.collect_keys(keys);
+                }
+            }
+        }
+    }
+}
+
+impl<'a> Operand<&'a str> {
+    fn bind_variable(&mut self, variable: &str) {
+        match self {
+            Self::Key(key) => key.bind_variable(variable),
+            Self::String(_) | Self::Integer(_) => {}
+            Self::Call { arguments, .. } => {
+                for argument in arguments {
+                    argument.bind_variable(variable);
+                }
+            }
+        }
+    }
+}
+
+impl Function {
+    /// Number of arguments this function accepts
+    pub fn arity(self) -> RangeInclusive<usize> {
+        match self {
+            Self::Uuid | Self::Timestamp => 0..=0,
+            Self::Now => 0..=1,
+            Self::RandomInt => 2..=2,
+            Self::Base64 => 1..=1,
+        }
+    }
+
+    /// Human-readable description of [Self::arity], for parse errors. Needs
+    /// to be static because nom error contexts are
+    pub fn arity_message(self) -> &'static str {
+        match self {
+            Self::Uuid => "uuid() takes no arguments",
+            Self::Now => "now() takes an optional format argument",
+            Self::Timestamp => "timestamp() takes no arguments",
+            Self::RandomInt => "random_int() takes two arguments: min and max",
+            Self::Base64 => "base64() takes one argument",
+        }
+    }
+
+    /// Call this function with rendered arguments. The number of arguments
+    /// is checked during parsing, so it's assumed to be correct here
+    pub fn call(self, arguments: &[String]) -> Result<String, FunctionError> {
+        match (self, arguments) {
+            (Self::Uuid, _) => Ok(Uuid::new_v4().to_string()),
+            (Self::Now, [format]) => {
+                // An invalid format only fails when formatting, and
+                // to_string() would panic on it
+                let mut output = String::new();
+                write!(output, "{}", Utc::now().format(format)).map_err(
+                    |_: fmt::Error| FunctionError::InvalidDateFormat {
+                        format: format.clone(),
+                    },
+                )?;
+                Ok(output)
+            }
+            (Self::Now, _) => Ok(Utc::now().to_rfc3339()),
+            (Self::Timestamp, _) => Ok(Utc::now().timestamp().to_string()),
+            (Self::RandomInt, [min, max]) => {
+                let (min, max) = (parse_int(min)?, parse_int(max)?);
+                if min > max {
+                    return Err(FunctionError::EmptyRange { min, max });
+                }
+                Ok(rand::thread_rng().gen_range(min..=max).to_string())
+            }
+            (Self::Base64, [value]) => Ok(BASE64_STANDARD.encode(value)),
+            (_, _) => unreachable!(
+                "Function `{self}` called with invalid arguments {:?}",
+                arguments
+            ),
+        }
+    }
+}
+
+impl Filter {
+    /// Apply this filter to a value
+    pub fn apply(self, value: String) -> String {
+        match self {
+            Self::Upper => value.to_uppercase(),
+            Self::Lower => value.to_lowercase(),
+            Self::Trim => value.trim().to_owned(),
+            Self::Base64 => BASE64_STANDARD.encode(value),
+            Self::UrlEncode => uri_encode(&value),
+        }
+    }
+}
+
+/// The `Display` impl should return exactly what this was parsed from,
+/// modulo whitespace. This is used to match override keys.
+impl<T: fmt::Display> fmt::Display for Expression<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.operand)?;
+        for fallback in &self.fallbacks {
+            write!(f, " ?? {fallback}")?;
+        }
+        for filter in &self.filters {
+            write!(f, " | {filter}")?;
+        }
+        Ok(())
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for Operand<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Key(key) => write!(f, "{key}"),
+            Self::String(value) => write!(f, "\"{value}\""),
+            Self::Integer(value) => write!(f, "{value}"),
+            Self::Call {
+                function,
+                arguments,
+            } => write!(f, "{function}({})", arguments.iter().format(", ")),
+        }
+    }
+}
+
+fn parse_int(value: &str) -> Result<i64, FunctionError> {
+    value
+        .trim()
+        .parse()
+        .map_err(|error| FunctionError::InvalidInteger {
+            value: value.to_owned(),
+            error,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::*;
+    use rstest::rstest;
+
+    /// Test calling functions with valid arguments. Non-deterministic
+    /// functions are covered by the render tests
+    #[rstest]
+    #[case::random_int_single(Function::RandomInt, &["3", "3"], "3")]
+    #[case::random_int_negative(Function::RandomInt, &["-2", "-2"], "-2")]
+    #[case::random_int_max(
+        Function::RandomInt,
+        &["9223372036854775807", "9223372036854775807"],
+        "9223372036854775807",
+    )]
+    #[case::base64(Function::Base64, &["hello!"], "aGVsbG8h")]
+    fn test_call(
+        #[case] function: Function,
+        #[case] arguments: &[&str],
+        #[case] expected: &str,
+    ) {
+        let arguments = arguments.iter().map(|s| s.to_string()).collect_vec();
+        assert_eq!(function.call(&arguments).unwrap(), expected);
+    }
+
+    /// Random integers should cover the entire requested range, including
+    /// the upper bits of wide ranges
+    #[test]
+    fn test_random_int_distribution() {
+        let call = |min: &str, max: &str| -> i64 {
+            Function::RandomInt
+                .call(&[min.to_owned(), max.to_owned()])
+                .unwrap()
+                .parse()
+                .unwrap()
+        };
+
+        // The odds of 1000 samples missing any one of 4 values are negligible
+        let samples = (0..1000).map(|_| call("-1", "2")).collect_vec();
+        assert!(samples.iter().all(|n| (-1..=2).contains(n)));
+        for n in -1..=2 {
+            assert!(samples.contains(&n), "Never produced {n}");
+        }
+
+        // Half of all values in this range are at least 2^62, so the odds of
+        // never getting one in 100 samples are 2^-100
+        assert!(
+            (0..100)
+                .map(|_| call("0", "9223372036854775807"))
+                .any(|n| n >= 1 << 62),
+            "Never produced a value >= 2^62"
+        );
+        assert!(
+            (0..100)
+                .map(|_| call("-9223372036854775808", "9223372036854775807"))
+                .any(|n| n < 0),
+            "Never produced a negative value"
+        );
+    }
+
+    /// Test calling functions with invalid arguments
+    #[rstest]
+    #[case::random_int_invalid(
+        Function::RandomInt,
+        &["one", "2"],
+        "Invalid integer `one`"
+    )]
+    #[case::random_int_empty(
+        Function::RandomInt,
+        &["2", "1"],
+        "minimum `2` is greater than maximum `1`"
+    )]
+    #[case::now_invalid_format(
+        Function::Now,
+        &["%Q"],
+        "Invalid date format `%Q`"
+    )]
+    fn test_call_error(
+        #[case] function: Function,
+        #[case] arguments: &[&str],
+        #[case] expected_error: &str,
+    ) {
+        let arguments = arguments.iter().map(|s| s.to_string()).collect_vec();
+        assert_err!(function.call(&arguments), expected_error);
+    }
+
+    #[rstest]
+    #[case::upper(Filter::Upper, "Hello", "HELLO")]
+    #[case::lower(Filter::Lower, "Hello", "hello")]
+    #[case::trim(Filter::Trim, "  hello\n", "hello")]
+    #[case::base64(Filter::Base64, "hello!", "aGVsbG8h")]
+    #[case::url_encode(Filter::UrlEncode, "a b/c", "a%20b%2Fc")]
+    fn test_filter(
+        #[case] filter: Filter,
+        #[case] value: &str,
+        #[case] expected: &str,
+    ) {
+        assert_eq!(filter.apply(value.to_owned()), expected);
+    }
+}
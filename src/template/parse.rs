@@ -1,18 +1,29 @@
 //! Template string parser
 
-use crate::template::{error::TemplateParseError, Template, TemplateKey};
+use crate::template::{
+    error::TemplateParseError, Expression, Filter, Function, Operand, Template,
+    TemplateKey,
+};
 use nom::{
     branch::alt,
-    bytes::complete::{tag, take_while1},
-    combinator::{all_consuming, cut},
-    error::{context, ErrorKind, ParseError, VerboseError},
-    multi::many0,
-    sequence::{preceded, terminated},
-    FindSubstring, Finish, IResult, InputLength, InputTake, Offset, Parser,
+    bytes::complete::{tag, take_while, take_while1},
+    character::complete::{char, digit1, multispace0},
+    combinator::{
+        cut, eof, map_res, not, opt, peek, recognize, success, verify,
+    },
+    error::{context, ContextError, ErrorKind, ParseError, VerboseError},
+    multi::{many0, separated_list0},
+    sequence::{delimited, pair, preceded, separated_pair, terminated},
+    Finish, IResult, Offset, Parser,
 };
 
 const KEY_OPEN: &str = "{{";
 const KEY_CLOSE: &str = "}}";
+const BLOCK_OPEN: &str = "{%";
+const BLOCK_CLOSE: &str = "%}";
+const FALLBACK: &str = "??";
+/// Prefix for metadata variables inside a `{% for %}` block, e.g. `loop.index`
+const LOOP_PREFIX: &str = "loop.";
 // Export these so they can be used in TemplateKey's Display impl
 pub const CHAIN_PREFIX: &str = "chains.";
 pub const ENV_PREFIX: &str = "env.";
@@ -37,22 +48,111 @@ impl Template {
     }
 }
 
-/// A parsed piece of a template. After parsing, each chunk is either raw text
-/// or a parsed key, ready to be rendered.
-#[derive(Copy, Clone, Debug)]
+/// A parsed piece of a template. After parsing, each chunk is either raw text,
+/// a parsed key, or a block containing more chunks, ready to be rendered.
+#[derive(Clone, Debug)]
 #[cfg_attr(test, derive(PartialEq))]
 pub enum TemplateInputChunk<T> {
     Raw(T),
     Key(TemplateKey<T>),
+    Block(Box<TemplateBlock<T>>),
 }
 
-impl<T> TemplateInputChunk<T> {
+/// A control flow block, which renders its body zero or more times. The
+/// block tags themselves never appear in the output.
+#[derive(Clone, Debug)]
+#[cfg_attr(test, derive(PartialEq))]
+pub enum TemplateBlock<T> {
+    /// `{% if condition %}...{% else %}...{% endif %}`. Render the body if the
+    /// condition is truthy (or falsy, if `negated`), otherwise render the
+    /// `else` body
+    If {
+        condition: TemplateKey<T>,
+        /// Was the condition prefixed with `not`?
+        negated: bool,
+        body: Vec<TemplateInputChunk<T>>,
+        else_body: Vec<TemplateInputChunk<T>>,
+    },
+    /// `{% for variable in iterable %}...{% endfor %}`. The iterable must
+    /// render to a JSON array, and the body is rendered once per element
+    For {
+        variable: T,
+        iterable: TemplateKey<T>,
+        body: Vec<TemplateInputChunk<T>>,
+    },
+}
+
+impl<T: Copy> TemplateInputChunk<T> {
     /// Map the internal data using the given function. Useful for mapping
     /// string slices to spans and vice versa.
-    fn map<U>(self, f: impl Fn(T) -> U) -> TemplateInputChunk<U> {
+    pub(super) fn map<U>(&self, f: impl Fn(T) -> U) -> TemplateInputChunk<U> {
+        self.map_dyn(&f)
+    }
+
+    /// [Self::map], but with a trait object so it can recurse into blocks
+    fn map_dyn<U>(&self, f: &dyn Fn(T) -> U) -> TemplateInputChunk<U> {
+        let map_all = |chunks: &[Self]| {
+            chunks.iter().map(|chunk| chunk.map_dyn(f)).collect()
+        };
         match self {
-            Self::Raw(value) => TemplateInputChunk::Raw(f(value)),
-            Self::Key(key) => TemplateInputChunk::Key(key.map(f)),
+            Self::Raw(value) => TemplateInputChunk::Raw(f(*value)),
+            Self::Key(key) => TemplateInputChunk::Key(key.map_dyn(f)),
+            Self::Block(block) => {
+                let block = match &**block {
+                    TemplateBlock::If {
+                        condition,
+                        negated,
+                        body,
+                        else_body,
+                    } => TemplateBlock::If {
+                        condition: condition.map_dyn(f),
+                        negated: *negated,
+                        body: map_all(body),
+                        else_body: map_all(else_body),
+                    },
+                    TemplateBlock::For {
+                        variable,
+                        iterable,
+                        body,
+                    } => TemplateBlock::For {
+                        variable: f(*variable),
+                        iterable: iterable.map_dyn(f),
+                        body: map_all(body),
+                    },
+                };
+                TemplateInputChunk::Block(Box::new(block))
+            }
+        }
+    }
+}
+
+impl<'a> TemplateInputChunk<&'a str> {
+    /// Mark every reference to a `{% for %}` loop variable in this chunk as a
+    /// [TemplateKey::Variable], rather than a profile field. Nested loops bind
+    /// their own variable first, so inner variables shadow outer ones.
+    fn bind_variable(&mut self, variable: &str) {
+        match self {
+            Self::Raw(_) => {}
+            Self::Key(key) => key.bind_variable(variable),
+            Self::Block(block) => match &mut **block {
+                TemplateBlock::If {
+                    condition,
+                    body,
+                    else_body,
+                    ..
+                } => {
+                    condition.bind_variable(variable);
+                    for chunk in body.iter_mut().chain(else_body) {
+                        chunk.bind_variable(variable);
+                    }
+                }
+                TemplateBlock::For { iterable, body, .. } => {
+                    iterable.bind_variable(variable);
+                    for chunk in body {
+                        chunk.bind_variable(variable);
+                    }
+                }
+            },
         }
     }
 }
@@ -83,17 +183,124 @@ impl Span {
     }
 }
 
-/// Parse a template into keys and raw text
+/// Parse a template into keys, blocks, and raw text
 fn all_chunks(input: &str) -> ParseResult<Vec<TemplateInputChunk<&str>>> {
-    all_consuming(many0(alt((
+    // The only thing that can be left over is a tag that closes a block we
+    // never opened, e.g. a stray `{% endif %}`
+    terminated(chunks, context("block", eof))(input)
+}
+
+/// Parse a sequence of keys, blocks, and raw text. This stops at the end of
+/// input or at a tag that closes/continues a block (e.g. `{% endfor %}`), so
+/// the parent block can handle it
+fn chunks(input: &str) -> ParseResult<Vec<TemplateInputChunk<&str>>> {
+    many0(alt((
         key.map(TemplateInputChunk::Key),
+        block.map(|block| TemplateInputChunk::Block(Box::new(block))),
         raw.map(TemplateInputChunk::Raw),
-    ))))(input)
+    )))(input)
 }
 
-/// Parse raw text, until we hit a key or end of input
+/// Parse raw text, until we hit a key, block, or end of input. A `{%` that
+/// doesn't start a block we know about (e.g. `{% response %}` from an
+/// Insomnia import) is just raw text, but a closing tag is left for its block.
 fn raw(input: &str) -> ParseResult<&str> {
-    context("raw", take_until_or_eof(KEY_OPEN))(input)
+    context(
+        "raw",
+        verify(
+            recognize(pair(
+                opt(preceded(not(closing_tag), tag(BLOCK_OPEN))),
+                take_until_or_eof(&[KEY_OPEN, BLOCK_OPEN]),
+            )),
+            |raw: &str| !raw.is_empty(),
+        ),
+    )(input)
+}
+
+/// Parse a control flow block, e.g. `{% if field %}...{% endif %}`
+fn block(input: &str) -> ParseResult<TemplateBlock<&str>> {
+    alt((if_block, for_block))(input)
+}
+
+/// Parse an `if` block, with an optional `else` body. The condition can be
+/// any key contents, including an expression
+fn if_block(input: &str) -> ParseResult<TemplateBlock<&str>> {
+    context("if", |input| {
+        let (input, (negated, condition)) = block_tag(
+            "if",
+            pair(
+                opt(ws(keyword("not"))).map(|not| not.is_some()),
+                key_contents,
+            ),
+        )(input)?;
+        let (input, body) = chunks(input)?;
+        let (input, else_body) =
+            opt(preceded(block_tag("else", success(())), chunks))(input)?;
+        let (input, _) =
+            cut(context("endif", block_tag("endif", success(()))))(input)?;
+        Ok((
+            input,
+            TemplateBlock::If {
+                condition,
+                negated,
+                body,
+                else_body: else_body.unwrap_or_default(),
+            },
+        ))
+    })(input)
+}
+
+/// Parse a `for` block. References to the loop variable within the body are
+/// bound here, so they don't get treated as profile fields
+fn for_block(input: &str) -> ParseResult<TemplateBlock<&str>> {
+    context("for", |input| {
+        let (input, (variable, iterable)) = block_tag(
+            "for",
+            separated_pair(identifier, ws(keyword("in")), key_contents),
+        )(input)?;
+        let (input, mut body) = chunks(input)?;
+        let (input, _) =
+            cut(context("endfor", block_tag("endfor", success(()))))(input)?;
+        for chunk in &mut body {
+            chunk.bind_variable(variable);
+        }
+        Ok((
+            input,
+            TemplateBlock::For {
+                variable,
+                iterable,
+                body,
+            },
+        ))
+    })(input)
+}
+
+/// Parse a block tag with the given name, e.g. `{% for x in y %}`. Once the
+/// name is matched, any error in the rest of the tag is fatal
+fn block_tag<'a, T>(
+    name: &'static str,
+    contents: impl Parser<&'a str, T, VerboseError<&'a str>>,
+) -> impl FnMut(&'a str) -> ParseResult<'a, T> {
+    preceded(
+        pair(tag(BLOCK_OPEN), ws(keyword(name))),
+        cut(terminated(contents, tag(BLOCK_CLOSE))),
+    )
+}
+
+/// Check for a tag that closes or continues a block, without consuming it
+fn closing_tag(input: &str) -> ParseResult<&str> {
+    peek(preceded(
+        pair(tag(BLOCK_OPEN), multispace0),
+        alt((keyword("else"), keyword("endif"), keyword("endfor"))),
+    ))(input)
+}
+
+/// Parse a keyword. The keyword can't be followed directly by other
+/// identifier characters, e.g. `format` is *not* the keyword `for`
+fn keyword<'a>(
+    keyword: &'static str,
+) -> impl FnMut(&'a str) -> ParseResult<'a, &'a str> {
+    terminated(tag(keyword), not(take_while1(is_identifier_char)))
 }
 
 /// Parse a template key
@@ -109,9 +316,128 @@ fn key(input: &str) -> ParseResult<TemplateKey<&str>> {
     )(input)
 }
 
-/// Parse the contents of a key (inside the `{{ }}`)
+/// Parse the contents of a key (inside the `{{ }}`). This is either a plain
+/// key, or an expression with function calls, fallbacks, and/or filters.
+/// Whitespace around the contents is ignored.
 fn key_contents(input: &str) -> ParseResult<TemplateKey<&str>> {
+    let (input, operand) = preceded(multispace0, operand)(input)?;
+    // Fallbacks can be integers, because `{{ port ?? 8080 }}` is much more
+    // likely to be a default value than a field name
+    let (input, fallbacks) = many0(preceded(
+        ws(tag(FALLBACK)),
+        cut(context("fallback", argument)),
+    ))(input)?;
+    let (input, filters) = terminated(
+        many0(preceded(ws(char('|')), cut(filter))),
+        multispace0,
+    )(input)?;
+
+    // Plain keys are stored as-is so they render and match overrides the
+    // same as they always have
+    let key = match operand {
+        Operand::Key(key) if fallbacks.is_empty() && filters.is_empty() => key,
+        operand => TemplateKey::Expression(Box::new(Expression {
+            operand,
+            fallbacks,
+            filters,
+        })),
+    };
+    Ok((input, key))
+}
+
+/// Parse a single value within an expression: a literal, function call, or
+/// plain key
+fn operand(input: &str) -> ParseResult<Operand<&str>> {
     alt((
+        string_literal.map(Operand::String),
+        call,
+        simple_key.map(Operand::Key),
+    ))(input)
+}
+
+/// Parse a function argument. This is the same as [operand], but integers are
+/// also allowed. Outside of arguments, a number is a field name.
+fn argument(input: &str) -> ParseResult<Operand<&str>> {
+    alt((integer_literal.map(Operand::Integer), operand))(input)
+}
+
+/// Parse a function call, e.g. `now("%Y")`. The function name and argument
+/// count are validated here, so errors can point to the offending call.
+fn call(input: &str) -> ParseResult<Operand<&str>> {
+    // Look ahead for `name(`, so plain keys can fall through to the next
+    // parser. Once we've seen the paren, any error is fatal
+    peek(pair(identifier, ws(char('('))))(input)?;
+    let (rest, function) = cut(context(
+        "function",
+        map_res(identifier, |name: &str| name.parse::<Function>()),
+    ))(input)?;
+    let (rest, arguments) = cut(context(
+        "arguments",
+        delimited(
+            ws(char('(')),
+            separated_list0(ws(char(',')), argument),
+            ws(char(')')),
+        ),
+    ))(rest)?;
+
+    if !function.arity().contains(&arguments.len()) {
+        return Err(nom::Err::Failure(VerboseError::add_context(
+            input,
+            function.arity_message(),
+            VerboseError::from_error_kind(input, ErrorKind::Verify),
+        )));
+    }
+    Ok((
+        rest,
+        Operand::Call {
+            function,
+            arguments,
+        },
+    ))
+}
+
+/// Parse a filter name, after a `|`
+fn filter(input: &str) -> ParseResult<Filter> {
+    context(
+        "filter",
+        map_res(identifier, |name: &str| name.parse::<Filter>()),
+    )(input)
+}
+
+/// Parse a double-quoted string literal, returning its contents. There are no
+/// escape sequences, so the string can't contain a `"`
+fn string_literal(input: &str) -> ParseResult<&str> {
+    context(
+        "string",
+        preceded(
+            char('"'),
+            cut(terminated(take_while(|c| c != '"'), char('"'))),
+        ),
+    )(input)
+}
+
+/// Parse an integer literal, e.g. `-3`. A number followed by other identifier
+/// characters (e.g. `1abc`) is *not* an integer.
+fn integer_literal(input: &str) -> ParseResult<&str> {
+    context(
+        "integer",
+        terminated(
+            recognize(pair(opt(char('-')), digit1)),
+            not(take_while1(is_identifier_char)),
+        ),
+    )(input)
+}
+
+/// Parse a plain key: a field, chain, environment variable, or loop metadata
+/// variable. Loop item variables are parsed as fields, and bound later by
+/// their `{% for %}` block.
+fn simple_key(input: &str) -> ParseResult<TemplateKey<&str>> {
+    alt((
+        context(
+            "variable",
+            recognize(preceded(tag(LOOP_PREFIX), identifier))
+                .map(TemplateKey::Variable),
+        ),
         context(
             "chain",
             preceded(tag(CHAIN_PREFIX), identifier).map(TemplateKey::Chain),
@@ -126,25 +452,34 @@ fn key_contents(input: &str) -> ParseResult<TemplateKey<&str>> {
 
 /// Parse a field name/chain ID/env variable etc, inside a key
 fn identifier(input: &str) -> ParseResult<&str> {
-    context(
-        "identifier",
-        take_while1(|c: char| c.is_alphanumeric() || "-_".contains(c)),
-    )(input)
+    context("identifier", take_while1(is_identifier_char))(input)
+}
+
+/// Can this character appear in an identifier?
+fn is_identifier_char(c: char) -> bool {
+    c.is_alphanumeric() || "-_".contains(c)
+}
+
+/// Wrap a parser to ignore whitespace on either side of it
+fn ws<'a, T>(
+    parser: impl Parser<&'a str, T, VerboseError<&'a str>>,
+) -> impl FnMut(&'a str) -> ParseResult<'a, T> {
+    delimited(multispace0, parser, multispace0)
 }
 
-/// A copy pasta of nom's `take_until` that will take up to the end of a string
-/// if the terminator never appears, instead of erroring out. I couldn't
-/// figure out how to do this with other combinators, so here we go
+/// A take on nom's `take_until` that stops at the *first* of any of the given
+/// terminators, and will take up to the end of a string if none of them
+/// appear, instead of erroring out. This can return an empty string.
 fn take_until_or_eof<'a>(
-    tag: &'a str,
+    tags: &'a [&'a str],
 ) -> impl Fn(&'a str) -> ParseResult<&'a str> {
-    |i| match i.find_substring(tag) {
-        Some(index) => Ok(i.take_split(index)),
-        None if i.input_len() > 0 => Ok(i.take_split(i.input_len())),
-        None => Err(nom::Err::Error(VerboseError::from_error_kind(
-            i,
-            ErrorKind::TakeUntil,
-        ))),
+    |i| {
+        let index = tags
+            .iter()
+            .filter_map(|tag| i.find(tag))
+            .min()
+            .unwrap_or(i.len());
+        Ok((&i[index..], &i[..index]))
     }
 }
 
@@ -173,6 +508,153 @@ mod tests {
         "{{env.ENV}}",
         vec![TemplateInputChunk::Key(TemplateKey::Environment("ENV"))]
     )]
+    #[case::whitespace(
+        "{{ field1 }}",
+        vec![TemplateInputChunk::Key(TemplateKey::Field("field1"))]
+    )]
+    #[case::function(
+        "{{ uuid() }}",
+        vec![TemplateInputChunk::Key(expression(
+            call(Function::Uuid, vec![]),
+            vec![],
+        ))]
+    )]
+    #[case::function_arguments(
+        "{{random_int( 1 ,-10 )}}",
+        vec![TemplateInputChunk::Key(expression(
+            call(
+                Function::RandomInt,
+                vec![Operand::Integer("1"), Operand::Integer("-10")],
+            ),
+            vec![],
+        ))]
+    )]
+    #[case::function_nested(
+        "{{ base64(now(\"}}\")) }}",
+        vec![TemplateInputChunk::Key(expression(
+            call(
+                Function::Base64,
+                vec![call(Function::Now, vec![Operand::String("}}")])],
+            ),
+            vec![],
+        ))]
+    )]
+    #[case::function_key_arguments(
+        "{{ random_int(chains.min, max) }}",
+        vec![TemplateInputChunk::Key(expression(
+            call(
+                Function::RandomInt,
+                vec![
+                    Operand::Key(TemplateKey::Chain("min")),
+                    Operand::Key(TemplateKey::Field("max")),
+                ],
+            ),
+            vec![],
+        ))]
+    )]
+    #[case::filters(
+        "{{ env.USER | trim|upper }}",
+        vec![TemplateInputChunk::Key(expression(
+            Operand::Key(TemplateKey::Environment("USER")),
+            vec![Filter::Trim, Filter::Upper],
+        ))]
+    )]
+    #[case::string_filter(
+        "{{\"hello\" | base64}}",
+        vec![TemplateInputChunk::Key(expression(
+            Operand::String("hello"),
+            vec![Filter::Base64],
+        ))]
+    )]
+    #[case::fallback(
+        "{{ field1 ?? \"default\" }}",
+        vec![TemplateInputChunk::Key(fallback(
+            Operand::Key(TemplateKey::Field("field1")),
+            vec![Operand::String("default")],
+            vec![],
+        ))]
+    )]
+    #[case::fallback_multiple(
+        "{{env.PORT??chains.port ?? 8080 | trim}}",
+        vec![TemplateInputChunk::Key(fallback(
+            Operand::Key(TemplateKey::Environment("PORT")),
+            vec![
+                Operand::Key(TemplateKey::Chain("port")),
+                Operand::Integer("8080"),
+            ],
+            vec![Filter::Trim],
+        ))]
+    )]
+    #[case::if_block(
+        "{% if not field1 | trim %}a{{b}}{% else %}c{%endif%}",
+        vec![TemplateInputChunk::Block(Box::new(TemplateBlock::If {
+            condition: expression(
+                Operand::Key(TemplateKey::Field("field1")),
+                vec![Filter::Trim],
+            ),
+            negated: true,
+            body: vec![
+                TemplateInputChunk::Raw("a"),
+                TemplateInputChunk::Key(TemplateKey::Field("b")),
+            ],
+            else_body: vec![TemplateInputChunk::Raw("c")],
+        }))]
+    )]
+    #[case::for_block(
+        "{%for id in chains.ids%}{{id}}{{ id ?? other }}{{loop.index}}\
+        {% endfor %}",
+        vec![TemplateInputChunk::Block(Box::new(TemplateBlock::For {
+            variable: "id",
+            iterable: TemplateKey::Chain("ids"),
+            body: vec![
+                TemplateInputChunk::Key(TemplateKey::Variable("id")),
+                TemplateInputChunk::Key(fallback(
+                    Operand::Key(TemplateKey::Variable("id")),
+                    vec![Operand::Key(TemplateKey::Field("other"))],
+                    vec![],
+                )),
+                TemplateInputChunk::Key(TemplateKey::Variable("loop.index")),
+            ],
+        }))]
+    )]
+    #[case::nested_blocks(
+        "{% for a in as %}{% if a %}{% for b in a %}{{a}}{{b}}{% endfor %}\
+        {% endif %}{% endfor %}",
+        vec![TemplateInputChunk::Block(Box::new(TemplateBlock::For {
+            variable: "a",
+            iterable: TemplateKey::Field("as"),
+            body: vec![TemplateInputChunk::Block(Box::new(TemplateBlock::If {
+                condition: TemplateKey::Variable("a"),
+                negated: false,
+                body: vec![TemplateInputChunk::Block(Box::new(
+                    TemplateBlock::For {
+                        variable: "b",
+                        iterable: TemplateKey::Variable("a"),
+                        body: vec![
+                            TemplateInputChunk::Key(TemplateKey::Variable("a")),
+                            TemplateInputChunk::Key(TemplateKey::Variable("b")),
+                        ],
+                    }
+                ))],
+                else_body: vec![],
+            }))],
+        }))]
+    )]
+    // Unknown block tags are left as raw text, for compatibility with
+    // other template formats
+    #[case::unknown_block(
+        "{% response 'body' %}{{a}}{%{{b}}",
+        vec![
+            TemplateInputChunk::Raw("{% response 'body' %}"),
+            TemplateInputChunk::Key(TemplateKey::Field("a")),
+            TemplateInputChunk::Raw("{%"),
+            TemplateInputChunk::Key(TemplateKey::Field("b")),
+        ]
+    )]
+    #[case::keyword_prefix(
+        "{% format %}",
+        vec![TemplateInputChunk::Raw("{% format %}")]
+    )]
     #[case::utf8(
         "intro\n{{user_id}} 💚💙💜 {{chains.chain}}\noutro\r\nmore outro",
         vec![
@@ -208,8 +690,103 @@ mod tests {
     #[case::invalid_dotted_key("{{bogus.one}}")]
     #[case::invalid_chain("{{chains.one.two}}")]
     #[case::invalid_env("{{env.one.two}}")]
-    #[case::whitespace("{{ field }}")]
+    #[case::unknown_function("{{ bogus() }}")]
+    #[case::unclosed_call("{{ uuid( }}")]
+    #[case::too_many_arguments("{{ uuid(1) }}")]
+    #[case::too_few_arguments("{{ random_int(1) }}")]
+    #[case::unknown_filter("{{ field | bogus }}")]
+    #[case::empty_filter("{{ field | }}")]
+    #[case::unclosed_string("{{ base64(\"abc) }}")]
+    #[case::empty_fallback("{{ field ?? }}")]
+    #[case::fallback_after_filter("{{ field | upper ?? \"a\" }}")]
+    #[case::unclosed_if("{% if a %}b")]
+    #[case::unclosed_for("{% for a in b %}{{a}}")]
+    #[case::unclosed_if_tag("{% if a }}b{% endif %}")]
+    #[case::empty_condition("{% if %}b{% endif %}")]
+    #[case::missing_in("{% for a b %}{% endfor %}")]
+    #[case::stray_endif("a{% endif %}")]
+    #[case::stray_else("a{% else %}b")]
+    #[case::mismatched_end("{% if a %}{% endfor %}")]
+    #[case::invalid_key_in_block("{% if a %}{{.}}{% endif %}")]
     fn test_parse_error(#[case] template: &str) {
         assert_err!(Template::parse(template.into()), "at line 1");
     }
+
+    /// Test that expression errors point to the offending function/filter
+    #[rstest]
+    #[case::unknown_function(
+        "{{ bogus() }}",
+        "in function:\n{{ bogus() }}\n   ^"
+    )]
+    #[case::arity(
+        "{{ now(1, 2) }}",
+        "in now() takes an optional format argument:\n{{ now(1, 2) }}\n   ^"
+    )]
+    #[case::unknown_filter(
+        "{{ a | bogus }}",
+        "in filter:\n{{ a | bogus }}\n       ^"
+    )]
+    #[case::unclosed_block(
+        "a\n{% for x in y %}\n{{x}}",
+        "in endfor:\n{{x}}\n     ^\n\n2: at line 2, in for:\n{% for x in y %}\n^"
+    )]
+    #[case::key_in_block(
+        "{% if a %}\n  {{ a | bogus }}\n{% endif %}",
+        "in filter:\n  {{ a | bogus }}\n         ^"
+    )]
+    #[case::stray_endif(
+        "a\n{% endif %}",
+        "at line 2, in block:\n{% endif %}\n^"
+    )]
+    fn test_parse_error_span(#[case] template: &str, #[case] expected: &str) {
+        assert_err!(Template::parse(template.into()), expected);
+    }
+
+    /// Test that the parsed key displays the same as its source, minus
+    /// whitespace. This is used to match overrides
+    #[rstest]
+    #[case::field("{{ field1 }}", "field1")]
+    #[case::function(
+        "{{ random_int(1,chains.max) }}",
+        "random_int(1, chains.max)"
+    )]
+    #[case::filters(
+        "{{ now(\"%Y\")|upper | trim }}",
+        "now(\"%Y\") | upper | trim"
+    )]
+    #[case::fallback("{{ a??b ?? 3|upper }}", "a ?? b ?? 3 | upper")]
+    fn test_display(#[case] template: &str, #[case] expected: &str) {
+        let template = Template::parse(template.to_owned()).unwrap();
+        let keys = template.keys().map(|key| key.to_string()).collect_vec();
+        assert_eq!(keys, vec![expected]);
+    }
+
+    fn expression(
+        operand: Operand<&str>,
+        filters: Vec<Filter>,
+    ) -> TemplateKey<&str> {
+        fallback(operand, vec![], filters)
+    }
+
+    fn fallback<'a>(
+        operand: Operand<&'a str>,
+        fallbacks: Vec<Operand<&'a str>>,
+        filters: Vec<Filter>,
+    ) -> TemplateKey<&'a str> {
+        TemplateKey::Expression(Box::new(Expression {
+            operand,
+            fallbacks,
+            filters,
+        }))
+    }
+
+    fn call(
+        function: Function,
+        arguments: Vec<Operand<&str>>,
+    ) -> Operand<&str> {
+        Operand::Call {
+            function,
+            arguments,
+        }
+    }
 }
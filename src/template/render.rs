@@ -1,18 +1,26 @@
 //! Template rendering implementation
 
 use crate::{
-    collection::{ChainId, ChainRequestTrigger, ChainSource, RecipeId},
+    collection::{
+        ChainId, ChainRequestSection, ChainRequestTrigger, ChainSource,
+        ChainTransform, HashAlgorithm, RecipeId,
+    },
     http::{ContentType, RequestBuilder, RequestRecord, Response},
     template::{
-        error::TriggeredRequestError, parse::TemplateInputChunk, ChainError,
-        Prompt, Template, TemplateChunk, TemplateContext, TemplateError,
-        TemplateKey, RECURSION_LIMIT,
+        error::TriggeredRequestError,
+        parse::{Span, TemplateBlock, TemplateInputChunk},
+        ChainError, Expression, Operand, Prompt, Template, TemplateChunk,
+        TemplateContext, TemplateError, TemplateKey, RECURSION_LIMIT,
     },
-    util::ResultExt,
+    util::{uri_encode, ResultExt},
 };
 use async_trait::async_trait;
+use base64::{prelude::BASE64_STANDARD, Engine};
 use chrono::Utc;
-use futures::future;
+use futures::{future, future::BoxFuture, FutureExt};
+use itertools::Itertools;
+use md5::Md5;
+use sha2::{Digest, Sha256};
 use std::{
     env,
     path::PathBuf,
@@ -23,14 +31,36 @@ use tracing::{debug, debug_span, instrument, trace};
 
 /// Outcome of rendering a single chunk. This allows attaching some metadata to
 /// the render.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 struct RenderedChunk {
     value: String,
     sensitive: bool,
+    /// Did the key fail to render, and a fallback value was used instead?
+    fallback: bool,
 }
 
 type TemplateResult = Result<RenderedChunk, TemplateError>;
 
+/// Variables bound by enclosing `{% for %}` blocks. Each loop iteration gets
+/// its own scope, which links back to the scope around the loop.
+#[derive(Debug, Default)]
+struct Scope<'a> {
+    parent: Option<&'a Scope<'a>>,
+    /// The loop item and `loop.*` metadata for a single iteration
+    variables: Vec<(&'a str, RenderedChunk)>,
+}
+
+impl<'a> Scope<'a> {
+    /// Get the value of a variable, starting at the innermost loop
+    fn get(&self, variable: &str) -> Option<RenderedChunk> {
+        self.variables
+            .iter()
+            .find(|(name, _)| *name == variable)
+            .map(|(_, value)| value.clone())
+            .or_else(|| self.parent?.get(variable))
+    }
+}
+
 impl Template {
     /// Render the template string using values from the given context. If an
     /// error occurs, it is returned as general `anyhow` error. If you need a
@@ -58,66 +88,172 @@ impl Template {
         }
     }
 
+    /// Render the template string, and also return whether any part of the
+    /// output came from a sensitive value (e.g. a secret). This allows
+    /// sensitive parts of a request to be redacted when it's exported.
+    pub async fn render_sensitive(
+        &self,
+        context: &TemplateContext,
+    ) -> anyhow::Result<(String, bool)> {
+        self.render_stitched_chunk(context)
+            .await
+            .map(|chunk| (chunk.value, chunk.sensitive))
+            .map_err(anyhow::Error::from)
+            .traced()
+    }
+
     /// Render the template string using values from the given context,
     /// returning the individual rendered chunks. This is useful in any
     /// application where rendered chunks need to be handled differently from
     /// raw chunks, e.g. in render previews.
+    ///
+    /// Blocks are expanded into the chunks of whatever they render, so the
+    /// block tags themselves never appear in the output. The body of a loop
+    /// appears once per iteration, meaning the same raw span may appear
+    /// multiple times.
     #[instrument(skip_all, fields(template = self.template))]
     pub async fn render_chunks(
         &self,
         context: &TemplateContext,
     ) -> Vec<TemplateChunk> {
+        let scope = Scope::default();
+        self.render_input_chunks(&self.chunks, context, &scope)
+            .await
+    }
+
+    /// Render a sequence of parsed chunks, with some loop variables in scope.
+    /// Boxed because it's recursive.
+    fn render_input_chunks<'a>(
+        &'a self,
+        chunks: &'a [TemplateInputChunk<Span>],
+        context: &'a TemplateContext,
+        scope: &'a Scope<'a>,
+    ) -> BoxFuture<'a, Vec<TemplateChunk>> {
         // Map over each parsed chunk, and render the keys into strings. The
         // raw text chunks will be mapped 1:1
-        let futures = self.chunks.iter().copied().map(|chunk| async move {
+        let futures = chunks.iter().map(|chunk| async move {
             match chunk {
-                TemplateInputChunk::Raw(span) => TemplateChunk::Raw(span),
+                TemplateInputChunk::Raw(span) => {
+                    vec![TemplateChunk::Raw(*span)]
+                }
                 TemplateInputChunk::Key(key) => {
-                    // Grab the string corresponding to the span
-                    let key = key.map(|span| self.substring(span));
-
-                    // The formatted key should match the source that it was
-                    // parsed from, therefore we can use it to match the
-                    // override key
-                    let raw = key.to_string();
-                    // If the key is in the overrides, use the given value
-                    // without parsing it
-                    let result = match context.overrides.get(&raw) {
-                        Some(value) => {
-                            trace!(
-                                key = raw,
-                                value,
-                                "Rendered template key from override"
-                            );
-                            Ok(RenderedChunk {
-                                value: value.clone(),
-                                // The overriden value *could* be marked
-                                // sensitive, but we're taking a shortcut and
-                                // assuming it isn't
-                                sensitive: false,
-                            })
-                        }
-                        None => {
-                            // Standard case - parse the key and render it
-                            let result =
-                                key.into_source().render(context).await;
-                            if let Ok(value) = &result {
-                                trace!(
-                                    key = raw,
-                                    ?value,
-                                    "Rendered template key"
-                                );
-                            }
-                            result
-                        }
-                    };
-                    result.into()
+                    vec![self.render_key(key, context, scope).await]
+                }
+                TemplateInputChunk::Block(block) => {
+                    self.render_block(block, context, scope).await
                 }
             }
         });
 
         // Parallelization!
-        future::join_all(futures).await
+        future::join_all(futures)
+            .map(|chunks| chunks.into_iter().flatten().collect())
+            .boxed()
+    }
+
+    /// Render a single key into a chunk
+    async fn render_key<'a>(
+        &'a self,
+        key: &'a TemplateKey<Span>,
+        context: &'a TemplateContext,
+        scope: &'a Scope<'a>,
+    ) -> TemplateChunk {
+        // Grab the string corresponding to the span
+        let key = key.map(|span| self.substring(span));
+        key.render(context, scope).await.into()
+    }
+
+    /// Render a block into the chunks of its body. If the block's condition or
+    /// iterable fails to render, the whole block becomes a single error chunk
+    async fn render_block<'a>(
+        &'a self,
+        block: &'a TemplateBlock<Span>,
+        context: &'a TemplateContext,
+        scope: &'a Scope<'a>,
+    ) -> Vec<TemplateChunk> {
+        match block {
+            TemplateBlock::If {
+                condition,
+                negated,
+                body,
+                else_body,
+            } => {
+                let condition = condition.map(|span| self.substring(span));
+                let value = match condition.render(context, scope).await {
+                    Ok(chunk) => chunk.value,
+                    Err(error) => return vec![TemplateChunk::Error(error)],
+                };
+                let body = if is_truthy(&value) != *negated {
+                    body
+                } else {
+                    else_body
+                };
+                self.render_input_chunks(body, context, scope).await
+            }
+            TemplateBlock::For {
+                variable,
+                iterable,
+                body,
+            } => {
+                let iterable = iterable.map(|span| self.substring(span));
+                let key = iterable.to_string();
+                let RenderedChunk {
+                    value, sensitive, ..
+                } = match iterable.render(context, scope).await {
+                    Ok(chunk) => chunk,
+                    Err(error) => return vec![TemplateChunk::Error(error)],
+                };
+                let Ok(items) =
+                    serde_json::from_str::<Vec<serde_json::Value>>(&value)
+                else {
+                    return vec![TemplateChunk::Error(
+                        TemplateError::InvalidIterable { key },
+                    )];
+                };
+
+                // Each iteration gets its own scope, and they can all be
+                // rendered concurrently
+                let variable = self.substring(*variable);
+                let len = items.len();
+                let futures =
+                    items.into_iter().enumerate().map(|(i, item)| async move {
+                        let value = match item {
+                            serde_json::Value::String(value) => value,
+                            value => value.to_string(),
+                        };
+                        let chunk =
+                            |value: String, sensitive: bool| RenderedChunk {
+                                value,
+                                sensitive,
+                                fallback: false,
+                            };
+                        let scope = Scope {
+                            parent: Some(scope),
+                            variables: vec![
+                                (variable, chunk(value, sensitive)),
+                                (
+                                    "loop.index",
+                                    chunk((i + 1).to_string(), false),
+                                ),
+                                (
+                                    "loop.first",
+                                    chunk((i == 0).to_string(), false),
+                                ),
+                                (
+                                    "loop.last",
+                                    chunk((i == len - 1).to_string(), false),
+                                ),
+                            ],
+                        };
+                        self.render_input_chunks(body, context, &scope).await
+                    });
+                future::join_all(futures)
+                    .await
+                    .into_iter()
+                    .flatten()
+                    .collect()
+            }
+        }
     }
 
     /// Helper for stitching chunks together into a single string. If any chunk
@@ -126,6 +262,18 @@ impl Template {
         &self,
         context: &TemplateContext,
     ) -> Result<String, TemplateError> {
+        self.render_stitched_chunk(context)
+            .await
+            .map(|chunk| chunk.value)
+    }
+
+    /// Same as [Self::render_stitched], but the output is marked sensitive if
+    /// any of the rendered chunks were sensitive. Used for nested renders,
+    /// so sensitivity propagates up.
+    async fn render_stitched_chunk(
+        &self,
+        context: &TemplateContext,
+    ) -> TemplateResult {
         debug!(template = self.template, "Rendering template");
 
         if context.recursion_count.load(Ordering::Relaxed) >= RECURSION_LIMIT {
@@ -137,18 +285,26 @@ impl Template {
 
         // Stitch the rendered chunks together into one string
         let mut buffer = String::with_capacity(self.template.len());
+        let mut any_sensitive = false;
         for chunk in chunks {
             match chunk {
                 TemplateChunk::Raw(span) => {
                     buffer.push_str(self.substring(span));
                 }
-                TemplateChunk::Rendered { value, .. } => {
-                    buffer.push_str(&value)
+                TemplateChunk::Rendered {
+                    value, sensitive, ..
+                } => {
+                    buffer.push_str(&value);
+                    any_sensitive |= sensitive;
                 }
                 TemplateChunk::Error(error) => return Err(error),
             }
         }
-        Ok(buffer)
+        Ok(RenderedChunk {
+            value: buffer,
+            sensitive: any_sensitive,
+            fallback: false,
+        })
     }
 }
 
@@ -158,15 +314,61 @@ impl From<TemplateResult> for TemplateChunk {
             Ok(outcome) => Self::Rendered {
                 value: outcome.value,
                 sensitive: outcome.sensitive,
+                fallback: outcome.fallback,
             },
             Err(error) => Self::Error(error),
         }
     }
 }
 
+/// Is a rendered value truthy, for an `{% if %}` block? Empty values and values
+/// that are falsy in JSON are false, ignoring surrounding whitespace.
+fn is_truthy(value: &str) -> bool {
+    !matches!(value.trim(), "" | "false" | "null" | "0" | "[]" | "{}")
+}
+
 impl<'a> TemplateKey<&'a str> {
+    /// Render this key into a string. If the key is in the overrides, use the
+    /// given value instead.
+    async fn render(
+        self,
+        context: &'a TemplateContext,
+        scope: &'a Scope<'a>,
+    ) -> TemplateResult {
+        // The formatted key should match the source that it was parsed from,
+        // therefore we can use it to match the override key
+        let raw = self.to_string();
+        // If the key is in the overrides, use the given value without parsing
+        // it. Loop variables always come from their loop, so an override with
+        // the same name can't shadow them
+        let override_value = match self {
+            Self::Variable(_) => None,
+            _ => context.overrides.get(&raw),
+        };
+        match override_value {
+            Some(value) => {
+                trace!(key = raw, value, "Rendered template key from override");
+                Ok(RenderedChunk {
+                    value: value.clone(),
+                    // The overriden value *could* be marked sensitive, but
+                    // we're taking a shortcut and assuming it isn't
+                    sensitive: false,
+                    fallback: false,
+                })
+            }
+            None => {
+                // Standard case - parse the key and render it
+                let result = self.into_source(scope).render(context).await;
+                if let Ok(value) = &result {
+                    trace!(key = raw, ?value, "Rendered template key");
+                }
+                result
+            }
+        }
+    }
+
     /// Convert this key into a renderable value type
-    fn into_source(self) -> Box<dyn TemplateSource<'a>> {
+    fn into_source(self, scope: &'a Scope<'a>) -> Box<dyn TemplateSource<'a>> {
         match self {
             Self::Field(field) => Box::new(FieldTemplateSource { field }),
             Self::Chain(chain_id) => Box::new(ChainTemplateSource {
@@ -175,6 +377,15 @@ impl<'a> TemplateKey<&'a str> {
             Self::Environment(variable) => {
                 Box::new(EnvironmentTemplateSource { variable })
             }
+            Self::Expression(expression) => {
+                Box::new(ExpressionTemplateSource {
+                    expression: *expression,
+                    scope,
+                })
+            }
+            Self::Variable(variable) => {
+                Box::new(VariableTemplateSource { variable, scope })
+            }
         }
     }
 }
@@ -217,25 +428,30 @@ impl<'a> TemplateSource<'a> for FieldTemplateSource<'a> {
                     profile_id: profile_id.clone(),
                 }
             })?;
-        let template = profile.data.get(field).ok_or_else(|| {
-            TemplateError::FieldUnknown {
+        // Fields can be inherited from parent profiles
+        let (source, template) = profile
+            .resolve_field(&context.collection.profiles, field)
+            .ok_or_else(|| TemplateError::FieldUnknown {
                 field: field.to_owned(),
-            }
-        })?;
+            })?;
 
         // recursion!
         trace!(%field, %template, "Rendering recursive template");
         context.recursion_count.fetch_add(1, Ordering::Relaxed);
         let rendered =
-            template.render_stitched(context).await.map_err(|error| {
-                TemplateError::FieldNested {
+            template
+                .render_stitched_chunk(context)
+                .await
+                .map_err(|error| TemplateError::FieldNested {
                     field: field.to_owned(),
                     error: Box::new(error),
-                }
-            })?;
+                })?;
         Ok(RenderedChunk {
-            value: rendered,
-            sensitive: false,
+            value: rendered.value,
+            // Secrets are sensitive, as is anything that uses them
+            sensitive: rendered.sensitive
+                || source.sensitive_fields.contains(field),
+            fallback: false,
         })
     }
 }
@@ -264,15 +480,14 @@ impl<'a> TemplateSource<'a> for ChainTemplateSource<'a> {
             // We intentionally throw the content detection error away here,
             // because it isn't that intuitive for users and is hard to plumb
             let (value, content_type) = match &chain.source {
-                ChainSource::Request { recipe, trigger } => {
+                ChainSource::Request {
+                    recipe,
+                    trigger,
+                    section,
+                } => {
                     let response =
                         self.get_response(context, recipe, *trigger).await?;
-                    // Guess content type based on HTTP header
-                    let content_type =
-                        ContentType::from_response(&response).ok();
-                    // This will clone the bytes, which is necessary for the
-                    // string conversion below anyway
-                    (response.body.into_bytes().into(), content_type)
+                    Self::response_section(response, section)?
                 }
                 ChainSource::File { path } => {
                     self.render_file(context, path).await?
@@ -313,9 +528,18 @@ impl<'a> TemplateSource<'a> for ChainTemplateSource<'a> {
                     .map_err(|error| ChainError::InvalidUtf8 { error })?
             };
 
+            // Apply transforms in order
+            let value = chain
+                .transforms
+                .iter()
+                .try_fold(value, |value, transform| {
+                    Self::apply_transform(value, transform)
+                })?;
+
             Ok(RenderedChunk {
                 value,
                 sensitive: chain.sensitive,
+                fallback: false,
             })
         }
         .await;
@@ -424,6 +648,108 @@ impl<'a> ChainTemplateSource<'a> {
             .expect("Request Arc should have only one reference"))
     }
 
+    /// Extract one section of a response. Return its bytes, as well as its
+    /// content type if it's known
+    fn response_section(
+        response: Response,
+        section: &ChainRequestSection,
+    ) -> Result<(Vec<u8>, Option<ContentType>), ChainError> {
+        match section {
+            ChainRequestSection::Body => {
+                // Guess content type based on HTTP header
+                let content_type = ContentType::from_response(&response).ok();
+                // This will clone the bytes, which is necessary for the
+                // string conversion later anyway
+                Ok((response.body.into_bytes().into(), content_type))
+            }
+            ChainRequestSection::Status => {
+                Ok((response.status.as_u16().to_string().into_bytes(), None))
+            }
+            ChainRequestSection::Headers => {
+                // Repeated headers are joined into one value, which is
+                // equivalent according to the HTTP spec
+                let headers: serde_json::Map<_, _> = response
+                    .headers
+                    .keys()
+                    .map(|name| {
+                        let value = response
+                            .headers
+                            .get_all(name)
+                            .iter()
+                            .map(|value| {
+                                String::from_utf8_lossy(value.as_bytes())
+                            })
+                            .join(", ");
+                        (name.as_str().to_owned(), value.into())
+                    })
+                    .collect();
+                Ok((
+                    serde_json::Value::Object(headers).to_string().into_bytes(),
+                    Some(ContentType::Json),
+                ))
+            }
+            ChainRequestSection::Header(header) => {
+                let values = response.headers.get_all(header.as_str());
+                if values.iter().next().is_none() {
+                    return Err(ChainError::MissingHeader {
+                        header: header.clone(),
+                    });
+                }
+                let value = values
+                    .iter()
+                    .map(|value| value.as_bytes())
+                    .collect_vec()
+                    .join(b", ".as_slice());
+                Ok((value, None))
+            }
+        }
+    }
+
+    /// Apply a single transform to a chained value
+    fn apply_transform(
+        value: String,
+        transform: &ChainTransform,
+    ) -> Result<String, ChainError> {
+        match transform {
+            ChainTransform::Trim => Ok(value.trim().to_owned()),
+            ChainTransform::Base64Encode => Ok(BASE64_STANDARD.encode(value)),
+            ChainTransform::Base64Decode => {
+                let bytes = BASE64_STANDARD
+                    .decode(value.trim())
+                    .map_err(|error| ChainError::Base64Decode { error })?;
+                String::from_utf8(bytes)
+                    .map_err(|error| ChainError::InvalidUtf8 { error })
+            }
+            ChainTransform::UrlEncode => Ok(uri_encode(&value)),
+            ChainTransform::Hash(HashAlgorithm::Md5) => {
+                Ok(hex::encode(Md5::digest(value)))
+            }
+            ChainTransform::Hash(HashAlgorithm::Sha256) => {
+                Ok(hex::encode(Sha256::digest(value)))
+            }
+            ChainTransform::Regex(pattern) => {
+                let captures = pattern.captures(&value).ok_or_else(|| {
+                    ChainError::RegexNoMatch {
+                        pattern: pattern.to_string(),
+                    }
+                })?;
+                // Use the first group if there is one, otherwise the whole
+                // match. The whole match is always present
+                let group = captures
+                    .get(1)
+                    .or_else(|| captures.get(0))
+                    .map(|group| group.as_str())
+                    .unwrap_or_default();
+                Ok(group.to_owned())
+            }
+            ChainTransform::JsonEscape => {
+                // Serialize as a JSON string, then strip the quotes
+                let json = serde_json::Value::String(value).to_string();
+                Ok(json[1..json.len() - 1].to_owned())
+            }
+        }
+    }
+
     /// Render a chained value from a file. Return the files bytes, as well as
     /// its content type if it's known
     async fn render_file(
@@ -550,6 +876,128 @@ impl<'a> TemplateSource<'a> for EnvironmentTemplateSource<'a> {
         Ok(RenderedChunk {
             value,
             sensitive: false,
+            fallback: false,
+        })
+    }
+}
+
+/// A variable bound by an enclosing `{% for %}` block
+struct VariableTemplateSource<'a> {
+    variable: &'a str,
+    scope: &'a Scope<'a>,
+}
+
+#[async_trait]
+impl<'a> TemplateSource<'a> for VariableTemplateSource<'a> {
+    async fn render(&self, _: &'a TemplateContext) -> TemplateResult {
+        self.scope.get(self.variable).ok_or_else(|| {
+            TemplateError::VariableUnknown {
+                variable: self.variable.to_owned(),
+            }
         })
     }
 }
+
+/// A function call and/or filters, e.g. `{{ uuid() }}` or
+/// `{{ username | upper }}`
+struct ExpressionTemplateSource<'a> {
+    expression: Expression<&'a str>,
+    /// Loop variables, for any keys used in the expression
+    scope: &'a Scope<'a>,
+}
+
+#[async_trait]
+impl<'a> TemplateSource<'a> for ExpressionTemplateSource<'a> {
+    async fn render(&self, context: &'a TemplateContext) -> TemplateResult {
+        let mut chunk = self.render_with_fallbacks(context).await?;
+        for filter in &self.expression.filters {
+            chunk.value = filter.apply(chunk.value);
+        }
+        Ok(chunk)
+    }
+}
+
+impl<'a> ExpressionTemplateSource<'a> {
+    /// Render the operand. If it fails, try each fallback in order until one
+    /// succeeds. If they all fail, return the operand's error, because that's
+    /// the root cause.
+    async fn render_with_fallbacks(
+        &self,
+        context: &'a TemplateContext,
+    ) -> TemplateResult {
+        let operand = &self.expression.operand;
+        let error = match self.render_operand(operand, context).await {
+            Ok(chunk) => return Ok(chunk),
+            Err(error) => error,
+        };
+        for fallback in &self.expression.fallbacks {
+            match self.render_operand(fallback, context).await {
+                Ok(chunk) => {
+                    debug!(%error, "Rendered template fallback");
+                    return Ok(RenderedChunk {
+                        fallback: true,
+                        ..chunk
+                    });
+                }
+                Err(error) => {
+                    trace!(%error, "Template fallback failed");
+                }
+            }
+        }
+        Err(error)
+    }
+
+    /// Render a single operand. Function arguments are rendered concurrently.
+    /// Boxed because it's recursive.
+    fn render_operand<'b>(
+        &'b self,
+        operand: &'b Operand<&'a str>,
+        context: &'a TemplateContext,
+    ) -> BoxFuture<'b, TemplateResult>
+    where
+        'a: 'b,
+    {
+        async move {
+            match operand {
+                Operand::Key(key) => {
+                    key.clone().render(context, self.scope).await
+                }
+                Operand::String(value) | Operand::Integer(value) => {
+                    Ok(RenderedChunk {
+                        value: (*value).to_owned(),
+                        sensitive: false,
+                        fallback: false,
+                    })
+                }
+                Operand::Call {
+                    function,
+                    arguments,
+                } => {
+                    let arguments = future::try_join_all(arguments.iter().map(
+                        |argument| self.render_operand(argument, context),
+                    ))
+                    .await?;
+                    // Anything derived from a sensitive value is sensitive
+                    let sensitive =
+                        arguments.iter().any(|argument| argument.sensitive);
+                    let arguments = arguments
+                        .into_iter()
+                        .map(|argument| argument.value)
+                        .collect_vec();
+                    let value = function.call(&arguments).map_err(|error| {
+                        TemplateError::Function {
+                            function: *function,
+                            error,
+                        }
+                    })?;
+                    Ok(RenderedChunk {
+                        value,
+                        sensitive,
+                        fallback: false,
+                    })
+                }
+            }
+        }
+        .boxed()
+    }
+}
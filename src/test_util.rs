@@ -1,7 +1,7 @@
 use crate::{
     collection::{
         Chain, ChainSource, Collection, Folder, Profile, ProfileId, Recipe,
-        RecipeId, RecipeNode, RecipeTree,
+        RecipeBody, RecipeId, RecipeNode, RecipeTree,
     },
     db::CollectionDatabase,
     http::{Body, Request, RequestId, RequestRecord, Response},
@@ -17,6 +17,7 @@ use reqwest::{
 
 factori!(Collection, {
     default {
+        imports = Default::default(),
         profiles = Default::default(),
         chains = Default::default(),
         recipes = Default::default(),
@@ -28,7 +29,11 @@ factori!(Profile, {
     default {
         id = "profile1".into(),
         name = None,
+        extends = Default::default(),
+        env_file = None,
         data = Default::default(),
+        http = Default::default(),
+        sensitive_fields = Default::default(),
     }
 });
 
@@ -36,6 +41,12 @@ factori!(Folder, {
     default {
         id = "folder1".into(),
         name = None,
+        description = None,
+        tags = Default::default(),
+        url_prefix = None,
+        authentication = None,
+        query = Default::default(),
+        headers = Default::default(),
         children = Default::default(),
     }
 });
@@ -44,12 +55,18 @@ factori!(Recipe, {
     default {
         id = "recipe1".into(),
         name = None,
-        method = "GET".parse().unwrap(),
-        url = "http://localhost".into(),
+        description = None,
+        tags = Default::default(),
+        extends = None,
+        method = Some("GET".parse().unwrap()),
+        url = Some("http://localhost".into()),
+        path = Default::default(),
         body = None,
         authentication = None,
         query = Default::default(),
         headers = Default::default(),
+        http = Default::default(),
+        assertions = Default::default(),
     }
 });
 
@@ -62,6 +79,10 @@ factori!(Request, {
         url = "http://localhost/url".parse().unwrap(),
         headers = HeaderMap::new(),
         body = None,
+        authentication = None,
+        http_settings = Default::default(),
+        assertions = Default::default(),
+        redactions = Default::default(),
     }
 });
 
@@ -89,6 +110,7 @@ factori!(RequestRecord, {
         response = response().into(),
         start_time = Utc::now(),
         end_time = Utc::now(),
+        challenge = None,
     }
 });
 
@@ -98,10 +120,12 @@ factori!(Chain, {
         source = ChainSource::Request {
             recipe: RecipeId::default(),
             trigger: Default::default(),
+            section: Default::default(),
         },
         sensitive = false,
         selector = None,
         content_type = None,
+        transforms = Vec::new(),
     }
 });
 
@@ -172,6 +196,12 @@ impl From<&str> for Template {
 }
 // Can't implement this for From<String> because it conflicts with TryFrom
 
+impl From<&str> for RecipeBody {
+    fn from(value: &str) -> Self {
+        Self::Raw(value.into())
+    }
+}
+
 /// Helper for creating a header map
 pub fn header_map<'a>(
     headers: impl IntoIterator<Item = (&'a str, &'a str)>,
@@ -5,7 +5,7 @@ mod signal;
 mod view;
 
 use crate::{
-    collection::{Collection, CollectionFile, ProfileId, RecipeId},
+    collection::{CollectionFile, ProfileId, RecipeId},
     config::Config,
     db::Database,
     http::{Request, RequestBuilder},
@@ -17,7 +17,7 @@ use crate::{
         signal::signals,
         view::{ModalPriority, PreviewPrompter, RequestState, View},
     },
-    util::Replaceable,
+    util::{Replaceable, ResultExt},
 };
 use anyhow::{anyhow, Context};
 use crossterm::{
@@ -25,12 +25,15 @@ use crossterm::{
     terminal::{EnterAlternateScreen, LeaveAlternateScreen},
 };
 use futures::{Future, StreamExt};
-use notify::{event::ModifyKind, RecursiveMode, Watcher};
+use notify::{
+    event::ModifyKind, EventKind, RecommendedWatcher, RecursiveMode, Watcher,
+};
 use ratatui::{prelude::CrosstermBackend, Terminal};
 use std::{
+    collections::HashSet,
     io::{self, Stdout},
     ops::Deref,
-    path::PathBuf,
+    path::{Path, PathBuf},
     sync::{Arc, OnceLock},
     time::Duration,
 };
@@ -51,6 +54,10 @@ pub struct Tui {
     /// before the new one is created.
     view: Replaceable<View>,
     collection_file: CollectionFile,
+    /// Watches every file in the collection, and triggers a reload when any
+    /// of them changes. Stops watching when dropped. This is replaced on
+    /// each reload, because the set of imported files can change.
+    collection_watcher: Option<RecommendedWatcher>,
     should_run: bool,
 }
 
@@ -77,17 +84,18 @@ impl Tui {
         let database = Database::load()?.into_collection(&collection_path)?;
         // Initialize global view context
         TuiContext::init(config, messages_tx.clone(), database.clone());
+        let config = &TuiContext::get().config;
 
         // ===== Initialize collection & view =====
 
-        // If the collection fails to load, create an empty one just so we can
-        // move along. We'll watch the file and hopefully the user can fix it
-        let collection_file = CollectionFile::load(collection_path.clone())
-            .await
-            .unwrap_or_else(|error| {
-                TuiContext::send_message(Message::Error { error });
-                CollectionFile::with_path(collection_path)
-            });
+        // If the collection fails to load, we get an empty one just so we can
+        // move along. It still knows about every file found before the
+        // failure, so we'll watch those and hopefully the user can fix it
+        let (collection_file, error) =
+            CollectionFile::load_partial(collection_path, config).await;
+        if let Some(error) = error {
+            TuiContext::send_message(Message::Error { error });
+        }
         let view = View::new(&collection_file.collection);
 
         // The code to revert the terminal takeover is in `Tui::drop`, so we
@@ -100,6 +108,7 @@ impl Tui {
             messages_rx,
 
             collection_file,
+            collection_watcher: None,
             should_run: true,
 
             view: Replaceable::new(view),
@@ -113,8 +122,7 @@ impl Tui {
     /// run loop.
     async fn run(mut self) -> anyhow::Result<()> {
         self.listen_for_signals();
-        // Hang onto this because it stops running when dropped
-        let _watcher = self.watch_collection()?;
+        self.watch_collection(Vec::new())?;
 
         // Listen for input in a separate task
         tokio::spawn(Self::input_loop());
@@ -178,15 +186,30 @@ impl Tui {
             Message::CollectionStartReload => {
                 let future = self.collection_file.reload();
                 self.spawn(async move {
-                    let collection = future.await?;
-                    TuiContext::send_message(Message::CollectionEndReload(
-                        collection,
-                    ));
-                    Ok(())
+                    match future.await {
+                        (collection_file, None) => {
+                            TuiContext::send_message(
+                                Message::CollectionEndReload(collection_file),
+                            );
+                            Ok(())
+                        }
+                        // Keep the current collection, but watch any new
+                        // files that were found, so fixing them triggers
+                        // another reload
+                        (collection_file, Some(error)) => {
+                            TuiContext::send_message(Message::CollectionWatch(
+                                collection_file.watch_paths(),
+                            ));
+                            Err(error)
+                        }
+                    }
                 });
             }
-            Message::CollectionEndReload(collection) => {
-                self.reload_collection(collection);
+            Message::CollectionEndReload(collection_file) => {
+                self.reload_collection(collection_file);
+            }
+            Message::CollectionWatch(paths) => {
+                self.watch_collection(paths)?;
             }
             Message::CollectionEdit => {
                 let path = self.collection_file.path();
@@ -297,23 +320,51 @@ impl Tui {
         });
     }
 
-    /// Spawn a watcher to automatically reload the collection when the file
-    /// changes. Return the watcher because it stops when dropped.
-    fn watch_collection(&self) -> anyhow::Result<impl Watcher> {
-        // Spawn a watcher for the collection file
-        let f = move |result: notify::Result<_>| {
+    /// Spawn a watcher to automatically reload the collection when any of
+    /// its files (including profile env files and secrets) change, in
+    /// addition to the given paths. This replaces the previous watcher, if
+    /// any.
+    fn watch_collection(
+        &mut self,
+        extra_paths: Vec<PathBuf>,
+    ) -> anyhow::Result<()> {
+        // Drop the old watcher first, so we don't get duplicate events
+        self.collection_watcher = None;
+        let paths: HashSet<PathBuf> = self
+            .collection_file
+            .watch_paths()
+            .into_iter()
+            .chain(extra_paths)
+            .collect();
+        // Watch the directories rather than the files themselves, so we catch
+        // files that don't exist yet (e.g. a new secrets file) or that get
+        // replaced by an editor. Events for any other files are ignored.
+        let directories: HashSet<PathBuf> = paths
+            .iter()
+            .filter_map(|path| path.parent())
+            .map(Path::to_owned)
+            .collect();
+        let watched_paths = paths.clone();
+        let f = move |result: notify::Result<notify::Event>| {
             match result {
-                // Only reload if the file *content* changes
-                Ok(
-                    event @ notify::Event {
-                        kind: notify::EventKind::Modify(ModifyKind::Data(_)),
-                        ..
-                    },
-                ) => {
+                // Only reload if the file *content* changes, or a file is
+                // created/moved into place
+                Ok(event)
+                    if matches!(
+                        event.kind,
+                        EventKind::Create(_)
+                            | EventKind::Modify(
+                                ModifyKind::Data(_) | ModifyKind::Name(_)
+                            )
+                    ) && event
+                        .paths
+                        .iter()
+                        .any(|path| watched_paths.contains(path)) =>
+                {
                     info!(?event, "Collection file changed, reloading");
                     TuiContext::send_message(Message::CollectionStartReload);
                 }
-                // Do nothing for other event kinds
+                // Do nothing for other event kinds or files
                 Ok(_) => {}
                 Err(err) => {
                     error!(error = %err, "Error watching collection file");
@@ -321,18 +372,23 @@ impl Tui {
             }
         };
         let mut watcher = notify::recommended_watcher(f)?;
-        watcher
-            .watch(self.collection_file.path(), RecursiveMode::NonRecursive)?;
-        info!(
-            path = ?self.collection_file.path(), ?watcher,
-            "Watching collection file for changes"
-        );
-        Ok(watcher)
+        for directory in &directories {
+            watcher.watch(directory, RecursiveMode::NonRecursive)?;
+        }
+        info!(?paths, ?watcher, "Watching collection files for changes");
+        self.collection_watcher = Some(watcher);
+        Ok(())
     }
 
     /// Reload state with a new collection
-    fn reload_collection(&mut self, collection: Collection) {
-        self.collection_file.collection = collection;
+    fn reload_collection(&mut self, collection_file: CollectionFile) {
+        self.collection_file = collection_file;
+        // Imports may have changed, so watch the new set of files. If this
+        // fails, the old watcher is gone, but the view can still be updated
+        let _ = self
+            .watch_collection(Vec::new())
+            .context("Error watching collection files")
+            .traced();
 
         // Rebuild the whole view, because tons of things can change. Drop the
         // old one *first* to make sure UI state is saved before being restored
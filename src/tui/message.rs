@@ -2,7 +2,7 @@
 //! state updates.
 
 use crate::{
-    collection::{Collection, ProfileId, RecipeId},
+    collection::{CollectionFile, ProfileId, RecipeId},
     http::{
         RecipeOptions, Request, RequestBuildError, RequestError, RequestRecord,
     },
@@ -12,7 +12,10 @@ use crate::{
 };
 use anyhow::Context;
 use derive_more::From;
-use std::sync::{Arc, OnceLock};
+use std::{
+    path::PathBuf,
+    sync::{Arc, OnceLock},
+};
 use tokio::sync::mpsc::UnboundedSender;
 use tracing::trace;
 
@@ -55,7 +58,11 @@ pub enum Message {
     /// Trigger collection reload
     CollectionStartReload,
     /// Store a reloaded collection value in state
-    CollectionEndReload(Collection),
+    CollectionEndReload(CollectionFile),
+    /// Watch additional files for changes, alongside the current collection's
+    /// files. Used when a reload fails, so fixing a newly found file (e.g. an
+    /// invalid import) triggers another reload.
+    CollectionWatch(Vec<PathBuf>),
     /// Open the collection in the user's editor
     CollectionEdit,
 
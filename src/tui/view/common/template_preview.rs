@@ -118,6 +118,9 @@ impl<'a> TextStitcher<'a> {
             let chunk_text = Self::get_chunk_text(template, chunk);
             let style = match &chunk {
                 TemplateChunk::Raw(_) => Style::default(),
+                TemplateChunk::Rendered { fallback: true, .. } => {
+                    theme.template_preview.fallback
+                }
                 TemplateChunk::Rendered { .. } => theme.template_preview.text,
                 TemplateChunk::Error(_) => theme.template_preview.error,
             };
@@ -151,7 +154,9 @@ impl<'a> TextStitcher<'a> {
     ) -> &'a str {
         match chunk {
             TemplateChunk::Raw(span) => template.substring(*span),
-            TemplateChunk::Rendered { value, sensitive } => {
+            TemplateChunk::Rendered {
+                value, sensitive, ..
+            } => {
                 if *sensitive {
                     // Hide sensitive values. Ratatui has a Masked type, but
                     // it complicates the string ownership a lot and also
@@ -210,7 +215,9 @@ mod tests {
     async fn test_template_stitch(_tui_context: ()) {
         // Render a template
         let template = Template::parse(
-            "intro\n{{user_id}} 💚💙💜 {{unknown}}\noutro\r\nmore outro".into(),
+            "intro\n{{user_id}} 💚💙💜 {{unknown}}\noutro\r\nmore outro \
+            {{ unknown ?? \"default\" }}"
+                .into(),
         )
         .unwrap();
         let profile_data = indexmap! { "user_id".into() => "🧡\n💛".into() };
@@ -230,6 +237,7 @@ mod tests {
 
         let text = TextStitcher::stitch_chunks(&template, &chunks);
         let rendered_style = theme.template_preview.text;
+        let fallback_style = theme.template_preview.fallback;
         let error_style = theme.template_preview.error;
         let expected = Text::from(vec![
             Line::from("intro"),
@@ -240,7 +248,52 @@ mod tests {
                 Span::styled("Error", error_style),
             ]),
             Line::from("outro\r"), // \r shouldn't create any issues
-            Line::from("more outro"),
+            Line::from(vec![
+                Span::raw("more outro "),
+                Span::styled("default", fallback_style),
+            ]),
+        ]);
+        assert_eq!(text, expected);
+    }
+
+    /// Keys inside blocks should be highlighted, and loop bodies should
+    /// appear once per iteration
+    #[rstest]
+    #[tokio::test]
+    async fn test_template_stitch_block(_tui_context: ()) {
+        let template = Template::parse(
+            "{% for id in ids %}- {{id}}\n{% endfor %}\
+            {% if missing %}yes{% endif %}"
+                .into(),
+        )
+        .unwrap();
+        let profile_data = indexmap! { "ids".into() => "[1, 2]".into() };
+        let profile = create!(Profile, data: profile_data);
+        let profile_id = profile.id.clone();
+        let context = create!(
+            TemplateContext,
+            collection: create!(
+                Collection,
+                profiles: indexmap!{profile_id.clone() => profile},
+            ),
+            selected_profile: Some(profile_id),
+        );
+        let chunks = template.render_chunks(&context).await;
+        let theme = &TuiContext::get().theme;
+
+        let text = TextStitcher::stitch_chunks(&template, &chunks);
+        let rendered_style = theme.template_preview.text;
+        let error_style = theme.template_preview.error;
+        let expected = Text::from(vec![
+            Line::from(vec![
+                Span::raw("- "),
+                Span::styled("1", rendered_style),
+            ]),
+            Line::from(vec![
+                Span::raw("- "),
+                Span::styled("2", rendered_style),
+            ]),
+            Line::from(Span::styled("Error", error_style)),
         ]);
         assert_eq!(text, expected);
     }
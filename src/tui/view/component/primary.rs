@@ -116,7 +116,7 @@ impl PrimaryView {
 
             profile_list_pane,
             recipe_list_pane,
-            profile_pane: Default::default(),
+            profile_pane: ProfilePane::new(collection.profiles.clone()).into(),
             recipe_pane: Default::default(),
             request_pane: Default::default(),
             response_pane: Default::default(),
@@ -184,6 +184,7 @@ impl PrimaryView {
                 RecipePaneProps {
                     is_selected: self.is_selected(PrimaryPane::Recipe),
                     selected_recipe: self.selected_recipe(),
+                    recipes: self.recipe_list_pane.recipes(),
                     selected_profile_id: self
                         .selected_profile()
                         .map(|profile| &profile.id),
@@ -404,6 +405,7 @@ impl<'a> Draw<PrimaryViewProps<'a>> for PrimaryView {
                 RecipePaneProps {
                     is_selected: true,
                     selected_recipe: self.selected_recipe(),
+                    recipes: self.recipe_list_pane.recipes(),
                     selected_profile_id: self
                         .selected_profile()
                         .map(|profile| &profile.id),
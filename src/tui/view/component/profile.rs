@@ -6,19 +6,46 @@ use crate::{
         state::StateCell,
     },
 };
+use indexmap::IndexMap;
 use itertools::Itertools;
-use ratatui::{layout::Rect, Frame};
+use ratatui::{
+    layout::{Constraint, Rect},
+    Frame,
+};
 
 /// Display the contents of a profile
-#[derive(Debug, Default)]
+#[derive(Debug)]
 pub struct ProfilePane {
-    fields: StateCell<ProfileId, Vec<(String, TemplatePreview)>>,
+    /// All profiles in the collection, needed to resolve inherited fields
+    profiles: IndexMap<ProfileId, Profile>,
+    fields: StateCell<ProfileId, Vec<ProfileField>>,
+}
+
+/// A single field in a profile, which may be inherited from a parent
+#[derive(Debug)]
+struct ProfileField {
+    key: String,
+    value: TemplatePreview,
+    /// Secret fields are hidden entirely, because the raw value is the secret
+    sensitive: bool,
+    /// Name of the parent profile that defined this field. `None` if it's
+    /// defined on the selected profile itself
+    inherited_from: Option<String>,
 }
 
 pub struct ProfilePaneProps<'a> {
     pub profile: &'a Profile,
 }
 
+impl ProfilePane {
+    pub fn new(profiles: IndexMap<ProfileId, Profile>) -> Self {
+        Self {
+            profiles,
+            fields: Default::default(),
+        }
+    }
+}
+
 impl<'a> Draw<ProfilePaneProps<'a>> for ProfilePane {
     fn draw(&self, frame: &mut Frame, props: ProfilePaneProps<'a>, area: Rect) {
         // Whenever the selected profile changes, rebuild the internal state.
@@ -27,16 +54,19 @@ impl<'a> Draw<ProfilePaneProps<'a>> for ProfilePane {
             self.fields.get_or_update(props.profile.id.clone(), || {
                 props
                     .profile
-                    .data
-                    .iter()
-                    .map(|(key, template)| {
-                        (
-                            key.clone(),
-                            TemplatePreview::new(
-                                template.clone(),
-                                Some(props.profile.id.clone()),
-                            ),
-                        )
+                    .resolved_data(&self.profiles)
+                    .into_iter()
+                    .map(|(key, (source, template))| ProfileField {
+                        key: key.to_owned(),
+                        // Inherited templates are still rendered with the
+                        // selected profile, so overridden fields apply
+                        value: TemplatePreview::new(
+                            template.clone(),
+                            Some(props.profile.id.clone()),
+                        ),
+                        sensitive: source.sensitive_fields.contains(key),
+                        inherited_from: (source.id != props.profile.id)
+                            .then(|| source.name().to_owned()),
                     })
                     .collect_vec()
             });
@@ -46,11 +76,26 @@ impl<'a> Draw<ProfilePaneProps<'a>> for ProfilePane {
             is_focused: false,
         };
         let table = Table {
-            header: Some(["Field", "Value"]),
+            header: Some(["Field", "Value", "Inherited From"]),
             rows: fields
                 .iter()
-                .map(|(key, value)| [key.as_str().into(), value.generate()])
+                .map(|field| {
+                    [
+                        field.key.as_str().into(),
+                        if field.sensitive {
+                            "<sensitive>".into()
+                        } else {
+                            field.value.generate()
+                        },
+                        field.inherited_from.as_deref().unwrap_or("").into(),
+                    ]
+                })
                 .collect_vec(),
+            column_widths: &[
+                Constraint::Percentage(30),
+                Constraint::Percentage(50),
+                Constraint::Percentage(20),
+            ],
             alternate_row_style: true,
             ..Default::default()
         };
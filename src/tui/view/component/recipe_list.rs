@@ -4,7 +4,7 @@ use crate::{
         context::TuiContext,
         input::Action,
         view::{
-            common::Pane,
+            common::{text_box::TextBox, Pane},
             draw::{Draw, Generate},
             event::{Event, EventHandler, EventQueue, Update},
             state::{
@@ -17,7 +17,11 @@ use crate::{
 };
 use derive_more::{Deref, DerefMut};
 use itertools::Itertools;
-use ratatui::{prelude::Rect, Frame};
+use ratatui::{
+    layout::{Constraint, Layout},
+    prelude::Rect,
+    Frame,
+};
 use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
 
@@ -31,7 +35,7 @@ use std::collections::HashSet;
 /// This implementation leans heavily on the fact that all nodes in the tree
 /// have a unique ID, which is another reason why it deserves its own
 /// implementation.
-#[derive(Debug)]
+#[derive(derive_more::Debug)]
 pub struct RecipeListPane {
     /// A clone of the recipe tree
     recipes: RecipeTree,
@@ -42,6 +46,11 @@ pub struct RecipeListPane {
     /// Set of all folders that are collapsed
     /// Invariant: No recipes, only folders
     collapsed: Persistent<Collapsed>,
+    /// Only show recipes with this tag (and the folders containing them)
+    tag_filter: Option<String>,
+    /// Where the user enters the tag to filter by
+    #[debug(skip)]
+    tag_text_box: Component<TextBox>,
 }
 
 pub struct RecipeListPaneProps {
@@ -54,6 +63,9 @@ pub struct RecipeListPaneProps {
 #[serde(transparent)]
 struct Collapsed(HashSet<RecipeId>);
 
+/// Callback event from the tag text box when user hits Enter
+struct TagFilterSubmit(String);
+
 /// Ternary state for modifying node collapse state
 enum CollapseState {
     Expand,
@@ -71,15 +83,31 @@ impl RecipeListPane {
         );
         let persistent = Persistent::new(
             PersistentKey::RecipeId,
-            build_select_state(recipes, &collapsed),
+            build_select_state(recipes, &collapsed, None),
         );
         Self {
             recipes: recipes.clone(),
             select_state: persistent.into(),
             collapsed,
+            tag_filter: None,
+            tag_text_box: TextBox::default()
+                .with_focus(false)
+                .with_placeholder("'/' to filter by tag")
+                // Callback triggers an event, so we can modify our own state
+                .with_on_submit(|text_box| {
+                    EventQueue::push(Event::other(TagFilterSubmit(
+                        text_box.text().trim().to_owned(),
+                    )))
+                })
+                .into(),
         }
     }
 
+    /// Get the full recipe tree
+    pub fn recipes(&self) -> &RecipeTree {
+        &self.recipes
+    }
+
     /// Which recipe in the recipe list is selected? `None` iff the list is
     /// empty OR a folder is selected.
     pub fn selected_recipe(&self) -> Option<&Recipe> {
@@ -111,25 +139,47 @@ impl RecipeListPane {
 
         // If we changed the set of what is visible, rebuild the list state
         if changed {
-            let mut new_select_state =
-                build_select_state(&self.recipes, &self.collapsed);
-            // Carry over the selection
-            if let Some(selected) = self.select_state.selected() {
-                new_select_state.select(selected.id());
-            }
-            **self.select_state = new_select_state;
+            self.rebuild_select_state();
         }
 
         changed
     }
+
+    /// Rebuild the list of visible nodes, after collapsing/expanding a folder
+    /// or changing the tag filter
+    fn rebuild_select_state(&mut self) {
+        let mut new_select_state = build_select_state(
+            &self.recipes,
+            &self.collapsed,
+            self.tag_filter.as_deref(),
+        );
+        // Carry over the selection
+        if let Some(selected) = self.select_state.selected() {
+            new_select_state.select(selected.id());
+        }
+        **self.select_state = new_select_state;
+    }
 }
 
 impl EventHandler for RecipeListPane {
     fn update(&mut self, event: Event) -> Update {
+        if let Event::Other(ref other) = event {
+            return match other.downcast_ref::<TagFilterSubmit>() {
+                Some(TagFilterSubmit(tag)) => {
+                    self.tag_filter =
+                        Some(tag.clone()).filter(|tag| !tag.is_empty());
+                    self.rebuild_select_state();
+                    Update::Consumed
+                }
+                None => Update::Propagate(event),
+            };
+        }
+
         let Some(action) = event.action() else {
             return Update::Propagate(event);
         };
         match action {
+            Action::Search => self.tag_text_box.focus(),
             Action::Left => {
                 self.set_selected_collapsed(CollapseState::Collapse);
             }
@@ -147,14 +197,17 @@ impl EventHandler for RecipeListPane {
     }
 
     fn children(&mut self) -> Vec<Component<&mut dyn EventHandler>> {
-        vec![self.select_state.as_child()]
+        if self.tag_text_box.is_focused() {
+            vec![self.tag_text_box.as_child()]
+        } else {
+            vec![self.select_state.as_child()]
+        }
     }
 }
 
 impl Draw<RecipeListPaneProps> for RecipeListPane {
     fn draw(&self, frame: &mut Frame, props: RecipeListPaneProps, area: Rect) {
         let context = TuiContext::get();
-        self.select_state.set_area(area); // Needed for tracking cursor events
 
         let title = context
             .input_engine
@@ -163,6 +216,12 @@ impl Draw<RecipeListPaneProps> for RecipeListPane {
             title: &title,
             is_focused: props.is_selected,
         };
+        let block = pane.generate();
+        let [list_area, tag_area] =
+            Layout::vertical([Constraint::Min(0), Constraint::Length(1)])
+                .areas(block.inner(area));
+        frame.render_widget(block, area);
+        self.select_state.set_area(list_area); // Needed for tracking cursor events
 
         // We have to build this manually instead of using our own List type,
         // because we need outside context during the render
@@ -201,14 +260,14 @@ impl Draw<RecipeListPaneProps> for RecipeListPane {
             })
             .collect_vec();
         let list = ratatui::widgets::List::new(items)
-            .block(pane.generate())
             .highlight_style(context.theme.list.highlight);
 
         frame.render_stateful_widget(
             list,
-            area,
+            list_area,
             &mut self.select_state.state_mut(),
         );
+        self.tag_text_box.draw(frame, (), tag_area);
     }
 }
 
@@ -257,10 +316,13 @@ impl Persistable for Collapsed {
     }
 }
 
-/// Construct select list based on which nodes are currently visible
+/// Construct select list based on which nodes are currently visible. If a tag
+/// filter is given, only recipes with that tag are visible, along with the
+/// folders that contain them.
 fn build_select_state(
     recipes: &RecipeTree,
     collapsed: &Collapsed,
+    tag_filter: Option<&str>,
 ) -> SelectState<RecipeNode> {
     // When highlighting a new recipe, load it from the repo
     fn on_select(_: &mut RecipeNode) {
@@ -268,11 +330,104 @@ fn build_select_state(
         EventQueue::push(Event::HttpLoadRequest);
     }
 
+    let tagged: Option<HashSet<&RecipeId>> = tag_filter.map(|tag| {
+        recipes
+            .recipes_with_tag(tag)
+            .flat_map(|recipe| {
+                // Include the path to each recipe
+                recipes
+                    .get_lookup_key(&recipe.id)
+                    .map(RecipeLookupKey::as_slice)
+                    .unwrap_or_default()
+            })
+            .collect()
+    });
     let items = recipes
         .iter()
         // Filter out hidden nodes
-        .filter(|(lookup_key, _)| collapsed.is_visible(lookup_key))
+        .filter(|(lookup_key, node)| {
+            collapsed.is_visible(lookup_key)
+                && tagged.as_ref().map_or(true, |ids| ids.contains(node.id()))
+        })
         .map(|(_, node)| node.clone())
         .collect();
     SelectState::new(items).on_select(on_select)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::*;
+    use factori::create;
+    use indexmap::indexmap;
+    use rstest::rstest;
+
+    /// Build a tree with tags on recipes and on (nested) folders:
+    /// - r1 (public)
+    /// - f1 (admin)
+    ///   - f2 (users)
+    ///     - r2
+    ///   - r3 (public)
+    /// - f3
+    ///   - r4
+    fn tree() -> RecipeTree {
+        RecipeTree::new(indexmap! {
+            "r1".into() => create!(
+                Recipe, id: "r1".into(), tags: vec!["public".into()]
+            ).into(),
+            "f1".into() => create!(
+                Folder,
+                id: "f1".into(),
+                tags: vec!["admin".into()],
+                children: indexmap! {
+                    "f2".into() => create!(
+                        Folder,
+                        id: "f2".into(),
+                        tags: vec!["users".into()],
+                        children: indexmap! {
+                            "r2".into() => create!(Recipe, id: "r2".into())
+                                .into(),
+                        },
+                    ).into(),
+                    "r3".into() => create!(
+                        Recipe, id: "r3".into(), tags: vec!["public".into()]
+                    ).into(),
+                },
+            ).into(),
+            "f3".into() => create!(
+                Folder,
+                id: "f3".into(),
+                children: indexmap! {
+                    "r4".into() => create!(Recipe, id: "r4".into()).into(),
+                },
+            ).into(),
+        })
+        .unwrap()
+    }
+
+    /// Recipes match by their own tags, or tags inherited from any folder
+    /// above them. Folders are only shown if they contain a match.
+    #[rstest]
+    #[case::no_filter(None, &[], &["r1", "f1", "f2", "r2", "r3", "f3", "r4"])]
+    #[case::recipe_tag(Some("public"), &[], &["r1", "f1", "r3"])]
+    #[case::folder_tag(Some("admin"), &[], &["f1", "f2", "r2", "r3"])]
+    #[case::nested_folder_tag(Some("users"), &[], &["f1", "f2", "r2"])]
+    #[case::unknown_tag(Some("unknown"), &[], &[])]
+    // Matches inside a collapsed folder stay hidden
+    #[case::collapsed(Some("admin"), &["f1"], &["f1"])]
+    fn test_build_select_state(
+        #[case] tag_filter: Option<&str>,
+        #[case] collapsed: &[&str],
+        #[case] expected: &[&str],
+    ) {
+        let collapsed =
+            Collapsed(collapsed.iter().map(|&id| id.into()).collect());
+        let state = build_select_state(&tree(), &collapsed, tag_filter);
+        let ids: Vec<&str> = state
+            .items()
+            .iter()
+            .map(|node| node.id().as_str())
+            .collect();
+        assert_eq!(ids, expected);
+    }
+}
@@ -1,5 +1,8 @@
 use crate::{
-    collection::{Authentication, ProfileId, Recipe, RecipeId},
+    collection::{
+        ApiKeyLocation, Authentication, JsonTemplate, MultipartField,
+        ProfileId, Recipe, RecipeBody, RecipeId, RecipeTree,
+    },
     http::RecipeOptions,
     template::Template,
     tui::{
@@ -9,9 +12,11 @@ use crate::{
         view::{
             common::{
                 actions::ActionsModal,
+                modal::{Modal, ModalPriority},
                 table::{Table, ToggleRow},
                 tabs::Tabs,
                 template_preview::TemplatePreview,
+                text_box::TextBox,
                 text_window::TextWindow,
                 Pane,
             },
@@ -27,15 +32,17 @@ use crate::{
     },
 };
 use derive_more::Display;
+use indexmap::IndexMap;
 use itertools::Itertools;
 use ratatui::{
     layout::Layout,
     prelude::{Constraint, Rect},
+    text::Text,
     widgets::{Paragraph, Row, TableState},
     Frame,
 };
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
+use std::{collections::HashSet, iter, mem};
 use strum::{EnumCount, EnumIter};
 
 /// Display a request recipe
@@ -60,6 +67,8 @@ pub struct RecipePaneProps<'a> {
     pub is_selected: bool,
     pub selected_recipe: Option<&'a Recipe>,
     pub selected_profile_id: Option<&'a ProfileId>,
+    /// Needed to find the folders that the selected recipe inherits from
+    pub recipes: &'a RecipeTree,
 }
 
 /// Template preview state will be recalculated when any of these fields change
@@ -71,11 +80,15 @@ struct RecipeStateKey {
 
 #[derive(Debug)]
 struct RecipeState {
+    method: String,
     url: TemplatePreview,
+    path: Component<Persistent<SelectState<PathRowState, TableState>>>,
     query: Component<Persistent<SelectState<RowState, TableState>>>,
     headers: Component<Persistent<SelectState<RowState, TableState>>>,
-    body: Option<Component<TextWindow<TemplatePreview>>>,
+    body: Option<RecipeBodyDisplay>,
     authentication: Option<Component<AuthenticationDisplay>>,
+    /// Tags and markdown description
+    description: Component<TextWindow<String>>,
 }
 
 #[derive(
@@ -91,19 +104,51 @@ struct RecipeState {
 )]
 enum Tab {
     Body,
+    Path,
     Query,
     Headers,
     Authentication,
+    Description,
 }
 
 /// One row in the query/header table
 #[derive(Debug)]
 struct RowState {
     key: String,
+    /// Rendered preview of the key, for templated header names. If absent,
+    /// the key is shown as-is
+    key_preview: Option<TemplatePreview>,
+    /// Position of this value among the values for the key, since query
+    /// params and headers can be repeated
+    index: usize,
     value: TemplatePreview,
+    /// Name of the recipe/folder that this row was inherited from, if any
+    inherited_from: Option<String>,
     enabled: Persistent<bool>,
 }
 
+/// One row in the path param table
+#[derive(Debug)]
+struct PathRowState {
+    param: String,
+    value: TemplatePreview,
+    /// Name of the recipe that this row was inherited from, if any
+    inherited_from: Option<String>,
+    /// Value entered by the user, to be used instead of the template
+    override_value: Persistent<PathOverride>,
+}
+
+/// A user-provided value for a path param. Wrapped so it can be persisted
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct PathOverride(Option<String>);
+
+/// Emitted by [PathOverrideModal] to set or clear a path param's override
+#[derive(Debug)]
+struct PathOverrideSubmit {
+    param: String,
+    value: Option<String>,
+}
+
 /// Items in the actions popup menu
 #[derive(Copy, Clone, Debug, Display, EnumCount, EnumIter, PartialEq)]
 #[allow(clippy::enum_variant_names)]
@@ -126,18 +171,30 @@ impl RecipePane {
             /// Convert select state into the set of disabled keys
             fn to_disabled_set(
                 select_state: &SelectState<RowState, TableState>,
-            ) -> HashSet<String> {
+            ) -> HashSet<(String, usize)> {
                 select_state
                     .items()
                     .iter()
                     .filter(|row| !*row.enabled)
-                    .map(|row| row.key.clone())
+                    .map(|row| (row.key.clone(), row.index))
                     .collect()
             }
 
             RecipeOptions {
                 disabled_headers: to_disabled_set(&state.headers),
                 disabled_query_parameters: to_disabled_set(&state.query),
+                disable_authentication: state
+                    .authentication
+                    .as_ref()
+                    .is_some_and(|authentication| !*authentication.enabled),
+                path_overrides: state
+                    .path
+                    .items()
+                    .iter()
+                    .filter_map(|row| {
+                        Some((row.param.clone(), row.override_value.0.clone()?))
+                    })
+                    .collect(),
             }
         } else {
             // Shouldn't be possible, because state is initialized on first
@@ -174,11 +231,25 @@ impl EventHandler for RecipePane {
                 ..
             } => EventQueue::open_modal_default::<ActionsModal<MenuAction>>(),
             Event::Other(callback) => {
-                match callback.downcast_ref::<MenuAction>() {
-                    Some(action) => {
-                        self.handle_menu_action(*action);
+                if let Some(action) = callback.downcast_ref::<MenuAction>() {
+                    self.handle_menu_action(*action);
+                } else if let Some(submit) =
+                    callback.downcast_ref::<PathOverrideSubmit>()
+                {
+                    if let Some(row) =
+                        self.recipe_state.get_mut().and_then(|state| {
+                            state
+                                .path
+                                .items_mut()
+                                .iter_mut()
+                                .find(|row| row.param == submit.param)
+                        })
+                    {
+                        *row.override_value =
+                            PathOverride(submit.value.clone());
                     }
-                    None => return Update::Propagate(event),
+                } else {
+                    return Update::Propagate(event);
                 }
             }
             _ => return Update::Propagate(event),
@@ -194,13 +265,22 @@ impl EventHandler for RecipePane {
         if let Some(state) = self.recipe_state.get_mut() {
             match selected_tab {
                 Tab::Body => {
-                    if let Some(body) = state.body.as_mut() {
+                    if let Some(RecipeBodyDisplay::Raw(body)) =
+                        state.body.as_mut()
+                    {
                         children.push(body.as_child());
                     }
                 }
+                Tab::Path => children.push(state.path.as_child()),
                 Tab::Query => children.push(state.query.as_child()),
                 Tab::Headers => children.push(state.headers.as_child()),
-                Tab::Authentication => {}
+                Tab::Authentication => {
+                    if let Some(authentication) = state.authentication.as_mut()
+                    {
+                        children.push(authentication.as_child());
+                    }
+                }
+                Tab::Description => children.push(state.description.as_child()),
             }
         }
 
@@ -224,7 +304,25 @@ impl<'a> Draw<RecipePaneProps<'a>> for RecipePane {
 
         // Render request contents
         if let Some(recipe) = props.selected_recipe {
-            let method = recipe.method.to_string();
+            // Whenever the recipe or profile changes, generate a preview for
+            // each templated value. Almost anything that could change the
+            // preview will either involve changing one of those two things, or
+            // would require reloading the whole collection which will reset
+            // UI state.
+            let recipe_state = self.recipe_state.get_or_update(
+                RecipeStateKey {
+                    selected_profile_id: props.selected_profile_id.cloned(),
+                    recipe_id: recipe.id.clone(),
+                },
+                || {
+                    RecipeState::new(
+                        recipe,
+                        props.recipes,
+                        props.selected_profile_id,
+                    )
+                },
+            );
+            let method = recipe_state.method.as_str();
 
             let [metadata_area, tabs_area, content_area] = Layout::vertical([
                 Constraint::Length(1),
@@ -239,19 +337,6 @@ impl<'a> Draw<RecipePaneProps<'a>> for RecipePane {
             )
             .areas(metadata_area);
 
-            // Whenever the recipe or profile changes, generate a preview for
-            // each templated value. Almost anything that could change the
-            // preview will either involve changing one of those two things, or
-            // would require reloading the whole collection which will reset
-            // UI state.
-            let recipe_state = self.recipe_state.get_or_update(
-                RecipeStateKey {
-                    selected_profile_id: props.selected_profile_id.cloned(),
-                    recipe_id: recipe.id.clone(),
-                },
-                || RecipeState::new(recipe, props.selected_profile_id),
-            );
-
             // First line: Method + URL
             frame.render_widget(Paragraph::new(method), method_area);
             frame.render_widget(&recipe_state.url, url_area);
@@ -261,20 +346,37 @@ impl<'a> Draw<RecipePaneProps<'a>> for RecipePane {
 
             // Request content
             match self.tabs.selected() {
-                Tab::Body => {
-                    if let Some(body) = &recipe_state.body {
-                        body.draw(frame, (), content_area);
+                Tab::Body => match &recipe_state.body {
+                    Some(RecipeBodyDisplay::Raw(body)) => {
+                        body.draw(frame, (), content_area)
                     }
-                }
+                    Some(RecipeBodyDisplay::Form(fields)) => frame
+                        .render_widget(
+                            to_form_table(fields).generate(),
+                            content_area,
+                        ),
+                    None => {}
+                },
+                Tab::Path => frame.render_stateful_widget(
+                    to_path_table(&recipe_state.path).generate(),
+                    content_area,
+                    &mut recipe_state.path.state_mut(),
+                ),
                 Tab::Query => frame.render_stateful_widget(
-                    to_table(&recipe_state.query, ["", "Parameter", "Value"])
-                        .generate(),
+                    to_table(
+                        &recipe_state.query,
+                        ["", "Parameter", "Value", "Inherited From"],
+                    )
+                    .generate(),
                     content_area,
                     &mut recipe_state.query.state_mut(),
                 ),
                 Tab::Headers => frame.render_stateful_widget(
-                    to_table(&recipe_state.headers, ["", "Header", "Value"])
-                        .generate(),
+                    to_table(
+                        &recipe_state.headers,
+                        ["", "Header", "Value", "Inherited From"],
+                    )
+                    .generate(),
                     content_area,
                     &mut recipe_state.headers.state_mut(),
                 ),
@@ -283,6 +385,9 @@ impl<'a> Draw<RecipePaneProps<'a>> for RecipePane {
                         authentication.draw(frame, (), content_area)
                     }
                 }
+                Tab::Description => {
+                    recipe_state.description.draw(frame, (), content_area)
+                }
             }
         }
     }
@@ -291,43 +396,107 @@ impl<'a> Draw<RecipePaneProps<'a>> for RecipePane {
 impl RecipeState {
     /// Initialize new recipe state. Should be called whenever the recipe or
     /// profile changes
-    fn new(recipe: &Recipe, selected_profile_id: Option<&ProfileId>) -> Self {
-        let query_items = recipe
-            .query
+    fn new(
+        recipe: &Recipe,
+        recipes: &RecipeTree,
+        selected_profile_id: Option<&ProfileId>,
+    ) -> Self {
+        // Tags include those inherited from folders
+        let tags = recipes.tags(&recipe.id).into_iter().join(", ");
+        let description =
+            recipe.description.as_deref().unwrap_or("No description");
+        let description = if tags.is_empty() {
+            description.to_owned()
+        } else {
+            format!("Tags: {tags}\n\n{description}")
+        };
+
+        // Show the recipe as it will be built, with inherited values applied
+        let mut recipe = recipe.clone();
+        let inherited = recipes.apply_inheritance(&mut recipe);
+        let source_name = |source_id: Option<&RecipeId>| {
+            source_id
+                .and_then(|source_id| recipes.get(source_id))
+                .map(|source| source.name().to_owned())
+        };
+
+        let path_items = recipe
+            .path
             .iter()
-            .map(|(param, value)| {
+            .map(|(param, value)| PathRowState {
+                param: param.clone(),
+                value: TemplatePreview::new(
+                    value.clone(),
+                    selected_profile_id.cloned(),
+                ),
+                inherited_from: source_name(inherited.path.get(param)),
+                override_value: Persistent::new(
+                    PersistentKey::RecipePath {
+                        recipe: recipe.id.clone(),
+                        param: param.clone(),
+                    },
+                    PathOverride::default(),
+                ),
+            })
+            .collect();
+        // Each value of a repeated param/header gets its own row
+        let query_items = flatten(&recipe.query)
+            .map(|(param, index, value)| {
                 RowState::new(
                     param.clone(),
+                    index,
                     value.clone(),
+                    source_name(inherited.query.get(param)),
                     selected_profile_id.cloned(),
                     PersistentKey::RecipeQuery {
                         recipe: recipe.id.clone(),
                         param: param.clone(),
+                        index,
                     },
                 )
             })
             .collect();
-        let header_items = recipe
-            .headers
-            .iter()
-            .map(|(header, value)| {
-                RowState::new(
-                    header.clone(),
+        let header_items = flatten(&recipe.headers)
+            .map(|(header, index, value)| {
+                let row = RowState::new(
+                    header.to_string(),
+                    index,
                     value.clone(),
+                    source_name(inherited.headers.get(header.as_str())),
                     selected_profile_id.cloned(),
                     PersistentKey::RecipeHeader {
                         recipe: recipe.id.clone(),
-                        header: header.clone(),
+                        header: header.to_string(),
+                        index,
                     },
-                )
+                );
+                // Header names are templates, so preview them like values
+                if header.keys().next().is_some() {
+                    row.with_key_preview(TemplatePreview::new(
+                        header.clone(),
+                        selected_profile_id.cloned(),
+                    ))
+                } else {
+                    row
+                }
             })
             .collect();
 
         Self {
+            method: recipe
+                .method
+                .map(|method| method.to_string())
+                .unwrap_or_default(),
             url: TemplatePreview::new(
-                recipe.url.clone(),
+                // Load-time validation should prevent a missing URL
+                recipe.url.clone().unwrap_or_default(),
                 selected_profile_id.cloned(),
             ),
+            path: Persistent::new(
+                PersistentKey::RecipeSelectedPath(recipe.id.clone()),
+                SelectState::new(path_items).on_submit(PathRowState::on_submit),
+            )
+            .into(),
             query: Persistent::new(
                 PersistentKey::RecipeSelectedQuery(recipe.id.clone()),
                 SelectState::new(query_items).on_submit(RowState::on_submit),
@@ -338,102 +507,303 @@ impl RecipeState {
                 SelectState::new(header_items).on_submit(RowState::on_submit),
             )
             .into(),
-            body: recipe.body.as_ref().map(|body| {
-                TextWindow::new(TemplatePreview::new(
-                    body.clone(),
-                    selected_profile_id.cloned(),
-                ))
-                .into()
-            }),
-            // Map authentication type
+            body: recipe
+                .body
+                .as_ref()
+                .map(|body| RecipeBodyDisplay::new(body, selected_profile_id)),
             authentication: recipe.authentication.as_ref().map(
                 |authentication| {
-                    match authentication {
-                        Authentication::Basic { username, password } => {
-                            AuthenticationDisplay::Basic {
-                                username: TemplatePreview::new(
-                                    username.clone(),
-                                    selected_profile_id.cloned(),
-                                ),
-                                password: password.clone().map(|password| {
-                                    TemplatePreview::new(
-                                        password,
-                                        selected_profile_id.cloned(),
-                                    )
-                                }),
-                            }
-                        }
-                        Authentication::Bearer(token) => {
-                            AuthenticationDisplay::Bearer(TemplatePreview::new(
-                                token.clone(),
-                                selected_profile_id.cloned(),
-                            ))
-                        }
-                    }
-                    .into() // Convert to Component
+                    AuthenticationDisplay::new(
+                        &recipe.id,
+                        authentication,
+                        source_name(inherited.authentication.as_ref()),
+                        selected_profile_id,
+                    )
+                    .into()
                 },
             ),
+            description: TextWindow::new(description).into(),
         }
     }
 }
 
-/// Display authentication settings. This is basically the underlying
-/// [Authentication] type, but the templates have been rendered
+/// Display a recipe body. Raw and JSON bodies are shown as text, while form
+/// bodies are shown as a table of their fields
 #[derive(Debug)]
-enum AuthenticationDisplay {
-    Basic {
-        username: TemplatePreview,
-        password: Option<TemplatePreview>,
-    },
-    Bearer(TemplatePreview),
+enum RecipeBodyDisplay {
+    Raw(Component<TextWindow<TemplatePreview>>),
+    Form(Vec<FormFieldDisplay>),
 }
 
-impl Draw for AuthenticationDisplay {
-    fn draw(&self, frame: &mut Frame, _: (), area: Rect) {
-        match self {
-            AuthenticationDisplay::Basic { username, password } => {
-                let table = Table {
-                    rows: vec![
-                        ["Type".into(), "Bearer".into()],
-                        ["Username".into(), username.generate()],
-                        [
-                            "Password".into(),
-                            password
-                                .as_ref()
-                                .map(Generate::generate)
-                                .unwrap_or_default(),
-                        ],
-                    ],
-                    column_widths: &[Constraint::Length(8), Constraint::Min(0)],
-                    ..Default::default()
-                };
-                frame.render_widget(table.generate(), area)
+/// One field in a form body
+#[derive(Debug)]
+struct FormFieldDisplay {
+    field: String,
+    /// Is the value a path to a file, rather than the field value itself?
+    is_file: bool,
+    value: TemplatePreview,
+}
+
+impl RecipeBodyDisplay {
+    fn new(body: &RecipeBody, selected_profile_id: Option<&ProfileId>) -> Self {
+        let preview = |template: &Template| {
+            TemplatePreview::new(template.clone(), selected_profile_id.cloned())
+        };
+        match body {
+            RecipeBody::Raw(template) => {
+                Self::Raw(TextWindow::new(preview(template)).into())
+            }
+            RecipeBody::Json(value) => {
+                let mut parts = JsonPreviewParts::default();
+                parts.push_value(value, 0);
+                Self::Raw(TextWindow::new(preview(&parts.finish())).into())
             }
-            AuthenticationDisplay::Bearer(token) => {
-                let table = Table {
-                    rows: vec![
-                        ["Type".into(), "Bearer".into()],
-                        ["Token".into(), token.generate()],
+            RecipeBody::FormUrlencoded(fields) => Self::Form(
+                fields
+                    .iter()
+                    .map(|(field, value)| FormFieldDisplay {
+                        field: field.clone(),
+                        is_file: false,
+                        value: preview(value),
+                    })
+                    .collect(),
+            ),
+            RecipeBody::FormMultipart(fields) => Self::Form(
+                fields
+                    .iter()
+                    .map(|(field, value)| {
+                        let (is_file, value) = match value {
+                            MultipartField::Text(value) => (false, value),
+                            MultipartField::File(path) => (true, path),
+                        };
+                        FormFieldDisplay {
+                            field: field.clone(),
+                            is_file,
+                            value: preview(value),
+                        }
+                    })
+                    .collect(),
+            ),
+        }
+    }
+}
+
+/// Display authentication settings. Each templated field is shown as a
+/// preview. Authentication can be toggled on/off as a single unit.
+#[derive(Debug)]
+struct AuthenticationDisplay {
+    /// Human-readable name of the authentication type
+    kind: String,
+    /// Label and value for each field that's defined. Empty optional fields
+    /// are excluded
+    fields: Vec<(&'static str, TemplatePreview)>,
+    /// Name of the recipe/folder that authentication was inherited from, if
+    /// any
+    inherited_from: Option<String>,
+    enabled: Persistent<bool>,
+}
+
+impl AuthenticationDisplay {
+    fn new(
+        recipe_id: &RecipeId,
+        authentication: &Authentication,
+        inherited_from: Option<String>,
+        selected_profile_id: Option<&ProfileId>,
+    ) -> Self {
+        let (kind, fields): (String, Vec<(&'static str, Option<&Template>)>) =
+            match authentication {
+                Authentication::Basic { username, password } => (
+                    "Basic".into(),
+                    vec![
+                        ("Username", Some(username)),
+                        ("Password", password.as_ref()),
                     ],
-                    column_widths: &[Constraint::Length(5), Constraint::Min(0)],
-                    ..Default::default()
-                };
-                frame.render_widget(table.generate(), area)
+                ),
+                Authentication::Bearer(token) => {
+                    ("Bearer".into(), vec![("Token", Some(token))])
+                }
+                Authentication::ApiKey {
+                    location,
+                    name,
+                    value,
+                } => {
+                    let location = match location {
+                        ApiKeyLocation::Header => "header",
+                        ApiKeyLocation::Query => "query parameter",
+                    };
+                    (
+                        format!("API Key ({location} `{name}`)"),
+                        vec![("Value", Some(value))],
+                    )
+                }
+                Authentication::Digest { username, password } => (
+                    "Digest".into(),
+                    vec![
+                        ("Username", Some(username)),
+                        ("Password", Some(password)),
+                    ],
+                ),
+                Authentication::Oauth2ClientCredentials {
+                    token_url,
+                    client_id,
+                    client_secret,
+                    scope,
+                } => (
+                    "OAuth 2.0 (Client Credentials)".into(),
+                    vec![
+                        ("Token URL", Some(token_url)),
+                        ("Client ID", Some(client_id)),
+                        ("Client Secret", Some(client_secret)),
+                        ("Scope", scope.as_ref()),
+                    ],
+                ),
+                Authentication::Oauth2Password {
+                    token_url,
+                    client_id,
+                    client_secret,
+                    username,
+                    password,
+                    scope,
+                } => (
+                    "OAuth 2.0 (Password)".into(),
+                    vec![
+                        ("Token URL", Some(token_url)),
+                        ("Client ID", Some(client_id)),
+                        ("Client Secret", client_secret.as_ref()),
+                        ("Username", Some(username)),
+                        ("Password", Some(password)),
+                        ("Scope", scope.as_ref()),
+                    ],
+                ),
+                Authentication::Oauth2RefreshToken {
+                    token_url,
+                    client_id,
+                    client_secret,
+                    refresh_token,
+                    scope,
+                } => (
+                    "OAuth 2.0 (Refresh Token)".into(),
+                    vec![
+                        ("Token URL", Some(token_url)),
+                        ("Client ID", Some(client_id)),
+                        ("Client Secret", client_secret.as_ref()),
+                        ("Refresh Token", Some(refresh_token)),
+                        ("Scope", scope.as_ref()),
+                    ],
+                ),
+                Authentication::AwsSigV4 {
+                    access_key,
+                    secret_key,
+                    session_token,
+                    region,
+                    service,
+                } => (
+                    "AWS Signature V4".into(),
+                    vec![
+                        ("Access Key", Some(access_key)),
+                        ("Secret Key", Some(secret_key)),
+                        ("Session Token", session_token.as_ref()),
+                        ("Region", Some(region)),
+                        ("Service", Some(service)),
+                    ],
+                ),
+            };
+
+        Self {
+            kind,
+            fields: fields
+                .into_iter()
+                .filter_map(|(label, template)| {
+                    Some((
+                        label,
+                        TemplatePreview::new(
+                            template?.clone(),
+                            selected_profile_id.cloned(),
+                        ),
+                    ))
+                })
+                .collect(),
+            inherited_from,
+            enabled: Persistent::new(
+                PersistentKey::RecipeAuthentication(recipe_id.clone()),
+                true,
+            ),
+        }
+    }
+}
+
+/// Toggle authentication on submit
+impl EventHandler for AuthenticationDisplay {
+    fn update(&mut self, event: Event) -> Update {
+        match event.action() {
+            Some(Action::Submit) => {
+                *self.enabled ^= true;
+                Update::Consumed
             }
+            _ => Update::Propagate(event),
         }
     }
 }
 
+impl Draw for AuthenticationDisplay {
+    fn draw(&self, frame: &mut Frame, _: (), area: Rect) {
+        const INHERITED_LABEL: &str = "Inherited From";
+        let enabled = *self.enabled;
+        let theme = &TuiContext::get().theme;
+        let style = if enabled {
+            theme.table.text
+        } else {
+            theme.table.disabled
+        };
+        // The checkbox goes on the first row, and applies to the whole table
+        let rows = iter::once(
+            ToggleRow::new(["Type", self.kind.as_str()], enabled).generate(),
+        )
+        .chain(self.inherited_from.as_deref().map(|folder| {
+            Row::new([Text::default(), INHERITED_LABEL.into(), folder.into()])
+                .style(style)
+        }))
+        .chain(self.fields.iter().map(|(label, value)| {
+            Row::new([Text::default(), (*label).into(), value.generate()])
+                .style(style)
+        }))
+        .collect();
+        let label_width = self
+            .fields
+            .iter()
+            .map(|(label, _)| label.len())
+            .chain(self.inherited_from.as_ref().map(|_| INHERITED_LABEL.len()))
+            .max()
+            .unwrap_or_default()
+            .max("Type".len()) as u16;
+        let table = Table {
+            rows,
+            column_widths: &[
+                Constraint::Min(3),
+                Constraint::Length(label_width),
+                Constraint::Min(0),
+            ],
+            ..Default::default()
+        };
+        frame.render_widget(table.generate(), area)
+    }
+}
+
 impl RowState {
     fn new(
         key: String,
+        index: usize,
         value: Template,
+        inherited_from: Option<String>,
         selected_profile_id: Option<ProfileId>,
         persistent_key: PersistentKey,
     ) -> Self {
         Self {
             key,
+            key_preview: None,
+            index,
             value: TemplatePreview::new(value, selected_profile_id),
+            inherited_from,
             enabled: Persistent::new(
                 persistent_key,
                 // Value itself is the container, so just pass a default value
@@ -442,24 +812,212 @@ impl RowState {
         }
     }
 
+    /// Show a rendered preview of the key, instead of the raw key
+    fn with_key_preview(mut self, key_preview: TemplatePreview) -> Self {
+        self.key_preview = Some(key_preview);
+        self
+    }
+
     /// Toggle row state on submit
     fn on_submit(row: &mut Self) {
         *row.enabled ^= true;
     }
 }
 
+impl PathRowState {
+    /// Open a modal to edit the override on submit
+    fn on_submit(row: &mut Self) {
+        EventQueue::open_modal(
+            PathOverrideModal::new(
+                row.param.clone(),
+                row.override_value.0.clone(),
+            ),
+            ModalPriority::Low,
+        );
+    }
+}
+
+/// Modal to enter an override value for a path param. Submitting an empty
+/// value clears the override.
+#[derive(Debug)]
+struct PathOverrideModal {
+    title: String,
+    text_box: Component<TextBox>,
+}
+
+impl PathOverrideModal {
+    fn new(param: String, current: Option<String>) -> Self {
+        let title = format!("Override `{param}`");
+        let text_box = TextBox::default()
+            .with_default(current.unwrap_or_default())
+            .with_placeholder("Leave empty to use the recipe value")
+            .with_on_cancel(|_| EventQueue::push(Event::CloseModal))
+            .with_on_submit(move |text_box| {
+                // Close the modal *first*, so the recipe pane can handle the
+                // submission
+                EventQueue::push(Event::CloseModal);
+                let value = Some(text_box.text())
+                    .filter(|text| !text.is_empty())
+                    .map(String::from);
+                EventQueue::push(Event::other(PathOverrideSubmit {
+                    param: param.clone(),
+                    value,
+                }));
+            })
+            .into();
+        Self { title, text_box }
+    }
+}
+
+impl Modal for PathOverrideModal {
+    fn title(&self) -> &str {
+        &self.title
+    }
+
+    fn dimensions(&self) -> (Constraint, Constraint) {
+        (Constraint::Percentage(60), Constraint::Length(1))
+    }
+}
+
+impl EventHandler for PathOverrideModal {
+    fn children(&mut self) -> Vec<Component<&mut dyn EventHandler>> {
+        vec![self.text_box.as_child()]
+    }
+}
+
+impl Draw for PathOverrideModal {
+    fn draw(&self, frame: &mut Frame, _: (), area: Rect) {
+        self.text_box.draw(frame, (), area);
+    }
+}
+
+/// Helper to pretty-print a JSON body as a single template, so it can be
+/// previewed like a raw body. Strings are quoted, except those that are a
+/// single key, because those are substituted with their JSON type
+#[derive(Default)]
+struct JsonPreviewParts {
+    parts: Vec<Template>,
+    /// Static text that hasn't been pushed as a part yet
+    buffer: String,
+}
+
+impl JsonPreviewParts {
+    fn push_value(&mut self, value: &JsonTemplate, indent: usize) {
+        match value {
+            JsonTemplate::Null => self.buffer.push_str("null"),
+            JsonTemplate::Bool(value) => {
+                self.buffer.push_str(&value.to_string())
+            }
+            JsonTemplate::Number(value) => {
+                self.buffer.push_str(&value.to_string())
+            }
+            JsonTemplate::String(template) if template.is_single_key() => {
+                self.push_template(template)
+            }
+            JsonTemplate::String(template) => {
+                self.buffer.push('"');
+                self.push_template(template);
+                self.buffer.push('"');
+            }
+            JsonTemplate::Array(values) => {
+                self.push_collection(
+                    ('[', ']'),
+                    values.iter().map(|value| (None, value)),
+                    indent,
+                );
+            }
+            JsonTemplate::Object(fields) => {
+                self.push_collection(
+                    ('{', '}'),
+                    fields.iter().map(|(key, value)| (Some(key), value)),
+                    indent,
+                );
+            }
+        }
+    }
+
+    /// Push an array or object, with one entry per line
+    fn push_collection<'a>(
+        &mut self,
+        (open, close): (char, char),
+        entries: impl ExactSizeIterator<
+            Item = (Option<&'a String>, &'a JsonTemplate),
+        >,
+        indent: usize,
+    ) {
+        self.buffer.push(open);
+        if entries.len() > 0 {
+            let inner_indent = indent + 2;
+            for (i, (key, value)) in entries.enumerate() {
+                if i > 0 {
+                    self.buffer.push(',');
+                }
+                self.buffer.push('\n');
+                self.buffer.push_str(&" ".repeat(inner_indent));
+                if let Some(key) = key {
+                    // Use serde to get proper quoting/escaping
+                    self.buffer.push_str(
+                        &serde_json::Value::String(key.clone()).to_string(),
+                    );
+                    self.buffer.push_str(": ");
+                }
+                self.push_value(value, inner_indent);
+            }
+            self.buffer.push('\n');
+            self.buffer.push_str(&" ".repeat(indent));
+        }
+        self.buffer.push(close);
+    }
+
+    fn push_template(&mut self, template: &Template) {
+        self.flush();
+        self.parts.push(template.clone());
+    }
+
+    fn flush(&mut self) {
+        if !self.buffer.is_empty() {
+            self.parts.push(Template::raw(mem::take(&mut self.buffer)));
+        }
+    }
+
+    fn finish(mut self) -> Template {
+        self.flush();
+        Template::concat(&self.parts)
+    }
+}
+
+/// Flatten a multi-value map into (key, index, value) rows
+fn flatten<K>(
+    map: &IndexMap<K, Vec<Template>>,
+) -> impl Iterator<Item = (&K, usize, &Template)> {
+    map.iter().flat_map(|(key, values)| {
+        values
+            .iter()
+            .enumerate()
+            .map(move |(index, value)| (key, index, value))
+    })
+}
+
 /// Convert table select state into a renderable table
 fn to_table<'a>(
     state: &'a SelectState<RowState, TableState>,
-    header: [&'a str; 3],
-) -> Table<'a, 3, Row<'a>> {
+    header: [&'a str; 4],
+) -> Table<'a, 4, Row<'a>> {
     Table {
         rows: state
             .items()
             .iter()
             .map(|item| {
+                let key = match &item.key_preview {
+                    Some(preview) => preview.generate(),
+                    None => item.key.as_str().into(),
+                };
                 ToggleRow::new(
-                    [item.key.as_str().into(), item.value.generate()],
+                    [
+                        key,
+                        item.value.generate(),
+                        item.inherited_from.as_deref().unwrap_or("").into(),
+                    ],
                     *item.enabled,
                 )
                 .generate()
@@ -468,6 +1026,61 @@ fn to_table<'a>(
         header: Some(header),
         column_widths: &[
             Constraint::Min(3),
+            Constraint::Percentage(30),
+            Constraint::Percentage(50),
+            Constraint::Percentage(20),
+        ],
+        ..Default::default()
+    }
+}
+
+/// Convert path param select state into a renderable table. Overridden params
+/// show the override instead of the template
+fn to_path_table(
+    state: &SelectState<PathRowState, TableState>,
+) -> Table<'_, 3, [Text<'_>; 3]> {
+    Table {
+        rows: state
+            .items()
+            .iter()
+            .map(|row| {
+                let value = match &row.override_value.0 {
+                    Some(value) => format!("{value} (override)").into(),
+                    None => row.value.generate(),
+                };
+                [
+                    row.param.as_str().into(),
+                    value,
+                    row.inherited_from.as_deref().unwrap_or("").into(),
+                ]
+            })
+            .collect_vec(),
+        header: Some(["Parameter", "Value", "Inherited From"]),
+        column_widths: &[
+            Constraint::Percentage(30),
+            Constraint::Percentage(50),
+            Constraint::Percentage(20),
+        ],
+        ..Default::default()
+    }
+}
+
+/// Convert form body fields into a renderable table
+fn to_form_table(fields: &[FormFieldDisplay]) -> Table<'_, 2, [Text<'_>; 2]> {
+    Table {
+        rows: fields
+            .iter()
+            .map(|field| {
+                let key = if field.is_file {
+                    format!("{} (file)", field.field).into()
+                } else {
+                    field.field.as_str().into()
+                };
+                [key, field.value.generate()]
+            })
+            .collect_vec(),
+        header: Some(["Field", "Value"]),
+        column_widths: &[
             Constraint::Percentage(50),
             Constraint::Percentage(50),
         ],
@@ -489,3 +1102,27 @@ impl PartialEq<RowState> for String {
         self == &other.key
     }
 }
+
+/// This impl persists a path param's override value
+impl Persistable for PathOverride {
+    type Persisted = Self;
+
+    fn get_persistent(&self) -> &Self::Persisted {
+        self
+    }
+}
+
+/// This impl persists just which path row is *selected*
+impl Persistable for PathRowState {
+    type Persisted = String;
+
+    fn get_persistent(&self) -> &Self::Persisted {
+        &self.param
+    }
+}
+
+impl PartialEq<PathRowState> for String {
+    fn eq(&self, other: &PathRowState) -> bool {
+        self == &other.param
+    }
+}
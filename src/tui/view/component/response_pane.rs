@@ -6,8 +6,8 @@ use crate::{
         message::Message,
         view::{
             common::{
-                actions::ActionsModal, header_table::HeaderTable, tabs::Tabs,
-                Pane,
+                actions::ActionsModal, header_table::HeaderTable, table::Table,
+                tabs::Tabs, Pane,
             },
             component::record_body::{RecordBody, RecordBodyProps},
             draw::{Draw, Generate, ToStringGenerate},
@@ -19,10 +19,11 @@ use crate::{
 };
 use chrono::Utc;
 use derive_more::{Debug, Display};
+use itertools::Itertools;
 use ratatui::{
     layout::Layout,
     prelude::{Alignment, Constraint, Rect},
-    text::Line,
+    text::{Line, Span},
     widgets::{Paragraph, Wrap},
     Frame,
 };
@@ -114,6 +115,17 @@ struct CompleteResponseContent {
     /// loaded request changes
     #[debug(skip)]
     body: StateCell<RequestId, Component<RecordBody>>,
+    /// Assertion results for the loaded response. Cached because checking
+    /// may require parsing the body
+    #[debug(skip)]
+    tests: StateCell<RequestId, Vec<TestResult>>,
+}
+
+/// Outcome of a single assertion, for display
+struct TestResult {
+    assertion: String,
+    /// Why the assertion failed. `None` if it passed
+    failure: Option<String>,
 }
 
 impl Default for CompleteResponseContent {
@@ -121,6 +133,7 @@ impl Default for CompleteResponseContent {
         Self {
             tabs: Tabs::new(PersistentKey::ResponseTab).into(),
             body: Default::default(),
+            tests: Default::default(),
         }
     }
 }
@@ -143,6 +156,7 @@ struct CompleteResponseContentProps<'a> {
 enum Tab {
     Body,
     Headers,
+    Tests,
 }
 
 impl EventHandler for CompleteResponseContent {
@@ -181,7 +195,7 @@ impl EventHandler for CompleteResponseContent {
                     children.push(body.as_child());
                 }
             }
-            Tab::Headers => {}
+            Tab::Headers | Tab::Tests => {}
         }
         // Tabs goes last, because pane content gets priority
         children.push(self.tabs.as_child());
@@ -245,6 +259,64 @@ impl<'a> Draw<CompleteResponseContentProps<'a>> for CompleteResponseContent {
                 .generate(),
                 content_area,
             ),
+
+            Tab::Tests => self.draw_tests(frame, props.record, content_area),
         }
     }
 }
+
+impl CompleteResponseContent {
+    /// Draw the result of each assertion on the response
+    fn draw_tests(
+        &self,
+        frame: &mut Frame,
+        record: &RequestRecord,
+        area: Rect,
+    ) {
+        let results = self.tests.get_or_update(record.id, || {
+            record
+                .check_assertions()
+                .into_iter()
+                .map(|result| TestResult {
+                    assertion: result.assertion.to_string(),
+                    failure: result.failure,
+                })
+                .collect_vec()
+        });
+        if results.is_empty() {
+            frame.render_widget(
+                Paragraph::new("No assertions defined for this recipe"),
+                area,
+            );
+            return;
+        }
+
+        let theme = &TuiContext::get().theme;
+        let table = Table {
+            header: Some(["", "Assertion", "Failure"]),
+            rows: results
+                .iter()
+                .map(|result| {
+                    let status = if result.failure.is_none() {
+                        Span::styled("✓", theme.text.success)
+                    } else {
+                        Span::styled("✗", theme.text.error)
+                    };
+                    [
+                        status,
+                        result.assertion.as_str().into(),
+                        result.failure.as_deref().unwrap_or("").into(),
+                    ]
+                })
+                .collect_vec(),
+            column_widths: &[
+                Constraint::Length(1),
+                Constraint::Percentage(50),
+                Constraint::Percentage(50),
+            ],
+            alternate_row_style: true,
+            ..Default::default()
+        };
+        frame.render_widget(table.generate(), area);
+    }
+}
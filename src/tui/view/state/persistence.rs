@@ -79,20 +79,42 @@ pub enum PersistentKey {
     RecipeCollapsed,
     /// Selected tab in the recipe pane
     RecipeTab,
+    /// Selected path param, per recipe. Value is the path param name
+    RecipeSelectedPath(RecipeId),
+    /// User-provided override for a single recipe+path param
+    RecipePath { recipe: RecipeId, param: String },
     /// Selected query param, per recipe. Value is the query param name
     RecipeSelectedQuery(RecipeId),
-    /// Toggle state for a single recipe+query param
-    RecipeQuery { recipe: RecipeId, param: String },
+    /// Toggle state for a single recipe+query param value. Params can be
+    /// repeated, so `index` is the position of the value under the param.
+    /// It's omitted when zero so single-value params keep their old key.
+    RecipeQuery {
+        recipe: RecipeId,
+        param: String,
+        #[serde(skip_serializing_if = "is_zero")]
+        index: usize,
+    },
     /// Selected header, per recipe. Value is the header name
     RecipeSelectedHeader(RecipeId),
-    /// Toggle state for a single recipe+header
-    RecipeHeader { recipe: RecipeId, header: String },
+    /// Toggle state for a single recipe+header value. Same as query params
+    RecipeHeader {
+        recipe: RecipeId,
+        header: String,
+        #[serde(skip_serializing_if = "is_zero")]
+        index: usize,
+    },
+    /// Toggle state for a recipe's authentication
+    RecipeAuthentication(RecipeId),
     /// Selected tab in Request pane
     RequestTab,
     /// Selected tab in Response pane
     ResponseTab,
 }
 
+fn is_zero(value: &usize) -> bool {
+    *value == 0
+}
+
 /// A value type that can be persisted to the database
 pub trait Persistable {
     /// The type of the value that's actually persisted to the database. In most
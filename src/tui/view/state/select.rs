@@ -73,6 +73,11 @@ impl<Item, State: SelectStateData> SelectState<Item, State> {
         &self.items
     }
 
+    /// Get all items in the list, mutably
+    pub fn items_mut(&mut self) -> &mut [Item] {
+        &mut self.items
+    }
+
     /// Get the index of the currently selected item (if any)
     pub fn selected_index(&self) -> Option<usize> {
         self.state.borrow().selected()
@@ -60,6 +60,8 @@ pub struct ThemeTable {
 #[derive(Debug)]
 pub struct ThemeTemplatePreview {
     pub text: Style,
+    /// A key that failed to render, so its fallback value was used
+    pub fallback: Style,
     pub error: Style,
 }
 
@@ -68,6 +70,10 @@ pub struct ThemeTemplatePreview {
 pub struct ThemeText {
     /// Text that needs some visual emphasis/separation
     pub highlight: Style,
+    /// Something went right, e.g. a passing assertion
+    pub success: Style,
+    /// Something went wrong, e.g. a failing assertion
+    pub error: Style,
 }
 
 /// Styles for TextBox component
@@ -123,12 +129,17 @@ impl Default for Theme {
             },
             template_preview: ThemeTemplatePreview {
                 text: Style::default().fg(Color::Blue),
+                fallback: Style::default()
+                    .fg(Color::Blue)
+                    .add_modifier(Modifier::ITALIC),
                 error: Style::default().bg(Self::ERROR_COLOR),
             },
             text: ThemeText {
                 highlight: Style::default()
                     .fg(Color::Black)
                     .bg(Self::PRIMARY_COLOR),
+                success: Style::default().fg(Self::PRIMARY_COLOR),
+                error: Style::default().fg(Self::ERROR_COLOR),
             },
             text_box: ThemeTextBox {
                 text: Style::default().bg(Color::DarkGray),
@@ -4,7 +4,7 @@ use crate::{http::RequestError, template::ChainError};
 use derive_more::{DerefMut, Display};
 use serde::de::DeserializeOwned;
 use std::{
-    fmt::{self, Debug},
+    fmt::{self, Debug, Write},
     iter::FusedIterator,
     ops::Deref,
 };
@@ -35,6 +35,22 @@ pub fn parse_yaml<T: DeserializeOwned>(bytes: &[u8]) -> serde_yaml::Result<T> {
     serde_yaml::from_value(yaml_value)
 }
 
+/// Percent-encode everything except unreserved characters (RFC 3986), i.e.
+/// everything but ASCII alphanumerics and `-_.~`. This is safe for encoding a
+/// single URL path segment or query value, and matches what AWS request
+/// signing expects.
+pub fn uri_encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-_.~".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            let _ = write!(encoded, "%{byte:02X}");
+        }
+    }
+    encoded
+}
+
 /// A value that can be replaced in-place. This is useful for two purposes:
 /// - Transferring ownership of values from old to new
 /// - Dropping the old value before creating the new one
@@ -305,8 +321,17 @@ where
 #[cfg(test)]
 mod tests {
     use super::*;
+    use rstest::rstest;
     use strum::EnumIter;
 
+    #[rstest]
+    #[case::unreserved("AZaz09-_.~", "AZaz09-_.~")]
+    #[case::reserved("a b/c?d=e&f", "a%20b%2Fc%3Fd%3De%26f")]
+    #[case::unicode("🧡", "%F0%9F%A7%A1")]
+    fn test_uri_encode(#[case] value: &str, #[case] expected: &str) {
+        assert_eq!(uri_encode(value), expected);
+    }
+
     #[derive(Clone, Debug, PartialEq, EnumIter)]
     enum A {
         One,